@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use super::chunk::{Chunk, INSTRUCTION_SIZE, OPERAND_SIZE};
+use super::data_structures::{obj_big_int::ObjBigInt, obj_decimal::ObjDecimal, obj_rational::ObjRational, DynType};
+use super::native::NATIVES;
+use super::opcode::{OpCode, OPCODES};
+use super::value::Value;
+
+/* ASSEMBLY SYNTAX
+
+ The assembler turns a textual listing into a Chunk, so tests can be written for bytecode the compiler
+ doesn't emit. It reads what listing in chunk.rs writes, one item per line:
+
+  === name ===                header, ignored
+  .line 3                     the following instructions are on line 3, until the next .line
+  end:                        a label for the offset of the next instruction
+  [OFFSET] OPCODE [| OPERAND] an instruction, OFFSET is checked against the offset it ends up at
+  OPCODE -> TARGET            a jump, the '|' can be left out
+
+ Everything after a ';' outside of a string is a comment. The operand depends on the opcode:
+
+  Constant, AddConstant, SubtractConstant   Null, Boolean(true), Integer(1), Float(1.5), String("a\n"),
+                                            BigInt(-10000000000000000000000), Decimal(1.50), Rational(1/3)
+  Interpolate                               the number of parts
+  CallNative                                the name of the native
+  Jump and the JumpIfFalse variants         [OFFSET] -> TARGET, TARGET is a label or the offset of an instruction
+
+ Jumps only go forward, OFFSET is the number of bytes skipped and is checked against TARGET if both are given.
+ The chunk isn't verified, so it is possible to write invalid chunks on purpose; run verify before executing it.
+
+ */
+
+enum Target {
+    Offset(usize),
+    Label(String)
+}
+
+struct PendingJump {
+    row: usize,
+    index: usize, // the offset of the jump instruction
+    relative: Option<u32>,
+    target: Option<Target>
+}
+
+pub fn assemble(source: &str) -> Result<Chunk, String> {
+    let mut chunk: Chunk = Chunk::new(source.len().max(INSTRUCTION_SIZE));
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut starts: Vec<usize> = Vec::new();
+    let mut jumps: Vec<PendingJump> = Vec::new();
+    let mut line: u32 = 1;
+
+    for (row, text) in source.lines().enumerate().map(|(row, text)| (row + 1, strip_comment(text).trim())) {
+        let error = |message: String| format!("Assembly line {row}: {message}");
+
+        if text.is_empty() || (text.starts_with("===") && text.ends_with("===")) {
+            continue;
+        }
+        if let Some(number) = text.strip_prefix(".line") {
+            line = number.trim().parse::<u32>().map_err(|_| error(format!("Invalid line number '{}'", number.trim())))?;
+            continue;
+        }
+        if let Some(label) = text.strip_suffix(':') {
+            if !is_label(label) {
+                return Err(error(format!("Invalid label '{label}'")));
+            }
+            if labels.insert(label, chunk.len()).is_some() {
+                return Err(error(format!("The label '{label}' is defined twice")));
+            }
+            continue;
+        }
+
+        // the '|' may be left out before the arrow of a jump
+        let (instruction, operand): (&str, Option<&str>) = match text.split_once('|') {
+            Some((instruction, operand)) => (instruction.trim(), Some(operand.trim())),
+            None => match text.find("->") {
+                Some(arrow) => (text[..arrow].trim(), Some(&text[arrow..])),
+                None => (text, None)
+            }
+        };
+        let (offset, name): (Option<&str>, &str) = match instruction.split_once(char::is_whitespace) {
+            Some((offset, name)) => (Some(offset), name.trim()),
+            None => (None, instruction)
+        };
+
+        if let Some(offset) = offset {
+            let expected: usize = offset.parse::<usize>().map_err(|_| error(format!("Invalid offset '{offset}'")))?;
+            if expected != chunk.len() {
+                return Err(error(format!("The instruction is at offset {:06}, not {offset}", chunk.len())));
+            }
+        }
+        let opcode: OpCode = *OPCODES.iter().find(|opcode| format!("{opcode:?}") == name)
+            .ok_or_else(|| error(format!("Unknown opcode '{name}'")))?;
+
+        starts.push(chunk.len());
+        match (opcode, operand) {
+            (OpCode::Constant | OpCode::AddConstant | OpCode::SubtractConstant, Some(operand)) => {
+                chunk.add_opcode_with_value(opcode, parse_constant(operand).map_err(error)?, line);
+            },
+            (OpCode::Interpolate, Some(operand)) => {
+                let parts: u32 = operand.parse::<u32>().map_err(|_| error(format!("Invalid number of parts '{operand}'")))?;
+                chunk.add_opcode_with_operand(opcode, parts, line);
+            },
+            (OpCode::CallNative, Some(operand)) => {
+                let native: usize = NATIVES.iter().position(|native| native.name == operand)
+                    .ok_or_else(|| error(format!("Unknown native '{operand}'")))?;
+                chunk.add_opcode_with_operand(opcode, native as u32, line);
+            },
+            (OpCode::Jump | OpCode::JumpIfFalse | OpCode::EqualJumpIfFalse | OpCode::GreaterJumpIfFalse | OpCode::LessJumpIfFalse, Some(operand)) => {
+                jumps.push(parse_jump(operand, row, chunk.len()).map_err(error)?);
+                chunk.add_opcode_with_operand(opcode, 0, line);
+            },
+            (OpCode::Constant | OpCode::AddConstant | OpCode::SubtractConstant | OpCode::Interpolate | OpCode::CallNative, None) |
+            (OpCode::Jump | OpCode::JumpIfFalse | OpCode::EqualJumpIfFalse | OpCode::GreaterJumpIfFalse | OpCode::LessJumpIfFalse, None) => {
+                return Err(error(format!("{opcode:?} needs an operand")));
+            },
+            (_, Some(_)) => return Err(error(format!("{opcode:?} doesn't take an operand"))),
+            (_, None) => chunk.add_opcode(opcode, line)
+        }
+    }
+
+    starts.push(chunk.len());
+    for jump in jumps {
+        let end: usize = jump.index + INSTRUCTION_SIZE + OPERAND_SIZE;
+        let target: usize = match (&jump.target, jump.relative) {
+            (Some(Target::Label(label)), _) => *labels.get(label.as_str())
+                .ok_or_else(|| format!("Assembly line {}: Undefined label '{label}'", jump.row))?,
+            (Some(Target::Offset(offset)), _) => *offset,
+            (None, Some(relative)) => end + relative as usize,
+            (None, None) => unreachable!("parse_jump requires a target or an offset")
+        };
+
+        if target < end {
+            return Err(format!("Assembly line {}: The jump to {target:06} goes backwards, jumps only go forward", jump.row));
+        }
+        if starts.binary_search(&target).is_err() {
+            return Err(format!("Assembly line {}: The jump target {target:06} isn't the start of an instruction", jump.row));
+        }
+        let offset: u32 = (target - end) as u32;
+        if jump.relative.is_some_and(|relative| relative != offset) {
+            return Err(format!("Assembly line {}: The offset {} doesn't lead to {target:06}", jump.row, jump.relative.unwrap()));
+        }
+        chunk.patch_operand(jump.index + INSTRUCTION_SIZE, offset);
+    }
+
+    Ok(chunk)
+}
+
+fn strip_comment(text: &str) -> &str {
+    let (mut in_string, mut escaped) = (false, false);
+    for (index, character) in text.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn is_label(label: &str) -> bool {
+    label.starts_with(|character: char| character.is_alphabetic() || character == '_')
+        && label.chars().all(|character| character.is_alphanumeric() || character == '_')
+}
+
+// '26 -> 000056', '-> end' or '26'
+fn parse_jump(operand: &str, row: usize, index: usize) -> Result<PendingJump, String> {
+    let (relative, target): (&str, Option<&str>) = match operand.split_once("->") {
+        Some((relative, target)) => (relative.trim(), Some(target.trim())),
+        None => (operand, None)
+    };
+
+    let relative: Option<u32> = match relative {
+        "" => None,
+        _ => Some(relative.parse::<u32>().map_err(|_| format!("Invalid jump offset '{relative}'"))?)
+    };
+    let target: Option<Target> = match target {
+        Some(target) if target.starts_with(|character: char| character.is_ascii_digit()) => {
+            Some(Target::Offset(target.parse::<usize>().map_err(|_| format!("Invalid jump target '{target}'"))?))
+        },
+        Some(target) if is_label(target) => Some(Target::Label(target.to_string())),
+        Some(target) => return Err(format!("Invalid jump target '{target}'")),
+        None if relative.is_none() => return Err("The jump needs a target".to_string()),
+        None => None
+    };
+
+    Ok(PendingJump { row, index, relative, target })
+}
+
+fn parse_constant(text: &str) -> Result<Value, String> {
+    if text == "Null" {
+        return Ok(Value::Null);
+    }
+
+    let invalid = || format!("Invalid constant '{text}'");
+    let (kind, inner): (&str, &str) = text.strip_suffix(')').and_then(|text| text.split_once('(')).ok_or_else(invalid)?;
+
+    match kind {
+        "Boolean" => match inner {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => Err(invalid())
+        },
+        "Integer" => inner.parse::<i64>().map(Value::Integer).map_err(|_| invalid()),
+        "Float" => inner.parse::<f64>().map(Value::Float).map_err(|_| invalid()),
+        "String" => Ok(Value::Object(DynType::from(unescape(inner).ok_or_else(invalid)?))),
+        // kept as a BigInt even if it fits into an Integer, so every listing can be read back
+        "BigInt" => Ok(Value::Object(DynType::from(parse_big_int(inner).ok_or_else(invalid)?))),
+        "Decimal" => ObjDecimal::parse(inner).map(Value::from).map_err(|_| invalid()),
+        "Rational" => {
+            let (numerator, denominator): (&str, &str) = inner.split_once('/').ok_or_else(invalid)?;
+            let numerator: ObjBigInt = parse_big_int(numerator).ok_or_else(invalid)?;
+            let denominator: ObjBigInt = parse_big_int(denominator).ok_or_else(invalid)?;
+            ObjRational::new(numerator, denominator).map(Value::from)
+        },
+        _ => Err(invalid())
+    }
+}
+
+fn parse_big_int(text: &str) -> Option<ObjBigInt> {
+    match text.strip_prefix('-') {
+        Some(digits) => ObjBigInt::parse(digits, 10).map(|big| big.neg()),
+        None => ObjBigInt::parse(text, 10)
+    }
+}
+
+// Reverses the escapes of {:?} on a str, the quotes included
+fn unescape(text: &str) -> Option<String> {
+    let text: &str = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result: String = String::with_capacity(text.len());
+    let mut characters = text.chars();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        match characters.next()? {
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            'r' => result.push('\r'),
+            '0' => result.push('\0'),
+            '\\' => result.push('\\'),
+            '"' => result.push('"'),
+            '\'' => result.push('\''),
+            'u' => {
+                let code: String = characters.by_ref().skip(1).take_while(|character| *character != '}').collect();
+                result.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            },
+            _ => return None
+        }
+    }
+    Some(result)
+}
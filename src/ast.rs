@@ -0,0 +1,113 @@
+use std::ops::Range;
+
+use super::token::Token;
+use super::value::Value;
+
+/* ABSTRACT SYNTAX TREE
+
+ The parser turns the tokens into a tree of expressions, the resolver checks it and fills in what the
+ parser can't know (e.g. which native a call refers to) and the generator writes it into a Chunk:
+
+  TOKENS -> Parser -> Program -> resolve -> Generator -> CHUNK
+
+ Every node keeps the span of source it was parsed from, so tools like linters, formatters or a language
+ server can work with the tree instead of the bytecode. The tree is exported by the library, see lib.rs.
+
+ */
+
+// The bytes start..end of the source, line is the line on which the span starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: u32,
+    pub end: u32,
+    pub line: u32
+}
+
+impl Span {
+    pub fn from_token(token: &Token) -> Self {
+        Self { start: token.range.0, end: token.range.1, line: token.line }
+    }
+
+    // From the start of self to the end of other
+    pub fn to(self, other: Span) -> Self {
+        Self { start: self.start, end: other.end, line: self.line }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub expression: Expr,
+    pub end: Span // the end of the file
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Value),
+    // the string parts and embedded expressions of "a ${b} c" in order, empty parts are left out
+    Interpolation(Vec<Expr>),
+    Grouping(Box<Expr>),
+    Unary { operator: UnaryOperator, operator_span: Span, operand: Box<Expr> },
+    Binary { operator: BinaryOperator, operator_span: Span, left: Box<Expr>, right: Box<Expr> },
+    // native is the index into NATIVES, it is set by the resolver
+    Call { name: String, callee: Span, arguments: Vec<Expr>, native: Option<usize> }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+    BitNot
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    FloorDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight
+}
+
+// span is None if the error isn't caused by a specific token, line is always set
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Option<Span>,
+    pub line: u32
+}
+
+impl CompileError {
+    pub fn at(span: Span, message: &str) -> Self {
+        Self { message: message.to_string(), span: Some(span), line: span.line }
+    }
+
+    pub fn describe(&self, source: &str) -> String {
+        match self.span {
+            Some(span) => format!("At line {}: '{}' -> {}", self.line, &source[span.range()], self.message),
+            None => format!("At line {}: {}", self.line, self.message)
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs::read_to_string;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use super::chunk::Chunk;
+use super::common::{SharedData, DEFAULT_STACK_CAPACITY};
+use super::compiler::Compiler;
+use super::lexer::Lexer;
+use super::vm::{StackValue, VM};
+
+/* BENCHMARKS
+
+ Runs the programs in src/benches with 'eos bench [runs]'. Every program is compiled once and executed runs times,
+ so the numbers only contain the time spent in the VM. Without functions and loops in the language the programs are
+ long expressions: integer and float arithmetic, comparisons, string concatenation and native calls. Run them once with and once without the feature
+ "nan-boxing" to compare both value representations:
+
+  cargo run --release -- bench
+  cargo run --release --features nan-boxing -- bench
+
+ */
+
+pub const DEFAULT_RUNS: usize = 1000;
+
+const BENCHMARKS: [&str; 5] = ["integer_arithmetic", "float_arithmetic", "comparison", "string_concat", "mixed_operations"];
+
+#[cfg(not(feature = "nan-boxing"))]
+const REPRESENTATION: &str = "enum";
+#[cfg(feature = "nan-boxing")]
+const REPRESENTATION: &str = "nan-boxing";
+
+pub fn run_benchmarks(runs: usize) {
+    println!("Value representation: {REPRESENTATION} ({} bytes per stack value), {runs} runs each", size_of::<StackValue>());
+    println!("{:<24} {:>12} {:>12}", "benchmark", "total", "per run");
+
+    for name in BENCHMARKS {
+        match measure(&format!("src/benches/{name}.eos"), runs) {
+            Ok(total) => println!("{name:<24} {:>12.2?} {:>12.2?}", total, total / runs.max(1) as u32),
+            Err(err) => eprintln!("{name:<24} {err}")
+        }
+    }
+}
+
+fn measure(path: &str, runs: usize) -> Result<Duration, String> {
+    let code: String = read_to_string(path).map_err(|err| format!("Can not read {path}: {err}"))?;
+
+    let mut lexer: Lexer = Lexer::new(&code);
+    let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+    let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(&code), SharedData::new(&chunk));
+    compiler.compile().ok_or_else(|| format!("{path} does not compile"))?;
+
+    let start: Instant = Instant::now();
+    for _ in 0..runs {
+        VM::new(&chunk).run().ok_or_else(|| format!("{path} failed at runtime"))?;
+    }
+    Ok(start.elapsed())
+}
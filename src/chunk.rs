@@ -1,7 +1,10 @@
 use std::alloc::{alloc, dealloc, Layout};
-use std::mem::size_of;
-use std::ptr::{copy_nonoverlapping, read, write};
+use std::fmt::Write;
+use std::mem::{size_of, ManuallyDrop};
+use std::ptr::{copy_nonoverlapping, read_unaligned, write, write_unaligned};
 
+use super::data_structures::DynType;
+use super::native::NATIVES;
 use super::opcode::OpCode::{self, *};
 use super::value::Value;
 
@@ -9,6 +12,7 @@ const OPCODE_SIZE: usize = size_of::<OpCode>();
 pub const VALUE_SIZE: usize = size_of::<Value>();
 const LINE_SIZE: usize = size_of::<u32>();
 pub const INSTRUCTION_SIZE: usize = OPCODE_SIZE + LINE_SIZE;
+pub const OPERAND_SIZE: usize = size_of::<u32>();
 
 
 /* MEMORY LAYOUT
@@ -24,6 +28,11 @@ pub const INSTRUCTION_SIZE: usize = OPCODE_SIZE + LINE_SIZE;
 
   OPCODE LINE
 
+ Some OpCodes carry an operand (e.g. the number of parts for OpCode::Interpolate). The operand is stored right after the line.
+ Jumps store the number of bytes to skip, counted from the end of the jump instruction.
+
+  OPCODE LINE OPERAND
+
  In practice the memory layout could look something like this
 
   OPCODE LINE OPCODE LINE CONSTANT LINE VALUE OPCODE CONSTANT LINE VALUE
@@ -35,13 +44,14 @@ pub const INSTRUCTION_SIZE: usize = OPCODE_SIZE + LINE_SIZE;
 pub struct Chunk {
     data: *const u8,
     size: usize,
-    capacity: usize
+    capacity: usize,
+    constants: Vec<usize> // offsets of the stored values, the chunk owns them and drops them with itself
 }
 
 impl Chunk {
     pub fn new(capacity: usize) -> Self {
         unsafe {
-            Self { data: alloc(Layout::array::<u8>(capacity).unwrap()), size: 0, capacity }
+            Self { data: alloc(Layout::array::<u8>(capacity).unwrap()), size: 0, capacity, constants: Vec::new() }
         }
     }
 
@@ -53,49 +63,113 @@ impl Chunk {
 
         unsafe {
             write(self.data.add(self.size) as *mut OpCode, instruction);
-            write(self.data.add(self.size + OPCODE_SIZE) as *mut u32, line);
+            write_unaligned(self.data.add(self.size + OPCODE_SIZE) as *mut u32, line);
         }
 
         self.size += INSTRUCTION_SIZE;
     }
 
+    pub fn add_opcode_with_operand(&mut self, instruction: OpCode, operand: u32, line: u32) {
+        self.add_opcode(instruction, line);
+
+        while self.size + OPERAND_SIZE > self.capacity {
+            self.resize();
+        }
+
+        unsafe {
+            write_unaligned(self.data.add(self.size) as *mut u32, operand);
+        }
+
+        self.size += OPERAND_SIZE;
+    }
+
     pub fn add_value(&mut self, value: Value, line: u32) {
-        self.add_opcode(OpCode::Constant, line);
+        self.add_opcode_with_value(OpCode::Constant, value, line);
+    }
+
+    // Superinstructions like OpCode::AddConstant store their value the same way as OpCode::Constant
+    pub fn add_opcode_with_value(&mut self, instruction: OpCode, value: Value, line: u32) {
+        self.add_opcode(instruction, line);
 
         while self.size + VALUE_SIZE > self.capacity {
             self.resize();
         }
 
         unsafe {
-            write(self.data.add(self.size) as *mut Value, value);
+            write_unaligned(self.data.add(self.size) as *mut Value, value);
         }
 
+        self.constants.push(self.size);
         self.size += VALUE_SIZE;
     }
 
+    // Overwrites an operand that has already been written, e.g. the offset of a jump once its target is known
+    pub fn patch_operand(&mut self, index: usize, operand: u32) {
+        assert!(index + OPERAND_SIZE <= self.size, "OPERAND OUT OF BOUNDS");
+
+        unsafe {
+            write_unaligned(self.data.add(index) as *mut u32, operand);
+        }
+    }
+
+    // Removes every instruction from index on, e.g. the operands of an operation the compiler folded
+    pub fn truncate(&mut self, index: usize) {
+        if index >= self.size {
+            return;
+        }
+
+        self.constants.retain(|offset| {
+            if *offset < index {
+                return true;
+            }
+            unsafe {
+                drop(read_unaligned(self.data.add(*offset) as *const Value));
+            }
+            false
+        });
+        self.size = index;
+    }
+
+    // The offsets of the values written by add_value and add_opcode_with_value, in ascending order
+    pub fn constant_offsets(&self) -> &[usize] {
+        &self.constants
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
     pub fn read_opcode(&self, index: usize) -> Option<(OpCode, u32)> {
         if index + INSTRUCTION_SIZE > self.size {
             return None;
         }
 
-        unsafe {
-            let opcde: OpCode = read(self.data.add(index) as *const OpCode);
-            let line: u32 = read(self.data.add(index + OPCODE_SIZE) as *const u32);
+        let code: &[u8] = self.code();
+        Some((decode_opcode(code, index).ok()?, decode_line(code, index)))
+    }
 
-            Some((opcde, line))
+    pub fn read_operand(&self, index: usize) -> Option<u32> {
+        if index + OPERAND_SIZE > self.size {
+            return None;
         }
+
+        Some(decode_operand(self.code(), index))
     }
 
     pub fn read_value(&self, index: usize) -> Option<(Value, u32)> {
-        if index + VALUE_SIZE >= self.size {
+        if index + VALUE_SIZE > self.size || self.constants.binary_search(&index).is_err() {
             return None;
         }
 
-        unsafe {
-            let value: Value = read(self.data.add(index) as *const Value);
-            let line: u32 = read(self.data.add(index - LINE_SIZE) as *const u32);
+        // the chunk keeps its value, the caller gets a clone so that the value can be read any number of times
+        let value: Value = unsafe { decode_value(self.code(), index) };
+        Some((value, decode_line(self.code(), index - INSTRUCTION_SIZE)))
+    }
 
-            Some((value, line))
+    // The raw instructions, the VM decodes them with the functions below instead of the checked read_* methods
+    pub fn code(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self.data, self.size)
         }
     }
 
@@ -121,38 +195,107 @@ impl Drop for Chunk {
         let old_layout: Layout = Layout::array::<u8>(self.capacity).unwrap();
 
         unsafe {
+            for offset in &self.constants {
+                drop(read_unaligned(self.data.add(*offset) as *const Value));
+            }
             dealloc(self.data.cast_mut(), old_layout);
         }
     }
 }
 
-pub fn print_chunk(chunk: &Chunk, name: &str){
-    println!("=== {name} ===");
+// The number of bytes an instruction takes up, including its operand or value
+pub fn instruction_size(opcode: OpCode) -> usize {
+    match opcode {
+        Constant | AddConstant | SubtractConstant => INSTRUCTION_SIZE + VALUE_SIZE,
+        Interpolate | CallNative | Jump | JumpIfFalse | EqualJumpIfFalse | GreaterJumpIfFalse | LessJumpIfFalse => INSTRUCTION_SIZE + OPERAND_SIZE,
+        _ => INSTRUCTION_SIZE
+    }
+}
 
-    let mut index: usize = 0;
+pub fn is_jump(opcode: OpCode) -> bool {
+    matches!(opcode, Jump | JumpIfFalse | EqualJumpIfFalse | GreaterJumpIfFalse | LessJumpIfFalse)
+}
 
-    while let Some(buffer) = chunk.read_opcode(index) {
-        match buffer.0 {
-            Return => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Negate => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Equal => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Greater => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Less => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Add => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Subtract => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Multiply => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Divide => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Print => {println!("{index:06} {:?}", buffer.0); index += INSTRUCTION_SIZE},
-            Constant => {
-                print!("{index:06} {:?}", buffer.0);
-                index += INSTRUCTION_SIZE;
-                let value: &(Value, u32) = &chunk.read_value(index).unwrap();
-                match &value.0 {
-                    Value::Object(object) => println!(" | {}", object),
-                    _ => println!(" | {:?}", value.0)
-                }
-                index += VALUE_SIZE;
-            }
+// The offset a jump at index lands on, its operand is counted from the end of the jump
+pub fn jump_target(code: &[u8], index: usize) -> usize {
+    index + INSTRUCTION_SIZE + OPERAND_SIZE + decode_operand(code, index + INSTRUCTION_SIZE) as usize
+}
+
+// Fails if the byte at index is no opcode, e.g. because a jump lands in the middle of an instruction
+pub fn decode_opcode(code: &[u8], index: usize) -> Result<OpCode, String> {
+    OpCode::try_from(code[index])
+}
+
+pub fn decode_line(code: &[u8], index: usize) -> u32 {
+    u32::from_ne_bytes(code[index + OPCODE_SIZE..index + INSTRUCTION_SIZE].try_into().unwrap())
+}
+
+pub fn decode_operand(code: &[u8], index: usize) -> u32 {
+    u32::from_ne_bytes(code[index..index + OPERAND_SIZE].try_into().unwrap())
+}
+
+// The chunk keeps the value and the caller gets a clone, Chunk::read_value is the checked version.
+//
+// # Safety
+// index has to be one of the constant_offsets of the chunk that code belongs to, any other bytes are read as a Value
+// and cloning them follows whatever pointer they happen to contain
+pub unsafe fn decode_value(code: &[u8], index: usize) -> Value {
+    let bytes: &[u8] = &code[index..index + VALUE_SIZE];
+    unsafe {
+        let stored: ManuallyDrop<Value> = ManuallyDrop::new(read_unaligned(bytes.as_ptr() as *const Value));
+        Value::clone(&stored)
+    }
+}
+
+// The chunk in the syntax of the assembler, assemble(&listing(chunk, name)) gives back the same chunk
+pub fn listing(chunk: &Chunk, name: &str) -> String {
+    let mut output: String = format!("=== {name} ===\n");
+    let mut last_line: Option<u32> = None;
+
+    let mut index: usize = 0;
+    while let Some((opcode, line)) = chunk.read_opcode(index) {
+        if last_line != Some(line) {
+            let _ = writeln!(output, ".line {line}");
+            last_line = Some(line);
         }
+
+        let _ = match format_operand(chunk, index) {
+            Some(operand) => writeln!(output, "{index:06} {opcode:?} | {operand}"),
+            None => writeln!(output, "{index:06} {opcode:?}")
+        };
+        index += instruction_size(opcode);
+    }
+    output
+}
+
+// The operand or value of the instruction at index as the assembler reads it, None if it has neither
+pub fn format_operand(chunk: &Chunk, index: usize) -> Option<String> {
+    let code: &[u8] = chunk.code();
+    let opcode: OpCode = decode_opcode(code, index).ok()?;
+    let operand: usize = index + INSTRUCTION_SIZE;
+
+    match opcode {
+        Constant | AddConstant | SubtractConstant => chunk.read_value(operand).map(|(value, _)| format_constant(&value)),
+        Interpolate => Some(decode_operand(code, operand).to_string()),
+        CallNative => {
+            let native: usize = decode_operand(code, operand) as usize;
+            Some(NATIVES.get(native).map_or_else(|| format!("<unknown native {native}>"), |native| native.name.to_string()))
+        },
+        _ if is_jump(opcode) => Some(format!("{} -> {:06}", decode_operand(code, operand), jump_target(code, index))),
+        _ => None
+    }
+}
+
+// A constant with its type, strings are quoted and escaped so every value can be read back
+pub fn format_constant(value: &Value) -> String {
+    match value {
+        Value::Null => "Null".to_string(),
+        Value::Boolean(boolean) => format!("Boolean({boolean})"),
+        Value::Integer(integer) => format!("Integer({integer})"),
+        Value::Float(float) => format!("Float({float:?})"),
+        Value::Object(DynType::Text(text)) => format!("String({:?})", text.get_data()),
+        Value::Object(DynType::BigInt(big)) => format!("BigInt({big})"),
+        Value::Object(DynType::Decimal(decimal)) => format!("Decimal({decimal})"),
+        Value::Object(DynType::Rational(rational)) => format!("Rational({}/{})", rational.numerator(), rational.denominator())
     }
 }
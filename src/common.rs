@@ -1,6 +1,5 @@
 use std::ptr;
 
-pub static DEBUG_BYTECODE: bool = false;
 pub const DEFAULT_STACK_CAPACITY: usize = 1024;
 pub static ENABLE_TESTING: bool = true;
 
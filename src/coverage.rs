@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::chunk::{decode_opcode, instruction_size, is_jump, jump_target, Chunk};
+use super::opcode::OpCode;
+use super::vm::{Status, VM};
+
+/* COVERAGE
+
+ Coverage::run executes a VM with run_until and records how often every offset of the chunk runs. Afterwards
+ the offsets are mapped to the lines of the source through the line of each instruction: a line is hit as
+ often as its most executed instruction. Every conditional jump is a branch point with two branches, taken
+ (0) and not taken (1), which are told apart by the instruction that runs after the jump.
+
+ Runs add up per file, merge adds up whole coverages and parse_lcov reads the output of lcov back, so the
+ coverage of separate processes can be merged as well. lcov writes the tracefile format of LCOV:
+
+  SF:<path>  BRDA:<line>,<jump offset>,<branch>,<taken or ->  BRF BRH  DA:<line>,<hits>  LF LH  end_of_record
+
+ Unconditional jumps always go the same way and aren't branches.
+
+ */
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileCoverage {
+    pub lines: BTreeMap<u32, u64>,
+    pub branches: BTreeMap<(u32, usize, u8), u64> // line, offset of the jump and branch
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Coverage {
+    files: BTreeMap<String, FileCoverage>
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(&self, path: &str) -> Option<&FileCoverage> {
+        self.files.get(path)
+    }
+
+    // Runs the VM to the end and adds what ran to the coverage of path, None if it fails like VM::run
+    pub fn run(&mut self, path: &str, vm: &mut VM) -> Option<()> {
+        let chunk: &Chunk = vm.get_chunk();
+        let code: &[u8] = chunk.code();
+        let mut hits: Vec<u64> = vec![0; code.len()];
+        let mut taken: BTreeMap<usize, (u64, u64)> = BTreeMap::new();
+        let mut previous: Option<usize> = None;
+
+        let mut branch = |jump: Option<usize>, next: usize| {
+            if let Some(jump) = jump.filter(|jump| decode_opcode(code, *jump).is_ok_and(is_branch)) {
+                let counts: &mut (u64, u64) = taken.entry(jump).or_default();
+                match next == jump_target(code, jump) {
+                    true => counts.0 += 1,
+                    false => counts.1 += 1
+                }
+            }
+        };
+
+        let status: Option<Status> = vm.run_until(|ip| {
+            hits[ip] += 1;
+            branch(previous.replace(ip), ip);
+            false
+        });
+        // the last instruction only went somewhere if the program didn't fail
+        if status.is_some() {
+            branch(previous, code.len());
+        }
+
+        let mut run: FileCoverage = FileCoverage::default();
+        let mut index: usize = 0;
+        while let Some((opcode, line)) = chunk.read_opcode(index) {
+            let count: &mut u64 = run.lines.entry(line).or_default();
+            *count = (*count).max(hits[index]);
+
+            if is_branch(opcode) {
+                let (jumped, fell_through): (u64, u64) = taken.get(&index).copied().unwrap_or_default();
+                run.branches.insert((line, index, 0), jumped);
+                run.branches.insert((line, index, 1), fell_through);
+            }
+            index += instruction_size(opcode);
+        }
+
+        self.merge(&Coverage { files: BTreeMap::from([(path.to_string(), run)]) });
+        status.map(|_| ())
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (path, other) in &other.files {
+            let file: &mut FileCoverage = self.files.entry(path.clone()).or_default();
+            for (line, hits) in &other.lines {
+                *file.lines.entry(*line).or_default() += hits;
+            }
+            for (branch, hits) in &other.branches {
+                *file.branches.entry(*branch).or_default() += hits;
+            }
+        }
+    }
+
+    pub fn lcov(&self) -> String {
+        let mut output: String = String::new();
+
+        for (path, file) in &self.files {
+            let _ = writeln!(output, "TN:\nSF:{path}");
+            for ((line, block, branch), hits) in &file.branches {
+                // '-' marks a branch point that never ran
+                let _ = match file.block_ran(*block) {
+                    true => writeln!(output, "BRDA:{line},{block},{branch},{hits}"),
+                    false => writeln!(output, "BRDA:{line},{block},{branch},-")
+                };
+            }
+            let _ = writeln!(output, "BRF:{}\nBRH:{}", file.branches.len(), file.branches.values().filter(|hits| **hits > 0).count());
+            for (line, hits) in &file.lines {
+                let _ = writeln!(output, "DA:{line},{hits}");
+            }
+            let _ = writeln!(output, "LF:{}\nLH:{}\nend_of_record", file.lines.len(), file.lines.values().filter(|hits| **hits > 0).count());
+        }
+        output
+    }
+
+    pub fn parse_lcov(text: &str) -> Result<Coverage, String> {
+        let mut coverage: Coverage = Coverage::new();
+        let mut current: Option<(String, FileCoverage)> = None;
+
+        for (row, record) in text.lines().enumerate().map(|(row, record)| (row + 1, record.trim())) {
+            let invalid = || format!("Invalid record '{record}' on line {row}");
+            let (kind, data): (&str, &str) = record.split_once(':').unwrap_or((record, ""));
+
+            match (kind, current.as_mut()) {
+                ("SF", _) => current = Some((data.to_string(), FileCoverage::default())),
+                ("DA", Some((_, file))) => {
+                    let mut fields = data.split(',');
+                    let line: u32 = fields.next().and_then(|line| line.parse().ok()).ok_or_else(invalid)?;
+                    let hits: u64 = fields.next().and_then(|hits| hits.parse().ok()).ok_or_else(invalid)?;
+                    *file.lines.entry(line).or_default() += hits;
+                },
+                ("BRDA", Some((_, file))) => {
+                    let fields: Vec<&str> = data.split(',').collect();
+                    let [line, block, branch, hits] = fields[..] else {
+                        return Err(invalid());
+                    };
+                    let key: (u32, usize, u8) = (line.parse().map_err(|_| invalid())?, block.parse().map_err(|_| invalid())?, branch.parse().map_err(|_| invalid())?);
+                    *file.branches.entry(key).or_default() += match hits {
+                        "-" => 0,
+                        hits => hits.parse::<u64>().map_err(|_| invalid())?
+                    };
+                },
+                ("end_of_record", Some(_)) => {
+                    let (path, file) = current.take().unwrap();
+                    coverage.merge(&Coverage { files: BTreeMap::from([(path, file)]) });
+                },
+                // the summaries are computed again and the other records aren't needed
+                ("TN" | "BRF" | "BRH" | "LF" | "LH" | "FN" | "FNDA" | "FNF" | "FNH" | "", _) => {},
+                _ => return Err(invalid())
+            }
+        }
+
+        match current {
+            Some((path, _)) => Err(format!("The record of {path} has no end_of_record")),
+            None => Ok(coverage)
+        }
+    }
+
+    // A table with the covered lines and branches of every file and the lines that never ran
+    pub fn summary(&self) -> String {
+        let mut output: String = format!("{:<32} {:>16} {:>16}  Missed lines\n", "File", "Lines", "Branches");
+        let (mut lines, mut branches): ((usize, usize), (usize, usize)) = ((0, 0), (0, 0));
+
+        for (path, file) in &self.files {
+            let file_lines: (usize, usize) = (file.lines.values().filter(|hits| **hits > 0).count(), file.lines.len());
+            let file_branches: (usize, usize) = (file.branches.values().filter(|hits| **hits > 0).count(), file.branches.len());
+            lines = (lines.0 + file_lines.0, lines.1 + file_lines.1);
+            branches = (branches.0 + file_branches.0, branches.1 + file_branches.1);
+
+            let missed: Vec<u32> = file.lines.iter().filter(|(_, hits)| **hits == 0).map(|(line, _)| *line).collect();
+            let row: String = format!("{path:<32} {:>16} {:>16}  {}", ratio(file_lines), ratio(file_branches), ranges(&missed));
+            let _ = writeln!(output, "{}", row.trim_end());
+        }
+        let _ = writeln!(output, "{:<32} {:>16} {:>16}", "Total", ratio(lines), ratio(branches));
+        output
+    }
+}
+
+impl FileCoverage {
+    fn block_ran(&self, block: usize) -> bool {
+        self.branches.iter().any(|((_, other, _), hits)| *other == block && *hits > 0)
+    }
+}
+
+fn is_branch(opcode: OpCode) -> bool {
+    is_jump(opcode) && opcode != OpCode::Jump
+}
+
+// '75.0% (3/4)', a file without branches has '-'
+fn ratio((covered, total): (usize, usize)) -> String {
+    match total {
+        0 => "-".to_string(),
+        _ => format!("{:.1}% ({covered}/{total})", covered as f64 * 100.0 / total as f64)
+    }
+}
+
+// 1, 3-5, 8
+fn ranges(lines: &[u32]) -> String {
+    let mut groups: Vec<(u32, u32)> = Vec::new();
+    for line in lines {
+        match groups.last_mut() {
+            Some((_, last)) if *last + 1 == *line => *last = *line,
+            _ => groups.push((*line, *line))
+        }
+    }
+    groups.iter()
+        .map(|(first, last)| if first == last { first.to_string() } else { format!("{first}-{last}") })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,238 @@
+use std::fs::read_to_string;
+use std::io::{self, BufRead, Write};
+
+use super::chunk::{format_constant, Chunk};
+use super::common::{SharedData, DEFAULT_STACK_CAPACITY};
+use super::compiler::Compiler;
+use super::debugger::{Debugger, Stop};
+use super::json::{read_message, write_message, Json};
+use super::lexer::Lexer;
+
+/* DEBUG ADAPTER PROTOCOL
+
+ serve speaks the Debug Adapter Protocol over stdin and stdout, so editors can debug .eos files with the
+ Debugger. A session goes through two phases:
+
+  initialize -> launch { program, stopOnEntry }                     compiles the program, sends 'initialized'
+  setBreakpoints, configurationDone, threads, stackTrace, scopes,
+  variables, continue, next, stepIn, stepOut, evaluate, disconnect   debug it
+
+ The program runs on the thread of the adapter, so it is always paused while a request is handled and pause
+ has nothing to do. There is one thread with one frame, the language has no functions yet. The scope 'Stack'
+ lists the values on the stack, 'Globals' is empty until the language has them. Lines start at 1.
+
+ A malformed message gets a failed response with request_seq 0 and the session goes on. A runtime error is
+ reported in an 'output' event with its line and message before the program exits with 1.
+
+ */
+
+const THREAD: u64 = 1;
+const FRAME: u64 = 1;
+const STACK_REFERENCE: u64 = 1;
+const GLOBALS_REFERENCE: u64 = 2;
+
+struct Connection<W: Write> {
+    output: W,
+    seq: u64
+}
+
+impl <W: Write> Connection<W> {
+    fn send(&mut self, kind: &str, mut members: Vec<(String, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        members.splice(0..0, [("seq".to_string(), Json::from(self.seq)), ("type".to_string(), Json::from(kind))]);
+        write_message(&mut self.output, &Json::Object(members))
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send("response", vec![
+            ("request_seq".to_string(), request.get("seq").clone()),
+            ("success".to_string(), Json::Bool(true)),
+            ("command".to_string(), request.get("command").clone()),
+            ("body".to_string(), body)
+        ])
+    }
+
+    fn fail(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send("response", vec![
+            ("request_seq".to_string(), request.get("seq").clone()),
+            ("success".to_string(), Json::Bool(false)),
+            ("command".to_string(), request.get("command").clone()),
+            ("message".to_string(), Json::from(message))
+        ])
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send("event", vec![("event".to_string(), Json::from(event)), ("body".to_string(), body)])
+    }
+
+    // The next request, a malformed message is answered with an error and skipped
+    fn next_request(&mut self, input: &mut impl BufRead) -> io::Result<Option<Json>> {
+        loop {
+            match read_message(input) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => self.send("response", vec![
+                    ("request_seq".to_string(), Json::from(0u32)),
+                    ("success".to_string(), Json::Bool(false)),
+                    ("command".to_string(), Json::from("")),
+                    ("message".to_string(), Json::from(format!("Malformed message: {err}")))
+                ])?,
+                message => return message
+            }
+        }
+    }
+}
+
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut connection: Connection<_> = Connection { output, seq: 0 };
+
+    while let Some(request) = connection.next_request(&mut input)? {
+        match request.get("command").as_str().unwrap_or_default() {
+            "initialize" => connection.respond(&request, Json::object([
+                ("supportsConfigurationDoneRequest", Json::Bool(true)),
+                ("supportsSteppingGranularity", Json::Bool(true))
+            ]))?,
+            "launch" => {
+                let arguments: &Json = request.get("arguments");
+                let Some(path) = arguments.get("program").as_str() else {
+                    connection.fail(&request, "launch needs the path of the program")?;
+                    continue;
+                };
+                let (source, chunk): (String, Chunk) = match compile(path) {
+                    Ok(program) => program,
+                    Err(err) => {
+                        connection.fail(&request, &err)?;
+                        continue;
+                    }
+                };
+
+                connection.respond(&request, Json::Null)?;
+                connection.event("initialized", Json::Null)?;
+                let stop_on_entry: bool = arguments.get("stopOnEntry").as_bool().unwrap_or(false);
+                return debug(&mut input, &mut connection, path, &source, &chunk, stop_on_entry);
+            },
+            "disconnect" => return connection.respond(&request, Json::Null),
+            command => connection.fail(&request, &format!("'{command}' needs a launched program"))?
+        }
+    }
+    Ok(())
+}
+
+fn compile(path: &str) -> Result<(String, Chunk), String> {
+    let source: String = read_to_string(path).map_err(|err| format!("Can not read {path}: {err}"))?;
+    let mut lexer: Lexer = Lexer::new(&source);
+    let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+
+    Compiler::new(lexer.lexing(), SharedData::new(&source), SharedData::new(&chunk)).compile_with_errors()
+        .map_err(|errors| errors.first().map_or_else(|| format!("{path} does not compile"), |error| error.describe(&source)))?;
+    Ok((source, chunk))
+}
+
+fn debug<W: Write>(input: &mut impl BufRead, connection: &mut Connection<W>, path: &str, source: &str, chunk: &Chunk, stop_on_entry: bool) -> io::Result<()> {
+    let mut debugger: Debugger = Debugger::new(chunk, Some(source));
+
+    while let Some(request) = connection.next_request(input)? {
+        let arguments: &Json = request.get("arguments");
+
+        match request.get("command").as_str().unwrap_or_default() {
+            "setBreakpoints" => {
+                let lines: Vec<u32> = debugger.breakpoints().copied().collect();
+                for line in lines {
+                    debugger.clear_breakpoint(line);
+                }
+
+                let breakpoints: Vec<Json> = arguments.get("breakpoints").as_array().unwrap_or_default().iter().map(|breakpoint| {
+                    let line: u32 = breakpoint.get("line").as_u64().unwrap_or(0) as u32;
+                    match debugger.set_breakpoint(line) {
+                        Ok(()) => Json::object([("verified", Json::Bool(true)), ("line", Json::from(line))]),
+                        Err(err) => Json::object([("verified", Json::Bool(false)), ("line", Json::from(line)), ("message", Json::from(err))])
+                    }
+                }).collect();
+                connection.respond(&request, Json::object([("breakpoints", Json::from(breakpoints))]))?;
+            },
+            "configurationDone" => {
+                connection.respond(&request, Json::Null)?;
+                match stop_on_entry {
+                    true => connection.event("stopped", stopped("entry"))?,
+                    false => {
+                        let stop: Stop = debugger.resume_until_breakpoint();
+                        report(connection, &debugger, stop)?;
+                    }
+                }
+            },
+            "threads" => connection.respond(&request, Json::object([
+                ("threads", Json::from(vec![Json::object([("id", Json::from(THREAD)), ("name", Json::from("main"))])]))
+            ]))?,
+            "stackTrace" => {
+                let frames: Vec<Json> = debugger.location().map(|(_, line)| Json::object([
+                    ("id", Json::from(FRAME)),
+                    ("name", Json::from("main")),
+                    ("line", Json::from(line)),
+                    ("column", Json::from(1u32)),
+                    ("source", Json::object([("path", Json::from(path))]))
+                ])).into_iter().collect();
+                connection.respond(&request, Json::object([("totalFrames", Json::from(frames.len())), ("stackFrames", Json::from(frames))]))?;
+            },
+            "scopes" => connection.respond(&request, Json::object([("scopes", Json::from(vec![
+                Json::object([("name", Json::from("Stack")), ("variablesReference", Json::from(STACK_REFERENCE)), ("expensive", Json::Bool(false))]),
+                Json::object([("name", Json::from("Globals")), ("variablesReference", Json::from(GLOBALS_REFERENCE)), ("expensive", Json::Bool(false))])
+            ]))]))?,
+            "variables" => {
+                let variables: Vec<Json> = match arguments.get("variablesReference").as_u64() {
+                    Some(STACK_REFERENCE) => debugger.stack().iter().enumerate().map(|(index, value)| variable(&format!("[{index}]"), &format_constant(value))).collect(),
+                    _ => debugger.globals().iter().map(|(name, value)| variable(name, &format_constant(value))).collect()
+                };
+                connection.respond(&request, Json::object([("variables", Json::from(variables))]))?;
+            },
+            "continue" => {
+                connection.respond(&request, Json::object([("allThreadsContinued", Json::Bool(true))]))?;
+                let stop: Stop = debugger.resume_until_breakpoint();
+                report(connection, &debugger, stop)?;
+            },
+            "next" | "stepIn" | "stepOut" => {
+                connection.respond(&request, Json::Null)?;
+                let stop: Stop = match (request.get("command").as_str(), arguments.get("granularity").as_str()) {
+                    (_, Some("instruction")) => debugger.step_instruction(),
+                    (Some("next"), _) => debugger.step_over(),
+                    (Some("stepIn"), _) => debugger.step_line(),
+                    _ => debugger.step_out()
+                };
+                report(connection, &debugger, stop)?;
+            },
+            "pause" => connection.respond(&request, Json::Null)?,
+            "evaluate" => match debugger.evaluate(arguments.get("expression").as_str().unwrap_or_default()) {
+                Ok(value) => connection.respond(&request, Json::object([("result", Json::from(format_constant(&value))), ("variablesReference", Json::from(0u32))]))?,
+                Err(err) => connection.fail(&request, &err)?
+            },
+            "disconnect" => return connection.respond(&request, Json::Null),
+            command => connection.fail(&request, &format!("Unsupported request '{command}'"))?
+        }
+    }
+    Ok(())
+}
+
+fn report<W: Write>(connection: &mut Connection<W>, debugger: &Debugger, stop: Stop) -> io::Result<()> {
+    match stop {
+        Stop::Step => connection.event("stopped", stopped("step")),
+        Stop::Breakpoint(_) => connection.event("stopped", stopped("breakpoint")),
+        Stop::Finished => {
+            if let Some(result) = debugger.stack().last() {
+                connection.event("output", Json::object([("category", Json::from("stdout")), ("output", Json::from(format!("{result}\n")))]))?;
+            }
+            connection.event("exited", Json::object([("exitCode", Json::from(0u32))]))?;
+            connection.event("terminated", Json::Null)
+        },
+        Stop::Error(line) => {
+            let message: String = format!("Runtime error on line {line}: {}\n", debugger.error().unwrap_or_default());
+            connection.event("output", Json::object([("category", Json::from("stderr")), ("output", Json::from(message))]))?;
+            connection.event("exited", Json::object([("exitCode", Json::from(1u32))]))?;
+            connection.event("terminated", Json::Null)
+        }
+    }
+}
+
+fn stopped(reason: &str) -> Json {
+    Json::object([("reason", Json::from(reason)), ("threadId", Json::from(THREAD)), ("allThreadsStopped", Json::Bool(true))])
+}
+
+fn variable(name: &str, value: &str) -> Json {
+    Json::object([("name", Json::from(name)), ("value", Json::from(value)), ("variablesReference", Json::from(0u32))])
+}
@@ -1,25 +1,36 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::ptr::addr_of_mut;
 use std::rc::Rc;
 use std::sync::OnceLock;
 use super::value::Error;
 
 use DynType::*;
+use obj_big_int::ObjBigInt;
+use obj_decimal::ObjDecimal;
+use obj_rational::ObjRational;
 use obj_string::ObjString;
 
+pub mod obj_big_int;
+pub mod obj_decimal;
+pub mod obj_rational;
+
 
 // Static mutable global state for interned strings, initialized once.
 static mut INTERNED_STRINGS: OnceLock<HashMap<usize, Rc<ObjString>>> = OnceLock::new();
 
 pub fn initialize_interned_string() {
     unsafe {
-        INTERNED_STRINGS.get_or_init(|| HashMap::new());
+        (*addr_of_mut!(INTERNED_STRINGS)).get_or_init(HashMap::new);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DynType {
     Text(Rc<ObjString>),
+    BigInt(Rc<ObjBigInt>),
+    Decimal(Rc<ObjDecimal>),
+    Rational(Rc<ObjRational>),
 }
 
 impl DynType {
@@ -33,7 +44,7 @@ impl DynType {
                 let (a, b) = (a.as_ref(), b.as_ref());
                 Ok(Text(Rc::new(a.add(b))))
             },
-            //_ => Err(format!("{:?} and {:?} can not be added", self, other))
+            _ => Err(format!("{:?} and {:?} can not be added", self, other))
         }
     }
 }
@@ -43,7 +54,7 @@ impl From<String> for DynType {
         let hash: usize = ObjString::compute_hash(&value);
 
         unsafe {
-            let interned_string: &mut HashMap<usize, Rc<ObjString>> = INTERNED_STRINGS.get_mut()
+            let interned_string: &mut HashMap<usize, Rc<ObjString>> = (*addr_of_mut!(INTERNED_STRINGS)).get_mut()
                 .expect("Use 'initialize_interned_string' in main to intialize INTERNED_STRING");
 
             if let Some(entry) = interned_string.get(&hash) {
@@ -59,11 +70,32 @@ impl From<String> for DynType {
     }
 }
 
+impl From<ObjBigInt> for DynType {
+    fn from(value: ObjBigInt) -> DynType {
+        DynType::BigInt(Rc::new(value))
+    }
+}
+
+impl From<ObjDecimal> for DynType {
+    fn from(value: ObjDecimal) -> DynType {
+        DynType::Decimal(Rc::new(value))
+    }
+}
+
+impl From<ObjRational> for DynType {
+    fn from(value: ObjRational) -> DynType {
+        DynType::Rational(Rc::new(value))
+    }
+}
+
 impl PartialEq for DynType {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Text(ptr), Text(ptr1)) => *ptr == *ptr1 // or use Arc::ptr_eq(ptr, ptr1)
-            //_ => false
+            (Text(ptr), Text(ptr1)) => *ptr == *ptr1, // or use Arc::ptr_eq(ptr, ptr1)
+            (BigInt(a), BigInt(b)) => *a == *b,
+            (Decimal(a), Decimal(b)) => *a == *b,
+            (Rational(a), Rational(b)) => *a == *b,
+            _ => false
         }
     }
 }
@@ -71,13 +103,17 @@ impl PartialEq for DynType {
 impl Display for DynType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Text(ptr) => write!(f, "{}", ptr.get_data())
+            Text(ptr) => write!(f, "{}", ptr.get_data()),
+            BigInt(ptr) => write!(f, "{}", ptr),
+            Decimal(ptr) => write!(f, "{}", ptr),
+            Rational(ptr) => write!(f, "{}", ptr)
         }
     }
 }
 
 
 pub mod obj_string {
+    use std::cmp::Ordering;
 
     #[derive(Debug)]
     pub struct ObjString {
@@ -117,4 +153,19 @@ pub mod obj_string {
             self.hash == other.hash && self.data == other.data
         }
     }
+
+    impl Eq for ObjString {}
+
+    impl PartialOrd for ObjString {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    // Lexicographic by bytes, for UTF-8 this is the same as comparing the code points
+    impl Ord for ObjString {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.data.as_bytes().cmp(other.data.as_bytes())
+        }
+    }
 }
@@ -0,0 +1,444 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+/* OBJ BIG INT
+
+ Arbitrary-precision integer in sign-magnitude representation.
+ The magnitude is stored in base 2^32, least significant limb first, without leading zero limbs.
+ Zero has an empty magnitude and is never negative, so every number has exactly one representation
+ and the derived PartialEq is a valid numeric comparison.
+
+ */
+
+const LIMB_BITS: u32 = u32::BITS;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjBigInt {
+    negative: bool,
+    magnitude: Vec<u32>
+}
+
+impl ObjBigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative: bool = negative && !magnitude.is_empty();
+        Self { negative, magnitude }
+    }
+
+    // Parses digits of the given radix, the caller has to remove prefixes and separators
+    pub fn parse(digits: &str, radix: u32) -> Option<Self> {
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut magnitude: Vec<u32> = Vec::new();
+        for digit in digits.chars() {
+            let digit: u32 = digit.to_digit(radix)?;
+            mul_add_small(&mut magnitude, radix, digit);
+        }
+        Some(Self::new(false, magnitude))
+    }
+
+    // The sign and the limbs of the magnitude, least significant first
+    pub fn to_limbs(&self) -> (bool, &[u32]) {
+        (self.negative, &self.magnitude)
+    }
+
+    pub fn from_limbs(negative: bool, magnitude: Vec<u32>) -> Self {
+        Self::new(negative, magnitude)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+
+        let magnitude: u64 = self.magnitude.iter().rev().fold(0, |acc, limb| (acc << LIMB_BITS) | *limb as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.magnitude.len() > 4 {
+            return None;
+        }
+
+        let magnitude: u128 = self.magnitude.iter().rev().fold(0, |acc, limb| (acc << LIMB_BITS) | *limb as u128);
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // the most significant limbs dominate, rounding the rest away is fine for a conversion to f64
+        let magnitude: f64 = self.magnitude.iter().rev().fold(0.0, |acc, limb| acc * 4294967296.0 + *limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(false, self.magnitude.clone())
+    }
+
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(last) => (self.magnitude.len() as u64 - 1) * LIMB_BITS as u64 + (LIMB_BITS - last.leading_zeros()) as u64,
+            None => 0
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.magnitude.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Self::new(other.negative, sub_magnitude(&other.magnitude, &self.magnitude)),
+            _ => Self::new(self.negative, sub_magnitude(&self.magnitude, &other.magnitude))
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(self.negative != other.negative, mul_magnitude(&self.magnitude, &other.magnitude))
+    }
+
+    // Truncating division like i64, None if other is zero
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.magnitude.is_empty() {
+            return None;
+        }
+
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &other.magnitude);
+        Some((Self::new(self.negative != other.negative, quotient), Self::new(self.negative, remainder)))
+    }
+
+    // Division rounding towards negative infinity, the remainder has the sign of other
+    pub fn floor_div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        let (quotient, remainder) = self.div_rem(other)?;
+
+        if !remainder.magnitude.is_empty() && remainder.negative != other.negative {
+            return Some((quotient.sub(&Self::from(1i64)), remainder.add(other)));
+        }
+        Some((quotient, remainder))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Self {
+        let mut result: Self = Self::from(1i64);
+        let mut base: Self = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    // &, | and ^ work on the infinite two's complement of both numbers like they do on i64
+    pub fn bitand(&self, other: &Self) -> Self {
+        self.bitwise(other, |a, b| a & b)
+    }
+
+    pub fn bitor(&self, other: &Self) -> Self {
+        self.bitwise(other, |a, b| a | b)
+    }
+
+    pub fn bitxor(&self, other: &Self) -> Self {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+
+    // ~a == -a - 1
+    pub fn not(&self) -> Self {
+        self.neg().sub(&Self::from(1i64))
+    }
+
+    fn bitwise(&self, other: &Self, operation: fn(u32, u32) -> u32) -> Self {
+        // one more limb than both magnitudes, so the sign bit always has room
+        let length: usize = self.magnitude.len().max(other.magnitude.len()) + 1;
+        let (a, b): (Vec<u32>, Vec<u32>) = (self.twos_complement(length), other.twos_complement(length));
+        let mut limbs: Vec<u32> = a.iter().zip(&b).map(|(a, b)| operation(*a, *b)).collect();
+
+        let negative: bool = limbs.last().is_some_and(|limb| limb >> (LIMB_BITS - 1) == 1);
+        if negative {
+            negate_limbs(&mut limbs);
+        }
+        Self::new(negative, limbs)
+    }
+
+    fn twos_complement(&self, length: usize) -> Vec<u32> {
+        let mut limbs: Vec<u32> = self.magnitude.clone();
+        limbs.resize(length, 0);
+        if self.negative {
+            negate_limbs(&mut limbs);
+        }
+        limbs
+    }
+
+    // Multiplies by 2^amount, the caller has to make sure the result isn't too large
+    pub fn shl(&self, amount: u64) -> Self {
+        if self.magnitude.is_empty() {
+            return self.clone();
+        }
+
+        let shift: u32 = (amount % LIMB_BITS as u64) as u32;
+        let mut magnitude: Vec<u32> = vec![0; (amount / LIMB_BITS as u64) as usize];
+        let mut carry: u32 = 0;
+        for limb in &self.magnitude {
+            let shifted: u64 = ((*limb as u64) << shift) | carry as u64;
+            magnitude.push(shifted as u32);
+            carry = (shifted >> LIMB_BITS) as u32;
+        }
+        magnitude.push(carry);
+        Self::new(self.negative, magnitude)
+    }
+
+    // Divides by 2^amount rounding towards negative infinity like >> on i64
+    pub fn shr(&self, amount: u64) -> Self {
+        let limbs: usize = usize::try_from(amount / LIMB_BITS as u64).unwrap_or(usize::MAX);
+        if limbs >= self.magnitude.len() {
+            return Self::from(if self.negative { -1i64 } else { 0 });
+        }
+
+        let shift: u32 = (amount % LIMB_BITS as u64) as u32;
+        let mut magnitude: Vec<u32> = Vec::with_capacity(self.magnitude.len() - limbs);
+        for (index, limb) in self.magnitude[limbs..].iter().enumerate() {
+            let next: u64 = self.magnitude.get(limbs + index + 1).copied().unwrap_or(0) as u64;
+            magnitude.push((((next << LIMB_BITS) | *limb as u64) >> shift) as u32);
+        }
+
+        // a negative number that lost bits is rounded down
+        let lost: bool = self.magnitude[..limbs].iter().any(|limb| *limb != 0) || self.magnitude[limbs] & ((1u64 << shift) - 1) as u32 != 0;
+        let result: Self = Self::new(self.negative, magnitude);
+        match self.negative && lost {
+            true => result.sub(&Self::from(1i64)),
+            false => result
+        }
+    }
+
+    // Exact comparison against a float, None if other is NaN
+    pub fn cmp_f64(&self, other: f64) -> Option<Ordering> {
+        if other.is_nan() {
+            return None;
+        }
+        if other.is_infinite() {
+            return Some(if other > 0.0 { Ordering::Less } else { Ordering::Greater });
+        }
+
+        let truncated: f64 = other.trunc();
+        match self.cmp(&Self::from_integral_f64(truncated)) {
+            Ordering::Equal => (0.0).partial_cmp(&(other - truncated)),
+            ordering => Some(ordering)
+        }
+    }
+
+    // other has to be finite and without a fractional part
+    fn from_integral_f64(other: f64) -> Self {
+        if other.abs() < 9.223372036854776e18 {
+            return Self::from(other as i64);
+        }
+
+        // |other| >= 2^63, so the value is the mantissa shifted to the left
+        let bits: u64 = other.to_bits();
+        let exponent: u64 = ((bits >> 52) & 0x7ff) - 1075;
+        let mantissa: u64 = (bits & ((1 << 52) - 1)) | (1 << 52);
+
+        let mut magnitude: Vec<u32> = vec![0; (exponent / LIMB_BITS as u64) as usize];
+        let shift: u32 = (exponent % LIMB_BITS as u64) as u32;
+        let shifted: u128 = (mantissa as u128) << shift;
+        magnitude.extend([shifted as u32, (shifted >> 32) as u32, (shifted >> 64) as u32]);
+
+        Self::new(other < 0.0, magnitude)
+    }
+}
+
+impl From<i64> for ObjBigInt {
+    fn from(value: i64) -> Self {
+        let magnitude: u64 = value.unsigned_abs();
+        Self::new(value < 0, vec![magnitude as u32, (magnitude >> LIMB_BITS) as u32])
+    }
+}
+
+impl From<i128> for ObjBigInt {
+    fn from(value: i128) -> Self {
+        let magnitude: u128 = value.unsigned_abs();
+        Self::new(value < 0, (0..4).map(|limb| (magnitude >> (limb * LIMB_BITS)) as u32).collect())
+    }
+}
+
+impl PartialOrd for ObjBigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ObjBigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude)
+        }
+    }
+}
+
+impl Display for ObjBigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.magnitude.is_empty() {
+            return write!(f, "0");
+        }
+
+        // split into chunks of 9 decimal digits, least significant first
+        const CHUNK: u32 = 1_000_000_000;
+        let mut chunks: Vec<u32> = Vec::new();
+        let mut magnitude: Vec<u32> = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, CHUNK));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.last().unwrap())?;
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{chunk:09}")?;
+        }
+        Ok(())
+    }
+}
+
+// Two's complement negation in place: every bit is inverted and one is added
+fn negate_limbs(limbs: &mut [u32]) {
+    let mut carry: bool = true;
+    for limb in limbs {
+        (*limb, carry) = (!*limb).overflowing_add(carry as u32);
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result: Vec<u32> = Vec::with_capacity(long.len() + 1);
+    let mut carry: u64 = 0;
+
+    for (index, limb) in long.iter().enumerate() {
+        let sum: u64 = *limb as u64 + *short.get(index).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> LIMB_BITS;
+    }
+    result.push(carry as u32);
+    result
+}
+
+// a has to be greater or equal to b
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result: Vec<u32> = Vec::with_capacity(a.len());
+    let mut borrow: i64 = 0;
+
+    for (index, limb) in a.iter().enumerate() {
+        let mut difference: i64 = *limb as i64 - *b.get(index).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << LIMB_BITS;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    result
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result: Vec<u32> = vec![0; a.len() + b.len()];
+
+    for (i, x) in a.iter().enumerate() {
+        let mut carry: u64 = 0;
+        for (j, y) in b.iter().enumerate() {
+            let product: u64 = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> LIMB_BITS;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    result
+}
+
+fn mul_add_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry: u64 = addend as u64;
+    for limb in magnitude.iter_mut() {
+        let product: u64 = *limb as u64 * factor as u64 + carry;
+        *limb = product as u32;
+        carry = product >> LIMB_BITS;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+// Divides in place and returns the remainder
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder: u64 = 0;
+    for limb in magnitude.iter_mut().rev() {
+        let current: u64 = (remainder << LIMB_BITS) | *limb as u64;
+        *limb = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    remainder as u32
+}
+
+// Schoolbook binary long division, b must not be zero
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = b {
+        let mut quotient: Vec<u32> = a.to_vec();
+        let remainder: u32 = div_rem_small(&mut quotient, *divisor);
+        return (quotient, vec![remainder]);
+    }
+
+    let mut quotient: Vec<u32> = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+
+    for bit in (0..a.len() * LIMB_BITS as usize).rev() {
+        // remainder = remainder * 2 + next bit of a
+        mul_add_small(&mut remainder, 2, (a[bit / LIMB_BITS as usize] >> (bit % LIMB_BITS as usize)) & 1);
+
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[bit / LIMB_BITS as usize] |= 1 << (bit % LIMB_BITS as usize);
+        }
+    }
+    (quotient, remainder)
+}
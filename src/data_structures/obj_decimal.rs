@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::value::Error;
+use super::obj_big_int::ObjBigInt;
+
+/* OBJ DECIMAL
+
+ Exact fixed-point decimal number: mantissa * 10^-scale, e.g. 0.10d is stored as (10, 2).
+ Addition, subtraction and multiplication are exact and keep the scale, like a written calculation would.
+ Division rounds half to even to DIVISION_SCALE places. Every operation fails instead of silently losing precision
+ when the mantissa doesn't fit into an i128 or the scale exceeds MAX_SCALE.
+
+ */
+
+pub const MAX_SCALE: u32 = 28;
+pub const DIVISION_SCALE: u32 = 18;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjDecimal {
+    mantissa: i128,
+    scale: u32
+}
+
+impl ObjDecimal {
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Self { mantissa, scale }
+    }
+
+    // Parses '12.50' or '1.5e-3', the caller has to remove the suffix and separators
+    pub fn parse(literal: &str) -> Result<Self, Error> {
+        let (number, exponent) = match literal.find(['e', 'E']) {
+            Some(index) => (&literal[..index], literal[index + 1..].parse::<i32>().map_err(|_| format!("Invalid exponent in '{literal}'"))?),
+            None => (literal, 0)
+        };
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+        let mantissa: i128 = format!("{integer}{fraction}").parse::<i128>().map_err(|_| overflow())?;
+        let scale: i64 = fraction.len() as i64 - exponent as i64;
+
+        if scale < 0 {
+            let factor: i128 = u32::try_from(scale.unsigned_abs()).ok().and_then(|exponent| 10i128.checked_pow(exponent)).ok_or_else(overflow)?;
+            return Ok(Self::new(mantissa.checked_mul(factor).ok_or_else(overflow)?, 0));
+        }
+        // rounding to MAX_SCALE divides by 10^(scale - MAX_SCALE), which has to fit into an i128
+        match u32::try_from(scale).ok().filter(|scale| *scale <= MAX_SCALE + 38) {
+            Some(scale) => Self::new(mantissa, scale).limit_scale(),
+            None => Err(format!("Decimal scale out of range: the number has {scale} decimal places"))
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        // going through the string gives the closest f64 instead of accumulating two rounding errors
+        self.to_string().parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn neg(&self) -> Result<Self, Error> {
+        Ok(Self::new(self.mantissa.checked_neg().ok_or_else(overflow)?, self.scale))
+    }
+
+    pub fn add(&self, other: &Self) -> Result<Self, Error> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Self::new(a.checked_add(b).ok_or_else(overflow)?, scale))
+    }
+
+    pub fn sub(&self, other: &Self) -> Result<Self, Error> {
+        let (a, b, scale) = self.align(other)?;
+        Ok(Self::new(a.checked_sub(b).ok_or_else(overflow)?, scale))
+    }
+
+    pub fn mul(&self, other: &Self) -> Result<Self, Error> {
+        let mantissa: i128 = self.mantissa.checked_mul(other.mantissa).ok_or_else(overflow)?;
+        Self::new(mantissa, self.scale + other.scale).limit_scale()
+    }
+
+    pub fn div(&self, other: &Self) -> Result<Self, Error> {
+        if other.mantissa == 0 {
+            return Err("Division by zero".to_string());
+        }
+
+        // a / b = (a.mantissa * 10^shift / b.mantissa) * 10^-(a.scale + shift - b.scale)
+        let shift: u32 = DIVISION_SCALE + other.scale - self.scale.min(DIVISION_SCALE + other.scale);
+        let numerator: i128 = self.mantissa.checked_mul(pow10(shift)?).ok_or_else(overflow)?;
+        let quotient: i128 = round_half_even(numerator, other.mantissa)?;
+
+        Ok(Self::new(quotient, self.scale + shift - other.scale).trim(self.scale.max(other.scale)))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> Result<Self, Error> {
+        let mut result: Self = Self::new(1, 0);
+        let mut base: Self = *self;
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&base)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(&base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    // Rounds half to even, 2.5d -> 2 and 3.5d -> 4
+    pub fn round(&self, places: u32) -> Result<Self, Error> {
+        if places >= self.scale {
+            let (mantissa, _, scale) = self.align(&Self::new(0, places))?;
+            return Ok(Self::new(mantissa, scale));
+        }
+        Ok(Self::new(round_half_even(self.mantissa, pow10(self.scale - places)?)?, places))
+    }
+
+    // Removes trailing zeros without going below the given scale
+    fn trim(mut self, minimum: u32) -> Self {
+        while self.scale > minimum && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    fn limit_scale(self) -> Result<Self, Error> {
+        match self.scale > MAX_SCALE {
+            true => self.round(MAX_SCALE),
+            false => Ok(self)
+        }
+    }
+
+    // Both mantissas with the same scale
+    fn align(&self, other: &Self) -> Result<(i128, i128, u32), Error> {
+        let scale: u32 = self.scale.max(other.scale);
+        let a: i128 = self.mantissa.checked_mul(pow10(scale - self.scale)?).ok_or_else(overflow)?;
+        let b: i128 = other.mantissa.checked_mul(pow10(scale - other.scale)?).ok_or_else(overflow)?;
+        Ok((a, b, scale))
+    }
+
+    pub fn to_big_fraction(self) -> (ObjBigInt, ObjBigInt) {
+        (ObjBigInt::from(self.mantissa), ObjBigInt::from(10i64).pow(self.scale))
+    }
+}
+
+impl From<i64> for ObjDecimal {
+    fn from(value: i64) -> Self {
+        Self::new(value as i128, 0)
+    }
+}
+
+impl PartialEq for ObjDecimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ObjDecimal {}
+
+impl PartialOrd for ObjDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ObjDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.align(other) {
+            Ok((a, b, _)) => a.cmp(&b),
+            Err(_) => {
+                // aligning overflows an i128, cross multiplying big integers is always exact
+                let ((a, a_denominator), (b, b_denominator)) = (self.to_big_fraction(), other.to_big_fraction());
+                a.mul(&b_denominator).cmp(&b.mul(&a_denominator))
+            }
+        }
+    }
+}
+
+impl Display for ObjDecimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits: String = self.mantissa.unsigned_abs().to_string();
+        let scale: usize = self.scale as usize;
+        let sign: &str = if self.mantissa < 0 { "-" } else { "" };
+
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits: String = format!("{digits:0>width$}", width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{integer}.{fraction}")
+    }
+}
+
+fn pow10(exponent: u32) -> Result<i128, Error> {
+    10i128.checked_pow(exponent).ok_or_else(overflow)
+}
+
+fn overflow() -> Error {
+    "Decimal overflow: the result has more than 38 significant digits".to_string()
+}
+
+// i128::MIN / -1 is the only quotient that doesn't fit
+fn round_half_even(numerator: i128, denominator: i128) -> Result<i128, Error> {
+    let quotient: i128 = numerator.checked_div(denominator).ok_or_else(overflow)?;
+    let remainder: i128 = numerator.checked_rem(denominator).ok_or_else(overflow)?;
+    let negative: bool = (numerator < 0) != (denominator < 0);
+    let twice: u128 = remainder.unsigned_abs() * 2;
+
+    if twice > denominator.unsigned_abs() || (twice == denominator.unsigned_abs() && quotient % 2 != 0) {
+        // the quotient is rounded away from zero
+        return Ok(quotient + if negative { -1 } else { 1 });
+    }
+    Ok(quotient)
+}
@@ -0,0 +1,174 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::value::Error;
+use super::obj_big_int::ObjBigInt;
+use super::obj_decimal::ObjDecimal;
+
+/* OBJ RATIONAL
+
+ Exact fraction numerator / denominator of big integers, created with rational(a, b).
+ The fraction is always reduced and the denominator is positive, so the derived PartialEq compares numerically.
+ Since both parts are big integers no operation can overflow.
+
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjRational {
+    numerator: ObjBigInt,
+    denominator: ObjBigInt
+}
+
+impl ObjRational {
+    pub fn new(numerator: ObjBigInt, denominator: ObjBigInt) -> Result<Self, Error> {
+        if denominator.is_zero() {
+            return Err("Division by zero".to_string());
+        }
+
+        let divisor: ObjBigInt = gcd(&numerator, &denominator);
+        let (mut numerator, mut denominator) = (divide(&numerator, &divisor), divide(&denominator, &divisor));
+        if denominator.is_negative() {
+            (numerator, denominator) = (numerator.neg(), denominator.neg());
+        }
+        Ok(Self { numerator, denominator })
+    }
+
+    pub fn from_integer(value: ObjBigInt) -> Self {
+        Self { numerator: value, denominator: ObjBigInt::from(1i64) }
+    }
+
+    pub fn from_decimal(value: &ObjDecimal) -> Self {
+        let (numerator, denominator) = value.to_big_fraction();
+        Self::new(numerator, denominator).expect("10^scale is never zero")
+    }
+
+    // Every finite float is a fraction with a power of two as denominator, None for NaN and infinity
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        // value = mantissa * 2^shift, subnormal numbers have no implicit leading bit
+        let bits: u64 = value.to_bits();
+        let (fraction, exponent) = (bits & ((1 << 52) - 1), ((bits >> 52) & 0x7ff) as i64);
+        let (mantissa, shift) = match exponent {
+            0 => (fraction as i64, -1074),
+            _ => ((fraction | (1 << 52)) as i64, exponent - 1075)
+        };
+        let mantissa: ObjBigInt = ObjBigInt::from(if value < 0.0 { -mantissa } else { mantissa });
+
+        let power: ObjBigInt = ObjBigInt::from(2i64).pow(shift.unsigned_abs() as u32);
+        match shift < 0 {
+            true => Self::new(mantissa, power).ok(),
+            false => Some(Self::from_integer(mantissa.mul(&power)))
+        }
+    }
+
+    pub fn numerator(&self) -> &ObjBigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &ObjBigInt {
+        &self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+
+    // Rounds half to even to the given number of decimal places
+    pub fn to_decimal(&self, places: u32) -> Result<ObjDecimal, Error> {
+        let scaled: ObjBigInt = self.numerator.mul(&ObjBigInt::from(10i64).pow(places));
+        let (quotient, remainder) = scaled.floor_div_rem(&self.denominator).expect("the denominator is never zero");
+
+        let twice: ObjBigInt = remainder.mul(&ObjBigInt::from(2i64));
+        let quotient: ObjBigInt = match twice.cmp(&self.denominator) {
+            Ordering::Greater => quotient.add(&ObjBigInt::from(1i64)),
+            Ordering::Equal if quotient.floor_div_rem(&ObjBigInt::from(2i64)).is_some_and(|(_, odd)| !odd.is_zero()) => quotient.add(&ObjBigInt::from(1i64)),
+            _ => quotient
+        };
+
+        match quotient.to_i128() {
+            Some(mantissa) => Ok(ObjDecimal::new(mantissa, places)),
+            None => Err("Decimal overflow: the result has more than 38 significant digits".to_string())
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self { numerator: self.numerator.neg(), denominator: self.denominator.clone() }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let numerator: ObjBigInt = self.numerator.mul(&other.denominator).add(&other.numerator.mul(&self.denominator));
+        Self::new(numerator, self.denominator.mul(&other.denominator)).expect("the denominator is never zero")
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(self.numerator.mul(&other.numerator), self.denominator.mul(&other.denominator)).expect("the denominator is never zero")
+    }
+
+    pub fn div(&self, other: &Self) -> Result<Self, Error> {
+        Self::new(self.numerator.mul(&other.denominator), self.denominator.mul(&other.numerator))
+    }
+
+    // Largest integer less or equal to self / other
+    pub fn floor_div(&self, other: &Self) -> Result<ObjBigInt, Error> {
+        let quotient: Self = self.div(other)?;
+        Ok(quotient.numerator.floor_div_rem(&quotient.denominator).expect("the denominator is never zero").0)
+    }
+
+    // self - other * floor(self / other), has the sign of other like % on integers
+    pub fn floor_rem(&self, other: &Self) -> Result<Self, Error> {
+        let quotient: Self = Self::from_integer(self.floor_div(other)?);
+        Ok(self.sub(&other.mul(&quotient)))
+    }
+
+    pub fn pow(&self, exponent: i64) -> Result<Self, Error> {
+        let power: u32 = u32::try_from(exponent.unsigned_abs()).map_err(|_| format!("The exponent {exponent} is too large"))?;
+        let result: Self = Self { numerator: self.numerator.pow(power), denominator: self.denominator.pow(power) };
+
+        match exponent < 0 {
+            true => Self::from_integer(ObjBigInt::from(1i64)).div(&result),
+            false => Ok(result)
+        }
+    }
+}
+
+impl PartialOrd for ObjRational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ObjRational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // both denominators are positive, so cross multiplying keeps the ordering
+        self.numerator.mul(&other.denominator).cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+
+impl Display for ObjRational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.denominator == ObjBigInt::from(1i64) {
+            return write!(f, "{}", self.numerator);
+        }
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+fn gcd(a: &ObjBigInt, b: &ObjBigInt) -> ObjBigInt {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while !b.is_zero() {
+        let (_, remainder) = a.div_rem(&b).expect("b is not zero");
+        (a, b) = (b, remainder);
+    }
+    a
+}
+
+fn divide(a: &ObjBigInt, b: &ObjBigInt) -> ObjBigInt {
+    a.div_rem(b).expect("the gcd of a fraction with a non-zero denominator is never zero").0
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::mem::replace;
+
+use super::chunk::{decode_line, format_constant, instruction_size, Chunk};
+use super::common::{SharedData, DEFAULT_STACK_CAPACITY};
+use super::compiler::Compiler;
+use super::disassembler::format_instruction;
+use super::lexer::Lexer;
+use super::value::Value;
+use super::vm::{as_values, Status, VM};
+
+/* DEBUGGER
+
+ The debugger drives a VM with run_until and stops it before the instructions it is interested in. It starts
+ paused before the first instruction. Breakpoints are set on source lines and hit when the VM enters a line
+ with one, that is before the first instruction of a run of instructions on that line.
+
+  stepi   runs one instruction
+  step    runs until the VM enters another line
+  next    steps over calls
+  finish  runs until the current call returns
+  continue runs until a breakpoint or the end
+
+ The language has no functions, globals or call frames yet: a call of a native is a single instruction, so
+ next and step do the same and finish runs the whole program like continue. Expressions are evaluated in a
+ VM of their own, there are no variables they could refer to in the paused program.
+
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint(u32),
+    Finished,
+    Error(u32) // the line of the instruction that failed
+}
+
+pub struct Debugger<'a> {
+    chunk: &'a Chunk,
+    vm: VM<'a>,
+    source: Vec<&'a str>,
+    breakpoints: BTreeSet<u32>,
+    stopped: Option<Stop> // set once the program finished or failed, it can't be resumed after that
+}
+
+impl <'a> Debugger<'a> {
+    pub fn new(chunk: &'a Chunk, source: Option<&'a str>) -> Self {
+        let source: Vec<&'a str> = source.map(|source| source.lines().collect()).unwrap_or_default();
+        Self { chunk, vm: VM::new(chunk), source, breakpoints: BTreeSet::new(), stopped: None }
+    }
+
+    pub fn set_breakpoint(&mut self, line: u32) -> Result<(), String> {
+        if !self.lines().contains(&line) {
+            return Err(format!("There is no code on line {line}"));
+        }
+        self.breakpoints.insert(line);
+        Ok(())
+    }
+
+    pub fn clear_breakpoint(&mut self, line: u32) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &u32> {
+        self.breakpoints.iter()
+    }
+
+    pub fn step_instruction(&mut self) -> Stop {
+        self.resume(|_| true)
+    }
+
+    pub fn step_line(&mut self) -> Stop {
+        self.resume(|entered| entered)
+    }
+
+    pub fn step_over(&mut self) -> Stop {
+        self.step_line()
+    }
+
+    pub fn step_out(&mut self) -> Stop {
+        self.resume(|_| false)
+    }
+
+    pub fn resume_until_breakpoint(&mut self) -> Stop {
+        self.resume(|_| false)
+    }
+
+    // The offset and line of the next instruction, None once the program stopped
+    pub fn location(&self) -> Option<(usize, u32)> {
+        let ip: usize = self.vm.get_ip();
+        match self.stopped.is_none() && ip < self.chunk.code().len() {
+            true => Some((ip, decode_line(self.chunk.code(), ip))),
+            false => None
+        }
+    }
+
+    pub fn stack(&self) -> Vec<Value> {
+        as_values(self.vm.get_stack()).into_owned()
+    }
+
+    // The message of the runtime error the program stopped with
+    pub fn error(&self) -> Option<&str> {
+        self.vm.get_error()
+    }
+
+    // The language has no globals yet
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        Vec::new()
+    }
+
+    pub fn evaluate(&self, expression: &str) -> Result<Value, String> {
+        let source: String = expression.to_string();
+        let mut lexer: Lexer = Lexer::new(&source);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+
+        Compiler::new(lexer.lexing(), SharedData::new(&source), SharedData::new(&chunk)).compile_with_errors()
+            .map_err(|errors| errors.first().map_or_else(|| format!("'{expression}' does not compile"), |error| error.describe(&source)))?;
+
+        let mut vm: VM = VM::new(&chunk);
+        vm.run().ok_or_else(|| format!("'{expression}' failed at runtime"))?;
+        Ok(as_values(vm.get_stack()).last().cloned().unwrap_or(Value::Null))
+    }
+
+    // Runs the current instruction and stops before the next one if step(entered) is true, entered is
+    // true for the first instruction of a line. Breakpoints are checked in any case.
+    fn resume(&mut self, mut step: impl FnMut(bool) -> bool) -> Stop {
+        if let Some(stop) = self.stopped {
+            return stop;
+        }
+
+        let code: &[u8] = self.chunk.code();
+        let breakpoints: &BTreeSet<u32> = &self.breakpoints;
+        let mut previous: Option<u32> = None;
+        let mut current: bool = true;
+        let mut stop: Stop = Stop::Step;
+
+        let status: Option<Status> = self.vm.run_until(|ip| {
+            let line: u32 = decode_line(code, ip);
+            let entered: bool = previous.replace(line) != Some(line);
+
+            if replace(&mut current, false) {
+                return false;
+            }
+            if entered && breakpoints.contains(&line) {
+                stop = Stop::Breakpoint(line);
+                return true;
+            }
+            step(entered)
+        });
+
+        match status {
+            Some(Status::Paused) => stop,
+            Some(Status::Finished) => *self.stopped.insert(Stop::Finished),
+            None => *self.stopped.insert(Stop::Error(self.vm.get_line()))
+        }
+    }
+
+    fn lines(&self) -> BTreeSet<u32> {
+        let mut lines: BTreeSet<u32> = BTreeSet::new();
+
+        let mut index: usize = 0;
+        while let Some((opcode, line)) = self.chunk.read_opcode(index) {
+            lines.insert(line);
+            index += instruction_size(opcode);
+        }
+        lines
+    }
+
+    /* COMMANDS
+
+     The command line interface reads one command per line and answers every one of them:
+
+      break N, b N      sets a breakpoint on line N       delete N      removes it
+      breakpoints       lists the breakpoints             where, w      shows the next instruction
+      stepi, si         step, s       next, n       finish       continue, c
+      stack             the values on the stack           globals       the global variables
+      print EXPR, p     evaluates EXPR                    help, quit, q
+
+     */
+
+    pub fn run_cli(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        writeln!(output, "{}", self.describe_location())?;
+        write!(output, "(eos) ")?;
+        output.flush()?;
+
+        for command in input.lines() {
+            let command: String = command?;
+            match self.command(command.trim()) {
+                Some(answer) => writeln!(output, "{answer}")?,
+                None => return Ok(())
+            }
+            write!(output, "(eos) ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // The answer to a command, None if it ends the session
+    pub fn command(&mut self, command: &str) -> Option<String> {
+        let (name, argument): (&str, &str) = command.split_once(' ').map_or((command, ""), |(name, argument)| (name, argument.trim()));
+
+        let answer: String = match name {
+            "break" | "b" => match argument.parse::<u32>() {
+                Ok(line) => self.set_breakpoint(line).map_or_else(|err| err, |_| format!("Breakpoint on line {line}")),
+                Err(_) => format!("Invalid line '{argument}'")
+            },
+            "delete" => match argument.parse::<u32>() {
+                Ok(line) if self.clear_breakpoint(line) => format!("Removed the breakpoint on line {line}"),
+                _ => format!("There is no breakpoint on line '{argument}'")
+            },
+            "breakpoints" => match self.breakpoints.is_empty() {
+                true => "No breakpoints".to_string(),
+                false => self.breakpoints().map(|line| format!("line {line}")).collect::<Vec<String>>().join("\n")
+            },
+            "stepi" | "si" => self.describe_stop(|debugger| debugger.step_instruction()),
+            "step" | "s" => self.describe_stop(|debugger| debugger.step_line()),
+            "next" | "n" => self.describe_stop(|debugger| debugger.step_over()),
+            "finish" => self.describe_stop(|debugger| debugger.step_out()),
+            "continue" | "c" => self.describe_stop(|debugger| debugger.resume_until_breakpoint()),
+            "where" | "w" => self.describe_location(),
+            "stack" => match self.stack().is_empty() {
+                true => "(empty)".to_string(),
+                false => self.stack().iter().map(|value| format!("[ {} ]", format_constant(value))).collect()
+            },
+            "globals" => match self.globals().is_empty() {
+                true => "No globals".to_string(),
+                false => self.globals().iter().map(|(name, value)| format!("{name} = {}", format_constant(value))).collect::<Vec<String>>().join("\n")
+            },
+            "print" | "p" => self.evaluate(argument).map_or_else(|err| err, |value| format_constant(&value)),
+            "help" => "break N, delete N, breakpoints, where, stepi, step, next, finish, continue, stack, globals, print EXPR, quit".to_string(),
+            "quit" | "q" => return None,
+            _ => format!("Unknown command '{command}', try help")
+        };
+        Some(answer)
+    }
+
+    fn describe_stop(&mut self, resume: impl FnOnce(&mut Self) -> Stop) -> String {
+        match resume(self) {
+            Stop::Step => self.describe_location(),
+            Stop::Breakpoint(line) => format!("Breakpoint on line {line}\n{}", self.describe_location()),
+            Stop::Finished => match self.stack().last() {
+                Some(value) => format!("Finished with {}", format_constant(value)),
+                None => "Finished".to_string()
+            },
+            Stop::Error(line) => format!("Runtime error on line {line}: {}", self.error().unwrap_or_default())
+        }
+    }
+
+    fn describe_location(&self) -> String {
+        let Some((ip, line)) = self.location() else {
+            return "The program is not running".to_string();
+        };
+
+        let instruction: String = format_instruction(self.chunk, ip, false);
+        match (line as usize).checked_sub(1).and_then(|row| self.source.get(row)) {
+            Some(text) => format!("{line:4} | {}\n{instruction}", text.trim()),
+            None => instruction
+        }
+    }
+}
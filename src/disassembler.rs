@@ -0,0 +1,136 @@
+use std::fmt::Write;
+
+use super::chunk::{decode_line, decode_opcode, format_operand, instruction_size, is_jump, Chunk, INSTRUCTION_SIZE, OPERAND_SIZE};
+use super::opcode::OpCode;
+
+/* DISASSEMBLER
+
+ listing writes a chunk so the assembler can read it back, the disassembler writes it for people:
+
+      === code ===
+      000000    1 Constant           Boolean(true)
+  +-- 000021    | JumpIfFalse        26 -> 000056
+  |   000030    | Pop
+  |   000035    | Constant           Integer(1)
+  +-> 000056      end
+
+ The columns are the offset, the line ('|' if it's the line of the instruction before) and the opcode
+ with its decoded operand. Every jump gets a lane in the gutter on the left, which runs from the jump
+ to its target; nested jumps get the lanes further right. Jumps to the end of the chunk point to an
+ 'end' row. With the source, the line of source is interleaved before the first instruction of a line.
+
+ Constants are printed by format_constant, so a function constant would be the place to disassemble
+ its chunk recursively; the language doesn't have functions yet, every chunk is flat.
+
+ */
+
+struct Arrow {
+    from: usize,
+    to: usize,
+    lane: usize
+}
+
+pub fn disassemble(chunk: &Chunk, name: &str, source: Option<&str>) -> String {
+    let code: &[u8] = chunk.code();
+    let source_lines: Vec<&str> = source.map(|source| source.lines().collect()).unwrap_or_default();
+
+    let mut instructions: Vec<(usize, OpCode, u32)> = Vec::new();
+    let mut index: usize = 0;
+    while let Some((opcode, line)) = chunk.read_opcode(index) {
+        instructions.push((index, opcode, line));
+        index += instruction_size(opcode);
+    }
+
+    let arrows: Vec<Arrow> = assign_lanes(instructions.iter()
+        .filter(|(_, opcode, _)| is_jump(*opcode))
+        .map(|(index, _, _)| (*index, index + INSTRUCTION_SIZE + OPERAND_SIZE + chunk.read_operand(index + INSTRUCTION_SIZE).unwrap() as usize)));
+    let lanes: usize = arrows.iter().map(|arrow| arrow.lane + 1).max().unwrap_or(0);
+
+    let mut output: String = format!("{}=== {name} ===\n", gutter(&arrows, lanes, None));
+    let mut last_line: Option<u32> = None;
+
+    for &(index, _, line) in &instructions {
+        if last_line != Some(line) {
+            if let Some(text) = (line as usize).checked_sub(1).and_then(|row| source_lines.get(row)) {
+                let _ = writeln!(output, "{}       ; {line}: {}", gutter(&arrows, lanes, Some((index, false))), text.trim());
+            }
+        }
+
+        let _ = writeln!(output, "{}{}", gutter(&arrows, lanes, Some((index, true))), format_instruction(chunk, index, last_line == Some(line)));
+        last_line = Some(line);
+    }
+
+    if arrows.iter().any(|arrow| arrow.to == code.len()) {
+        let _ = writeln!(output, "{}{:06}      end", gutter(&arrows, lanes, Some((code.len(), true))), code.len());
+    }
+    output
+}
+
+// One row of the disassembly without the gutter, repeated_line prints '|' instead of the line
+pub fn format_instruction(chunk: &Chunk, index: usize, repeated_line: bool) -> String {
+    let code: &[u8] = chunk.code();
+    let opcode: OpCode = match decode_opcode(code, index) {
+        Ok(opcode) => opcode,
+        Err(err) => return format!("{index:06} {err}")
+    };
+    let line_column: String = match repeated_line {
+        true => "   |".to_string(),
+        false => format!("{:4}", decode_line(code, index))
+    };
+
+    match format_operand(chunk, index) {
+        Some(operand) => format!("{index:06} {line_column} {:<18} {operand}", format!("{opcode:?}")),
+        None => format!("{index:06} {line_column} {opcode:?}")
+    }
+}
+
+// Sorted by their start, every jump takes the leftmost lane that is free again
+fn assign_lanes(jumps: impl Iterator<Item = (usize, usize)>) -> Vec<Arrow> {
+    let mut arrows: Vec<Arrow> = Vec::new();
+    let mut lane_ends: Vec<usize> = Vec::new();
+
+    for (from, to) in jumps {
+        let lane: usize = match lane_ends.iter().position(|end| *end < from) {
+            Some(lane) => lane,
+            None => {
+                lane_ends.push(0);
+                lane_ends.len() - 1
+            }
+        };
+        lane_ends[lane] = to;
+        arrows.push(Arrow { from, to, lane });
+    }
+    arrows
+}
+
+// The arrows next to the row at offset, the row is a source line before the instruction if it isn't an instruction
+fn gutter(arrows: &[Arrow], lanes: usize, row: Option<(usize, bool)>) -> String {
+    if lanes == 0 {
+        return String::new();
+    }
+
+    let mut cells: Vec<char> = vec![' '; lanes + 3];
+    let Some((offset, instruction)) = row else {
+        return cells.into_iter().collect();
+    };
+
+    for arrow in arrows {
+        let starts: bool = instruction && arrow.from == offset;
+        let ends: bool = instruction && arrow.to == offset;
+
+        if starts || ends {
+            cells[arrow.lane] = '+';
+            for cell in cells[arrow.lane + 1..lanes + 1].iter_mut().filter(|cell| **cell == ' ') {
+                *cell = '-';
+            }
+            if ends {
+                cells[lanes + 1] = '>';
+            } else if cells[lanes + 1] == ' ' {
+                cells[lanes + 1] = '-';
+            }
+        } else if arrow.from < offset && offset <= arrow.to {
+            cells[arrow.lane] = '|';
+        }
+    }
+    cells.into_iter().collect()
+}
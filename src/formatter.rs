@@ -0,0 +1,335 @@
+use super::ast::{BinaryOperator, CompileError, Expr, ExprKind, Program, Span};
+use super::common::SharedData;
+use super::lexer::Lexer;
+use super::parser::Parser;
+use super::precedence::Precedence;
+use super::token::{Token, TokenType};
+
+/* FORMATTER
+
+ format parses the source and prints it again in the canonical style. Expressions are laid out as documents
+ of groups: a group is printed on one line if it fits into the width, otherwise its lines break and the lines
+ inside of it are indented by one level:
+
+  wrapping_add(1, 2)           wrapping_add(
+                                   first_long_argument,
+                                   second_long_argument
+                               )
+
+  1 + 2 * 3                    first_long_operand
+                                   + second_long_operand
+                                   - third_long_operand
+
+ Operators of the same precedence break together. Literals, strings and interpolations are kept as they are
+ written. The comments come from the Lexer in its trivia mode: a comment behind code stays at the end of its
+ line, every other comment gets a line of its own, blank lines before and after those are kept. A group with
+ a comment inside of it always breaks.
+
+ The output doesn't change if it is formatted again. The language has no statements yet, the program is a
+ single expression.
+
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FormatConfig {
+    pub indent: usize,
+    pub width: usize
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self { indent: 4, width: 100 }
+    }
+}
+
+pub fn format(source: &str, config: &FormatConfig) -> Result<String, String> {
+    let code: String = source.to_string();
+    let program: Program = Parser::new(Lexer::new(source).lexing(), SharedData::new(&code)).parse()
+        .map_err(|errors: Vec<CompileError>| errors.first().map_or_else(|| "The source does not parse".to_string(), |error| error.describe(source)))?;
+
+    let mut formatter: Formatter = Formatter { source, comments: comments(source), next_comment: 0 };
+    let document: Doc = Doc::Concat(vec![formatter.expression(&program.expression), formatter.comments_before(usize::MAX)]);
+
+    let mut printer: Printer = Printer { config, output: String::new(), column: 0, line_start: true, pending: None };
+    printer.print(&document);
+    Ok(format!("{}\n", printer.output.trim_end()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comment {
+    text: String,
+    start: usize,
+    trailing: bool, // behind code on the same line
+    blank_before: bool,
+    blank_after: bool
+}
+
+fn comments(source: &str) -> Vec<Comment> {
+    let tokens: Vec<Token> = Lexer::new(source).with_comments().lexing();
+    let blank = |between: &str| between.matches('\n').count() > 1;
+
+    tokens.iter().enumerate().filter(|(_, token)| token.token_type == TokenType::Comment).map(|(index, token)| {
+        let before: usize = index.checked_sub(1).map_or(0, |previous| tokens[previous].range.1 as usize);
+        let after: usize = tokens.get(index + 1).map_or(source.len(), |next| next.range.0 as usize);
+        let gap: &str = &source[before..token.range.0 as usize];
+
+        Comment {
+            text: source[token.get_range()].trim_end().to_string(),
+            start: token.range.0 as usize,
+            trailing: index > 0 && !gap.contains('\n'),
+            blank_before: index > 0 && blank(gap),
+            blank_after: blank(&source[token.range.1 as usize..after])
+        }
+    }).collect()
+}
+
+enum Doc {
+    Text(String),
+    Line,     // a space, or a new line if the group breaks
+    SoftLine, // nothing, or a new line if the group breaks
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+    Comment(Comment)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    next_comment: usize
+}
+
+impl Formatter<'_> {
+    // The comments in front of an expression stay outside of its groups
+    fn expression(&mut self, expression: &Expr) -> Doc {
+        Doc::Concat(vec![self.comments_before(expression.span.start as usize), self.node(expression)])
+    }
+
+    fn node(&mut self, expression: &Expr) -> Doc {
+        match &expression.kind {
+            ExprKind::Literal(_) | ExprKind::Interpolation(_) => self.span(expression.span),
+            ExprKind::Grouping(inner) => Doc::Group(Box::new(Doc::Concat(vec![
+                self.token(expression.span.start as usize, "("),
+                Doc::Nest(Box::new(Doc::Concat(vec![Doc::SoftLine, self.expression(inner)]))),
+                Doc::SoftLine,
+                self.token(expression.span.end as usize - 1, ")")
+            ]))),
+            ExprKind::Unary { operator_span, operand, .. } => Doc::Concat(vec![self.span(*operator_span), self.expression(operand)]),
+            ExprKind::Binary { .. } => {
+                // the documents are built in the order of the source, so the comments end up in between
+                let (first, rest): (&Expr, Vec<(Span, &Expr)>) = chain(expression);
+                let first: Doc = self.expression(first);
+                let mut operands: Vec<Doc> = Vec::new();
+                for (operator, operand) in rest {
+                    operands.extend([Doc::Line, self.span(operator), Doc::Text(" ".to_string()), self.expression(operand)]);
+                }
+                Doc::Group(Box::new(Doc::Concat(vec![first, Doc::Nest(Box::new(Doc::Concat(operands)))])))
+            },
+            ExprKind::Call { callee, arguments, .. } => {
+                let name: Doc = self.span(*callee);
+                let open: usize = callee.end as usize + self.source[callee.end as usize..].find('(').unwrap_or(0);
+                let open: Doc = self.token(open, "(");
+
+                let mut inner: Vec<Doc> = vec![Doc::SoftLine];
+                for (index, argument) in arguments.iter().enumerate() {
+                    inner.push(self.expression(argument));
+                    if index + 1 < arguments.len() {
+                        let comma: usize = argument.span.end as usize + self.source[argument.span.end as usize..].find(',').unwrap_or(0);
+                        inner.extend([self.token(comma, ","), Doc::Line]);
+                    }
+                }
+                let close: Doc = self.token(expression.span.end as usize - 1, ")");
+
+                match arguments.is_empty() {
+                    true => Doc::Concat(vec![name, open, close]),
+                    false => Doc::Group(Box::new(Doc::Concat(vec![name, open, Doc::Nest(Box::new(Doc::Concat(inner))), Doc::SoftLine, close])))
+                }
+            }
+        }
+    }
+
+    fn span(&mut self, span: Span) -> Doc {
+        self.token(span.start as usize, &self.source[span.range()])
+    }
+
+    // The text of the token at start with the comments in front of it and the comment behind it
+    fn token(&mut self, start: usize, text: &str) -> Doc {
+        let end: usize = start + text.len();
+        let leading: Doc = self.comments_before(start);
+        // the comments inside of an interpolation are part of its text
+        self.skip_comments(end);
+
+        let trailing: Option<Doc> = self.comments.get(self.next_comment)
+            .filter(|comment| comment.trailing && self.source[end..comment.start].trim().is_empty())
+            .map(|comment| Doc::Comment(comment.clone()));
+        self.next_comment += trailing.is_some() as usize;
+        Doc::Concat([leading, Doc::Text(text.to_string())].into_iter().chain(trailing).collect())
+    }
+
+    fn comments_before(&mut self, offset: usize) -> Doc {
+        let mut comments: Vec<Doc> = Vec::new();
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|comment| comment.start < offset) {
+            comments.push(Doc::Comment(comment.clone()));
+            self.next_comment += 1;
+        }
+        Doc::Concat(comments)
+    }
+
+    fn skip_comments(&mut self, offset: usize) {
+        while self.comments.get(self.next_comment).is_some_and(|comment| comment.start < offset) {
+            self.next_comment += 1;
+        }
+    }
+}
+
+// The operands of a chain of operators with the same precedence, a + b - c is a, [(+, b), (-, c)]
+fn chain(expression: &Expr) -> (&Expr, Vec<(Span, &Expr)>) {
+    match &expression.kind {
+        ExprKind::Binary { operator_span, left, right, .. } => {
+            let level: Option<Precedence> = precedence(&expression.kind);
+            // ** is right associative, the chain continues on the right
+            let (first, mut rest): (&Expr, Vec<(Span, &Expr)>) = match precedence(&left.kind) == level && level != Some(Precedence::POWER) {
+                true => chain(left),
+                false => (left, Vec::new())
+            };
+            rest.push((*operator_span, right));
+            (first, rest)
+        },
+        _ => (expression, Vec::new())
+    }
+}
+
+fn precedence(kind: &ExprKind) -> Option<Precedence> {
+    use BinaryOperator::*;
+
+    match kind {
+        ExprKind::Binary { operator, .. } => Some(match operator {
+            Equal | NotEqual => Precedence::EQUALITY,
+            Greater | GreaterEqual | Less | LessEqual => Precedence::COMPARISON,
+            Add | Subtract => Precedence::TERM,
+            Multiply | Divide | Modulo | FloorDivide => Precedence::FACTOR,
+            Power => Precedence::POWER,
+            BitAnd => Precedence::BITAND,
+            BitOr => Precedence::BITOR,
+            BitXor => Precedence::BITXOR,
+            ShiftLeft | ShiftRight => Precedence::SHIFT
+        }),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break
+}
+
+struct Printer<'a> {
+    config: &'a FormatConfig,
+    output: String,
+    column: usize,
+    line_start: bool,
+    pending: Option<bool> // a comment ended the line, the next text starts on a new one, true after a blank line
+}
+
+impl Printer<'_> {
+    fn print(&mut self, document: &Doc) {
+        let mut commands: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, document)];
+
+        while let Some((indent, mode, document)) = commands.pop() {
+            match document {
+                // the space behind an operator isn't needed once a comment ended the line
+                Doc::Text(text) if self.pending.is_some() && text.trim().is_empty() => {},
+                Doc::Text(text) => {
+                    if let Some(blank) = self.pending.take() {
+                        self.newline(indent, blank);
+                    }
+                    self.output.push_str(text);
+                    self.column = match text.rfind('\n') {
+                        Some(newline) => text[newline + 1..].chars().count(),
+                        None => self.column + text.chars().count()
+                    };
+                    self.line_start = false;
+                },
+                Doc::Line | Doc::SoftLine if mode == Mode::Break => {
+                    let blank: bool = self.pending.take().unwrap_or(false);
+                    self.newline(indent, blank);
+                },
+                Doc::Line => {
+                    self.output.push(' ');
+                    self.column += 1;
+                },
+                Doc::SoftLine => {},
+                Doc::Nest(inner) => commands.push((indent + 1, mode, inner)),
+                Doc::Group(inner) => {
+                    let flat: bool = mode == Mode::Flat || self.fits(self.config.width as isize - self.column as isize, (indent, inner), &commands);
+                    commands.push((indent, if flat { Mode::Flat } else { Mode::Break }, inner));
+                },
+                Doc::Concat(documents) => commands.extend(documents.iter().rev().map(|document| (indent, mode, document))),
+                Doc::Comment(comment) if comment.trailing => {
+                    self.trim();
+                    self.output.push(' ');
+                    self.output.push_str(&comment.text);
+                    self.line_start = false;
+                    self.pending = Some(comment.blank_after);
+                },
+                Doc::Comment(comment) => {
+                    let blank: bool = comment.blank_before || self.pending.take().unwrap_or(false);
+                    if !self.line_start || (blank && !self.output.is_empty()) {
+                        self.newline(indent, blank && !self.output.is_empty());
+                    }
+                    self.output.push_str(&comment.text);
+                    self.line_start = false;
+                    self.pending = Some(comment.blank_after);
+                }
+            }
+        }
+    }
+
+    // Whether the group fits flat into width, followed by the rest up to the next line break
+    fn fits(&self, mut width: isize, (indent, group): (usize, &Doc), rest: &[(usize, Mode, &Doc)]) -> bool {
+        let mut commands: Vec<(usize, Mode, &Doc)> = vec![(indent, Mode::Flat, group)];
+        let mut rest = rest.iter().rev();
+        let mut commented: bool = false; // a comment ended the line, nothing else can follow on it
+
+        while width >= 0 {
+            let Some((indent, mode, document)) = commands.pop().or_else(|| rest.next().copied()) else {
+                return true;
+            };
+            match document {
+                Doc::Text(_) | Doc::Line if commented && mode == Mode::Flat => return false,
+                Doc::Text(text) => match text.split_once('\n') {
+                    Some((first, _)) => return width >= first.chars().count() as isize,
+                    None => width -= text.chars().count() as isize
+                },
+                Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+                Doc::Line => width -= 1,
+                Doc::SoftLine => {},
+                Doc::Nest(inner) | Doc::Group(inner) => commands.push((indent, mode, inner)),
+                Doc::Concat(documents) => commands.extend(documents.iter().rev().map(|document| (indent, mode, document))),
+                Doc::Comment(comment) if comment.trailing => {
+                    width -= comment.text.chars().count() as isize + 1;
+                    commented = true;
+                },
+                // a comment on a line of its own breaks the group around it
+                Doc::Comment(_) => return mode == Mode::Break
+            }
+        }
+        false
+    }
+
+    fn newline(&mut self, indent: usize, blank: bool) {
+        self.trim();
+        self.output.push('\n');
+        if blank {
+            self.output.push('\n');
+        }
+        self.output.push_str(&" ".repeat(indent * self.config.indent));
+        self.column = indent * self.config.indent;
+        self.line_start = true;
+    }
+
+    fn trim(&mut self) {
+        self.output.truncate(self.output.trim_end_matches(' ').len());
+    }
+}
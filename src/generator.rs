@@ -0,0 +1,158 @@
+use super::ast::{BinaryOperator, CompileError, Expr, ExprKind, Program, UnaryOperator};
+use super::chunk::Chunk;
+use super::opcode::OpCode;
+use super::value::Value;
+
+use folding::*;
+
+// The last pass: writes the resolved tree into the chunk
+pub struct Generator<'a> {
+    chunk: &'a mut Chunk,
+    fold_constants: bool
+}
+
+impl <'a> Generator<'a> {
+    pub fn new(chunk: &'a mut Chunk, fold_constants: bool) -> Self {
+        Self { chunk, fold_constants }
+    }
+
+    pub fn generate(&mut self, program: &Program) -> Result<(), CompileError> {
+        self.expression(&program.expression)?;
+        self.chunk.add_opcode(OpCode::Return, program.end.line);
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expr) -> Result<(), CompileError> {
+        match &expression.kind {
+            ExprKind::Literal(value) => self.chunk.add_value(value.clone(), expression.span.line),
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+                self.chunk.add_opcode_with_operand(OpCode::Interpolate, parts.len() as u32, expression.span.line);
+            },
+            ExprKind::Grouping(inner) => self.expression(inner)?,
+            ExprKind::Unary { operator, operator_span, operand } => {
+                let start: usize = self.chunk.len();
+                self.expression(operand)?;
+                self.write_folded(unary_opcode(*operator), &[start], operator_span.line);
+            },
+            ExprKind::Binary { operator, operator_span, left, right } => {
+                let left_start: usize = self.chunk.len();
+                self.expression(left)?;
+                let right_start: usize = self.chunk.len();
+                self.expression(right)?;
+                self.write_folded(binary_opcode(*operator), &[left_start, right_start], operator_span.line);
+            },
+            ExprKind::Call { name, callee, arguments, native } => {
+                let index: usize = native.ok_or_else(|| CompileError::at(*callee, &format!("Unresolved function '{name}'")))?;
+
+                for argument in arguments {
+                    self.expression(argument)?;
+                }
+                self.chunk.add_opcode_with_operand(OpCode::CallNative, index as u32, callee.line);
+            }
+        }
+        Ok(())
+    }
+
+    // Replaces an operation on literal operands by its result, operations that fail are left to the VM
+    // so that the error is reported at runtime like before
+    fn write_folded(&mut self, opcode: OpCode, operands: &[usize], line: u32) {
+        if self.fold_constants {
+            if let Some(value) = constant_operands(self.chunk, operands).and_then(|values| fold(opcode, values)) {
+                self.chunk.truncate(operands[0]);
+                self.chunk.add_value(value, line);
+                return;
+            }
+        }
+        self.chunk.add_opcode(opcode, line);
+    }
+}
+
+fn unary_opcode(operator: UnaryOperator) -> OpCode {
+    match operator {
+        UnaryOperator::Negate => OpCode::Negate,
+        UnaryOperator::Not => OpCode::Not,
+        UnaryOperator::BitNot => OpCode::BitNot
+    }
+}
+
+fn binary_opcode(operator: BinaryOperator) -> OpCode {
+    match operator {
+        BinaryOperator::Equal => OpCode::Equal,
+        BinaryOperator::NotEqual => OpCode::NotEqual,
+        BinaryOperator::Greater => OpCode::Greater,
+        BinaryOperator::GreaterEqual => OpCode::GreaterEqual,
+        BinaryOperator::Less => OpCode::Less,
+        BinaryOperator::LessEqual => OpCode::LessEqual,
+        BinaryOperator::Add => OpCode::Add,
+        BinaryOperator::Subtract => OpCode::Subtract,
+        BinaryOperator::Multiply => OpCode::Multiply,
+        BinaryOperator::Divide => OpCode::Divide,
+        BinaryOperator::Modulo => OpCode::Modulo,
+        BinaryOperator::FloorDivide => OpCode::FloorDivide,
+        BinaryOperator::Power => OpCode::Power,
+        BinaryOperator::BitAnd => OpCode::BitAnd,
+        BinaryOperator::BitOr => OpCode::BitOr,
+        BinaryOperator::BitXor => OpCode::BitXor,
+        BinaryOperator::ShiftLeft => OpCode::ShiftLeft,
+        BinaryOperator::ShiftRight => OpCode::ShiftRight
+    }
+}
+
+mod folding {
+    use crate::chunk::{decode_opcode, decode_value, instruction_size, INSTRUCTION_SIZE};
+    use crate::value::{Comparison, FloorDiv, Negate, Pow};
+
+    use super::{Chunk, OpCode, Value};
+
+    // Every operand starts at one of the offsets and ends where the next one starts, the last one ends with the chunk.
+    // Returns the values if each operand is a single constant
+    pub fn constant_operands(chunk: &Chunk, operands: &[usize]) -> Option<Vec<Value>> {
+        let code: &[u8] = chunk.code();
+        let ends = operands.iter().skip(1).copied().chain([code.len()]);
+
+        operands.iter().zip(ends).map(|(start, end)| {
+            let is_constant: bool = *start < end && decode_opcode(code, *start) == Ok(OpCode::Constant) && start + instruction_size(OpCode::Constant) == end;
+            is_constant.then(|| unsafe { decode_value(code, start + INSTRUCTION_SIZE) })
+        }).collect()
+    }
+
+    // Evaluates the operation with the same Value traits the VM uses
+    pub fn fold(opcode: OpCode, operands: Vec<Value>) -> Option<Value> {
+        let mut operands = operands.into_iter();
+        let a: Value = operands.next()?;
+
+        let result = match opcode {
+            OpCode::Negate => a.negate(),
+            OpCode::Not => Ok(Value::Boolean(a.is_falsey())),
+            OpCode::BitNot => !a,
+            _ => {
+                let b: Value = operands.next()?;
+                match opcode {
+                    OpCode::Add => a + b,
+                    OpCode::Subtract => a - b,
+                    OpCode::Multiply => a * b,
+                    OpCode::Divide => a / b,
+                    OpCode::Modulo => a % b,
+                    OpCode::FloorDivide => a.floor_div(b),
+                    OpCode::Power => a.pow(b),
+                    OpCode::BitAnd => a & b,
+                    OpCode::BitOr => a | b,
+                    OpCode::BitXor => a ^ b,
+                    OpCode::ShiftLeft => a << b,
+                    OpCode::ShiftRight => a >> b,
+                    OpCode::Equal => Ok(a.equal(&b)),
+                    OpCode::NotEqual => Ok(a.not_equal(&b)),
+                    OpCode::Greater => a.greater(&b),
+                    OpCode::GreaterEqual => a.greater_equal(&b),
+                    OpCode::Less => a.less(&b),
+                    OpCode::LessEqual => a.less_equal(&b),
+                    _ => return None
+                }
+            }
+        };
+        result.ok()
+    }
+}
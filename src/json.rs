@@ -0,0 +1,373 @@
+use std::fmt::{self, Display, Write as _};
+use std::io::{self, BufRead, Write};
+
+/* JSON
+
+ The debug adapter and the language server talk JSON over stdin and stdout. Both protocols frame every
+ message with a header:
+
+  Content-Length: LENGTH\r\n
+  \r\n
+  LENGTH bytes of JSON
+
+ read_message fails with InvalidData on a malformed message (no Content-Length, a body that isn't JSON) and
+ can read the next message afterwards, so a server can answer with an error and go on.
+
+ Json only covers what the protocols need: numbers are f64 and objects keep the order of their keys, which
+ makes the output predictable for tests.
+
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser: Parser = Parser { bytes: text.as_bytes(), position: 0 };
+        let json: Json = parser.value()?;
+        parser.whitespace();
+        match parser.position == text.len() {
+            true => Ok(json),
+            false => Err(format!("Unexpected data after the end at byte {}", parser.position))
+        }
+    }
+
+    // The member key of an object, Null for everything else so lookups can be chained
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map_or(&Json::Null, |(_, value)| value),
+            _ => &Json::Null
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(number) if number.fract() == 0.0 && *number >= 0.0 => Some(*number as u64),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(boolean) => Some(*boolean),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(array) => Some(array),
+            _ => None
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(value: Vec<Json>) -> Self {
+        Json::Array(value)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(boolean) => write!(f, "{boolean}"),
+            // integers are written without a fraction, NaN and infinity don't exist in JSON
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => write!(f, "{}", *number as i64),
+            Json::Number(number) if number.is_finite() => write!(f, "{number}"),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(string) => write_string(f, string),
+            Json::Array(array) => {
+                f.write_char('[')?;
+                for (index, value) in array.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            },
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for character in string.chars() {
+        match character {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            character if (character as u32) < 0x20 => write!(f, "\\u{:04x}", character as u32)?,
+            character => f.write_char(character)?
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(byte) => Err(format!("Unexpected '{}' at byte {}", *byte as char, self.position)),
+            None => Err("Unexpected end of the JSON".to_string())
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        let mut members: Vec<(String, Json)> = Vec::new();
+        self.position += 1;
+        self.whitespace();
+        if self.eat(b'}') {
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.whitespace();
+            if self.bytes.get(self.position) != Some(&b'"') {
+                return Err(format!("Expected a key at byte {}", self.position));
+            }
+            let key: String = self.string()?;
+            self.whitespace();
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            self.whitespace();
+            if self.eat(b'}') {
+                return Ok(Json::Object(members));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        let mut array: Vec<Json> = Vec::new();
+        self.position += 1;
+        self.whitespace();
+        if self.eat(b']') {
+            return Ok(Json::Array(array));
+        }
+
+        loop {
+            array.push(self.value()?);
+            self.whitespace();
+            if self.eat(b']') {
+                return Ok(Json::Array(array));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut string: String = String::new();
+        self.position += 1;
+
+        loop {
+            let start: usize = self.position;
+            while self.bytes.get(self.position).is_some_and(|byte| *byte != b'"' && *byte != b'\\') {
+                self.position += 1;
+            }
+            // the input is a str and the loop stops at ASCII characters, so the slice is valid UTF-8
+            string.push_str(std::str::from_utf8(&self.bytes[start..self.position]).unwrap());
+
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(string);
+                },
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped: u8 = *self.bytes.get(self.position).ok_or("Unexpected end of a string")?;
+                    self.position += 1;
+                    match escaped {
+                        b'"' => string.push('"'),
+                        b'\\' => string.push('\\'),
+                        b'/' => string.push('/'),
+                        b'b' => string.push('\u{8}'),
+                        b'f' => string.push('\u{c}'),
+                        b'n' => string.push('\n'),
+                        b'r' => string.push('\r'),
+                        b't' => string.push('\t'),
+                        b'u' => string.push(self.unicode()?),
+                        _ => return Err(format!("Invalid escape at byte {}", self.position - 1))
+                    }
+                },
+                _ => return Err("Unexpected end of a string".to_string())
+            }
+        }
+    }
+
+    // The 4 hex digits after \u, a surrogate pair takes up two escapes
+    fn unicode(&mut self) -> Result<char, String> {
+        let high: u32 = self.hex()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| format!("Invalid character at byte {}", self.position));
+        }
+
+        if self.bytes.get(self.position..self.position + 2) != Some(b"\\u") {
+            return Err(format!("Expected the second half of a surrogate pair at byte {}", self.position));
+        }
+        self.position += 2;
+        let low: u32 = self.hex()?;
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF))
+            .ok_or_else(|| format!("Invalid surrogate pair at byte {}", self.position))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits: &[u8] = self.bytes.get(self.position..self.position + 4).ok_or("Unexpected end of a string")?;
+        let digits: &str = std::str::from_utf8(digits).map_err(|_| "Invalid unicode escape".to_string())?;
+        self.position += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid unicode escape '{digits}'"))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start: usize = self.position;
+        while self.bytes.get(self.position).is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.position += 1;
+        }
+        let text: &str = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        text.parse::<f64>().map(Json::Number).map_err(|_| format!("Invalid number '{text}'"))
+    }
+
+    fn keyword(&mut self, keyword: &str, json: Json) -> Result<Json, String> {
+        match self.bytes[self.position..].starts_with(keyword.as_bytes()) {
+            true => {
+                self.position += keyword.len();
+                Ok(json)
+            },
+            false => Err(format!("Unexpected data at byte {}", self.position))
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self.bytes.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found: bool = self.bytes.get(self.position) == Some(&byte);
+        self.position += found as usize;
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        match self.eat(byte) {
+            true => Ok(()),
+            false => Err(format!("Expected '{}' at byte {}", byte as char, self.position))
+        }
+    }
+}
+
+// The next message, None at the end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length: Option<usize> = None;
+    let mut header: String = String::new();
+
+    loop {
+        header.clear();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header: &str = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        // the header of a message can follow the body of one without a Content-Length on the same line
+        if let Some(start) = header.to_ascii_lowercase().find("content-length:") {
+            length = header[start + "content-length:".len()..].trim().parse::<usize>().ok();
+        }
+    }
+
+    let length: usize = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "A message without a Content-Length"))?;
+    let mut body: Vec<u8> = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    let body: String = String::from_utf8(body).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "A message that isn't UTF-8"))?;
+    Json::parse(&body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body: String = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
@@ -8,24 +8,41 @@ const fn make_token(lexer: &Lexer, token_type: TokenType) -> Token {
     Token { token_type, range: (lexer.start as u32, lexer.current as u32), line: lexer.line }
 }
 
+// Tokens spanning several lines report the line they started on
+const fn make_token_at(lexer: &Lexer, token_type: TokenType, line: u32) -> Token {
+    Token { token_type, range: (lexer.start as u32, lexer.current as u32), line }
+}
+
 pub struct Lexer<'a>{
     source: &'a [u8],
     current: usize,
     start: usize,
-    line: u32
+    line: u32,
+    interpolation: Vec<u32>, // every open '${' keeps track of the braces nested inside of it
+    comments: bool
 }
 
 
 impl <'a> Lexer <'a>{
     pub fn new(source: &'a str) -> Self {
-        Self {source: source.as_bytes(), current: 0, start: 0, line: 1 }
+        Self {source: source.as_bytes(), current: 0, start: 0, line: 1, interpolation: Vec::new(), comments: false }
+    }
+
+    // Keeps '#' comments as Comment tokens instead of skipping them, for tools like the formatter
+    pub fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
     }
 
     pub fn next_token(&mut self) -> Token{
-        self.skip_whitespaces();
-        self.skip_comment();
-        self.skip_whitespaces();
-        self.tokenize()
+        loop {
+            self.skip_whitespaces();
+            match self.peek() {
+                Some(b'#') if self.comments => return self.parse_comment(),
+                Some(b'#') => self.skip_comment(),
+                _ => return self.tokenize()
+            }
+        }
     }
 
     pub fn lexing(&mut self) -> Vec<Token>{
@@ -53,6 +70,7 @@ impl <'a> Lexer <'a>{
                     b'o' if self.match_pattern(b"or") => return make_token(self, Or),
                     b'p' if self.match_pattern(b"print") => return make_token(self, Print),
                     b'r' if self.match_pattern(b"return") => return make_token(self, Return),
+                    b'r' if matches!(self.peek(), Some(b'"' | b'#')) => return self.parse_raw_string(),
                     b'w' if self.match_pattern(b"while") => return make_token(self, While),
                     b'c' if self.match_pattern(b"class") => return make_token(self, Class),
                     b's' if self.match_pattern(b"super") => return make_token(self, Super),
@@ -69,25 +87,48 @@ impl <'a> Lexer <'a>{
                 match next_char {
                     b'(' => return make_token(self, LeftParent),
                     b')' => return make_token(self, RightParent),
-                    b'{' => return make_token(self, LeftBrace),
-                    b'}' => return make_token(self, RightBrace),
+                    b'{' => {
+                        if let Some(depth) = self.interpolation.last_mut() {
+                            *depth += 1;
+                        }
+                        return make_token(self, LeftBrace)
+                    },
+                    b'}' => match self.interpolation.last_mut() {
+                        Some(0) => {
+                            // closes the embedded expression, the rest belongs to the string again
+                            self.interpolation.pop();
+                            return self.parse_string();
+                        },
+                        Some(depth) => {*depth -= 1; return make_token(self, RightBrace)},
+                        None => return make_token(self, RightBrace)
+                    },
                     b'+' => return make_token(self, Plus),
                     b'-' => return make_token(self, Minus),
+                    b'*' if self.match_pattern(b"**") => return make_token(self, StarStar),
                     b'*' => return make_token(self, Star),
+                    b'/' if self.match_pattern(b"//") => return make_token(self, SlashSlash),
                     b'/' => return make_token(self, Slash),
+                    b'%' => return make_token(self, Percent),
+                    b'&' => return make_token(self, Ampersand),
+                    b'|' => return make_token(self, Pipe),
+                    b'^' => return make_token(self, Caret),
+                    b'~' => return make_token(self, Tilde),
                     b',' => return make_token(self, Comma),
                     b'.' => return make_token(self, Dot),
                     b';' => return make_token(self, Semicolon),
+                    b'"' if self.match_pattern(b"\"\"\"") => return self.parse_multiline_string(),
                     b'"' => return self.parse_string(),
                     b'!' if self.match_pattern(b"!=") => return make_token(self, BangEqual),
                     b'!' => return make_token(self, Bang),
                     b'=' if self.match_pattern(b"==") => return make_token(self, EqualEqual),
                     b'=' => return make_token(self, Equal),
+                    b'>' if self.match_pattern(b">>") => return make_token(self, GreaterGreater),
                     b'>' if self.match_pattern(b">=") => return make_token(self, GreaterEqual),
                     b'>' => return make_token(self, Greater),
+                    b'<' if self.match_pattern(b"<<") => return make_token(self, LessLess),
                     b'<' if self.match_pattern(b"<=") => return make_token(self, LessEqual),
                     b'<' => return make_token(self, Less),
-                    _ => return make_token(&self, Error),
+                    _ => return self.error_token(),
                 }
             }
         }
@@ -123,10 +164,23 @@ impl <'a> Lexer <'a>{
         }
     }
 
+    // The comment ends before the newline, which is left to skip_whitespaces
+    fn parse_comment(&mut self) -> Token {
+        self.start = self.current;
+        while self.peek().is_some_and(|next_char| *next_char != b'\n') {
+            self.current += 1;
+        }
+        make_token(self, Comment)
+    }
+
     fn peek(&self) -> Option<&u8> {
         self.source.get(self.current)
     }
 
+    fn peek_next(&self) -> Option<&u8> {
+        self.source.get(self.current + 1)
+    }
+
     fn advance(&mut self) -> Option<u8> {
         if let Some(next_char) = self.source.get(self.current) {
             self.current += 1;
@@ -136,7 +190,7 @@ impl <'a> Lexer <'a>{
     }
 
     fn match_pattern<T: PartialEq<u8>>(&mut self, pattern: &[T]) -> bool {
-        if self.start + pattern.len() < self.source.len() {
+        if self.start + pattern.len() <= self.source.len() {
             let range: Range<usize> = self.start..self.start + pattern.len();
 
             if *pattern == self.source[range] {
@@ -148,36 +202,176 @@ impl <'a> Lexer <'a>{
     }
 
     fn parse_string(&mut self) -> Token{
+        let line: u32 = self.line;
+
+        while let Some(next_char) = self.advance() {
+            match next_char {
+                b'"' => return make_token_at(self, Text, line),
+                b'$' if self.peek() == Some(&b'{') => {
+                    self.advance();
+                    self.interpolation.push(0);
+                    return make_token_at(self, TextInterpolation, line);
+                },
+                b'\n' => self.line += 1,
+                _ => {}
+            }
+        }
+        self.error_token()
+    }
+
+    fn parse_multiline_string(&mut self) -> Token {
+        let line: u32 = self.line;
+
         while let Some(next_char) = self.advance() {
-            if next_char == b'"' {
-                return make_token(self, Text);
+            match next_char {
+                b'"' if self.source[self.current..].starts_with(b"\"\"") => {
+                    self.current += 2;
+                    return make_token_at(self, MultilineText, line);
+                },
+                b'\\' => self.skip_escaped(),
+                b'\n' => self.line += 1,
+                _ => {}
             }
         }
+        self.error_token()
+    }
+
+    // an invalid token ends on a character boundary, so that a character outside of ASCII isn't split
+    fn error_token(&mut self) -> Token {
+        while self.peek().is_some_and(|byte| byte & 0b1100_0000 == 0b1000_0000) {
+            self.current += 1;
+        }
         make_token(self, Error)
     }
 
+    // r"..." or r#"..."#, the number of '#' has to match on both sides
+    fn parse_raw_string(&mut self) -> Token {
+        let line: u32 = self.line;
+        let mut hashes: usize = 0;
+
+        while self.peek() == Some(&b'#') {
+            hashes += 1;
+            self.advance();
+        }
+
+        if self.advance() != Some(b'"') {
+            return self.error_token();
+        }
+
+        while let Some(next_char) = self.advance() {
+            match next_char {
+                b'"' if self.source.get(self.current..self.current + hashes).is_some_and(|closing| closing.iter().all(|byte| *byte == b'#')) => {
+                    self.current += hashes;
+                    return make_token_at(self, RawText, line);
+                },
+                b'\n' => self.line += 1,
+                _ => {}
+            }
+        }
+        self.error_token()
+    }
+
+    // the escaped character is validated by the compiler, the lexer only has to make sure it doesn't end the string
+    fn skip_escaped(&mut self) {
+        if self.advance() == Some(b'\n') {
+            self.line += 1;
+        }
+    }
+
     fn parse_identifier(&mut self) -> Token{
         while let Some(next_char) = self.peek() {
             match *next_char {
                 temp if temp.is_ascii_alphanumeric() || temp == b'_' => {self.current += 1},
-                _ => return make_token(self, Identifier)
+                _ => break
             }
         }
-        make_token(self, Error)
+        make_token(self, Identifier)
     }
 
     fn parse_number(&mut self) -> Token{
-        let mut is_float: bool = false;
+        // the first digit has already been consumed
+        let radix: Option<u32> = match (self.source[self.start], self.peek()) {
+            (b'0', Some(b'x' | b'X')) => Some(16),
+            (b'0', Some(b'b' | b'B')) => Some(2),
+            (b'0', Some(b'o' | b'O')) => Some(8),
+            _ => None
+        };
+
+        if let Some(radix) = radix {
+            self.advance();
+            if !self.consume_digits(radix) {
+                return self.malformed_number();
+            }
+            return self.finish_number(Integer);
+        }
+
+        self.current = self.start;
+        if !self.consume_digits(10) {
+            return self.malformed_number();
+        }
+
+        let mut token_type: TokenType = Integer;
+
+        if self.peek() == Some(&b'.') && self.peek_next().is_some_and(|next_char| next_char.is_ascii_digit()) {
+            self.advance();
+            if !self.consume_digits(10) {
+                return self.malformed_number();
+            }
+            token_type = Float;
+        }
+
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.advance();
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.advance();
+            }
+            if !self.peek().is_some_and(|next_char| next_char.is_ascii_digit()) || !self.consume_digits(10) {
+                return self.malformed_number();
+            }
+            token_type = Float;
+        }
+
+        // the suffix 'd' makes the literal an exact decimal, e.g. 0.1d or 5d
+        if self.peek() == Some(&b'd') {
+            self.advance();
+            token_type = Decimal;
+        }
+
+        self.finish_number(token_type)
+    }
+
+    // Consumes digits and '_' separators, a separator has to be followed by a digit
+    fn consume_digits(&mut self, radix: u32) -> bool {
+        let mut digits: usize = 0;
+        let mut trailing_separator: bool = false;
 
         while let Some(next_char) = self.peek() {
-            match next_char {
-                b'0'..=b'9' => {self.current += 1;},
-                b'.' if !is_float => {is_float = true; self.current += 1},
-                b'.' if is_float => break,
-                b' ' | b'\n' => {if is_float {return make_token(self, Float);} else {return make_token(self, Integer)}},
+            match *next_char {
+                b'_' => trailing_separator = true,
+                digit if (digit as char).is_digit(radix) => {digits += 1; trailing_separator = false},
                 _ => break
             }
+            self.current += 1;
         }
-        make_token(self, Error)
+        digits > 0 && !trailing_separator
+    }
+
+    // A number has to end at any character that can't continue it, '12ab' or '1.2.3' are errors
+    fn finish_number(&mut self, token_type: TokenType) -> Token {
+        match self.peek() {
+            Some(next_char) if next_char.is_ascii_alphanumeric() || *next_char == b'_' => self.malformed_number(),
+            Some(b'.') if self.peek_next().is_some_and(|next_char| next_char.is_ascii_digit()) => self.malformed_number(),
+            _ => make_token(self, token_type)
+        }
+    }
+
+    fn malformed_number(&mut self) -> Token {
+        while let Some(next_char) = self.peek() {
+            match *next_char {
+                temp if temp.is_ascii_alphanumeric() || temp == b'_' || temp == b'.' => self.current += 1,
+                _ => break
+            }
+        }
+        self.error_token()
     }
 }
@@ -0,0 +1,26 @@
+pub mod ast;
+pub mod common;
+pub mod data_structures;
+pub mod lexer;
+pub mod parser;
+pub mod precedence;
+pub mod token;
+pub mod value;
+
+pub use ast::{CompileError, Expr, ExprKind, Program, Span};
+pub use lexer::Lexer;
+pub use parser::Parser;
+
+
+/* LIBRARY
+
+ The front end of eos as a library, for tools like linters that want the syntax tree instead of the bytecode:
+
+    initialize_interned_string();
+    let tokens: Vec<Token> = Lexer::new(&source).lexing();
+    let program: Program = Parser::new(tokens, SharedData::new(&source)).parse()?;
+
+ Every Expr carries the Span of the source it was parsed from. The binary is built on top of these modules,
+ the passes after the parser stay inside of it.
+
+ */
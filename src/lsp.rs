@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+use super::ast::{CompileError, Expr, ExprKind, Program, Span};
+use super::chunk::{format_constant, Chunk};
+use super::common::{SharedData, DEFAULT_STACK_CAPACITY};
+use super::compiler::Compiler;
+use super::json::{read_message, write_message, Json};
+use super::lexer::Lexer;
+use super::native::{find_native, NATIVES};
+use super::parser::Parser;
+use super::resolver::resolve;
+use super::token::{Token, TokenType::{self, *}};
+
+/* LANGUAGE SERVER PROTOCOL
+
+ serve speaks the Language Server Protocol over stdin and stdout. Every open document is compiled again when
+ it changes, its CompileErrors are published as diagnostics. The requests work on the tokens of the Lexer and
+ the tree of the Parser:
+
+  textDocument/semanticTokens/full   keywords, functions, strings, numbers and operators
+  textDocument/hover                 the type and value of a literal, the arity of a native
+  textDocument/references            every call of the same native
+  textDocument/definition            the entry of the native in NATIVES
+  textDocument/documentSymbol        the program as 'main' with the calls inside of it
+
+ The language has no variables or functions yet, the only names are the natives and they are defined in Rust,
+ so a definition points into native.rs of the source tree eos was built from. Documents are synced in full.
+ Positions count lines from 0 and characters in UTF-16 code units like the protocol expects. A malformed
+ message is answered with a ParseError and the server reads on.
+
+ */
+
+const TOKEN_TYPES: [&str; 5] = ["keyword", "function", "string", "number", "operator"];
+const NATIVES_SOURCE: &str = include_str!("native.rs");
+const NATIVES_URI: &str = concat!("file://", env!("CARGO_MANIFEST_DIR"), "/src/native.rs");
+
+const PARSE_ERROR: f64 = -32700.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const SYMBOL_FUNCTION: u32 = 12;
+const SEVERITY_ERROR: u32 = 1;
+
+struct Server<W: Write> {
+    output: W,
+    documents: BTreeMap<String, String> // by uri
+}
+
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server: Server<_> = Server { output, documents: BTreeMap::new() };
+
+    while let Some(message) = server.next_message(&mut input)? {
+        let params: &Json = message.get("params");
+        let uri: String = params.get("textDocument").get("uri").as_str().unwrap_or_default().to_string();
+
+        let result: Json = match message.get("method").as_str().unwrap_or_default() {
+            "initialize" => Json::object([
+                ("capabilities", Json::object([
+                    ("textDocumentSync", Json::from(1u32)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("definitionProvider", Json::Bool(true)),
+                    ("referencesProvider", Json::Bool(true)),
+                    ("documentSymbolProvider", Json::Bool(true)),
+                    ("semanticTokensProvider", Json::object([
+                        ("legend", Json::object([
+                            ("tokenTypes", Json::from(TOKEN_TYPES.iter().map(|name| Json::from(*name)).collect::<Vec<Json>>())),
+                            ("tokenModifiers", Json::from(Vec::new()))
+                        ])),
+                        ("full", Json::Bool(true))
+                    ]))
+                ])),
+                ("serverInfo", Json::object([("name", Json::from("eos"))]))
+            ]),
+            "textDocument/didOpen" => {
+                let text: &str = params.get("textDocument").get("text").as_str().unwrap_or_default();
+                server.open(uri, text.to_string())?;
+                continue;
+            },
+            "textDocument/didChange" => {
+                // with full sync the last change holds the whole document
+                let changes: &[Json] = params.get("contentChanges").as_array().unwrap_or_default();
+                if let Some(text) = changes.last().and_then(|change| change.get("text").as_str()) {
+                    server.open(uri, text.to_string())?;
+                }
+                continue;
+            },
+            "textDocument/didClose" => {
+                server.documents.remove(&uri);
+                server.publish(&uri, Vec::new())?;
+                continue;
+            },
+            "textDocument/semanticTokens/full" => {
+                let text: &str = server.document(&uri);
+                Json::object([("data", Json::from(semantic_tokens(text).into_iter().map(Json::from).collect::<Vec<Json>>()))])
+            },
+            "textDocument/hover" => {
+                let text: &str = server.document(&uri);
+                hover(text, offset(text, params.get("position")))
+            },
+            "textDocument/references" => {
+                let text: &str = server.document(&uri);
+                let name: Option<&str> = name_at(text, offset(text, params.get("position")));
+                let declaration: Option<Json> = name.and_then(definition).filter(|_| params.get("context").get("includeDeclaration") == &Json::Bool(true));
+                let calls = name.map(|name| references(text, name)).unwrap_or_default().into_iter()
+                    .map(|span| Json::object([("uri", Json::from(uri.as_str())), ("range", range(text, span))]));
+                Json::from(declaration.into_iter().chain(calls).collect::<Vec<Json>>())
+            },
+            "textDocument/definition" => {
+                let text: &str = server.document(&uri);
+                name_at(text, offset(text, params.get("position"))).and_then(definition).unwrap_or(Json::Null)
+            },
+            "textDocument/documentSymbol" => {
+                let text: &str = server.document(&uri);
+                Json::from(document_symbols(text))
+            },
+            "initialized" | "$/cancelRequest" | "$/setTrace" => continue,
+            "shutdown" => Json::Null,
+            "exit" => return Ok(()),
+            method => {
+                // notifications without an id are ignored
+                if message.get("id") != &Json::Null {
+                    server.fail(message.get("id"), METHOD_NOT_FOUND, &format!("Unsupported method '{method}'"))?;
+                }
+                continue;
+            }
+        };
+        server.respond(message.get("id"), result)?;
+    }
+    Ok(())
+}
+
+impl <W: Write> Server<W> {
+    fn document(&self, uri: &str) -> &str {
+        self.documents.get(uri).map_or("", String::as_str)
+    }
+
+    fn open(&mut self, uri: String, text: String) -> io::Result<()> {
+        let diagnostics: Vec<Json> = diagnostics(&text).iter().map(|error| Json::object([
+            ("range", error_range(&text, error)),
+            ("severity", Json::from(SEVERITY_ERROR)),
+            ("source", Json::from("eos")),
+            ("message", Json::from(error.message.as_str()))
+        ])).collect();
+
+        self.documents.insert(uri.clone(), text);
+        self.publish(&uri, diagnostics)
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        write_message(&mut self.output, &Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            ("params", Json::object([("uri", Json::from(uri)), ("diagnostics", Json::from(diagnostics))]))
+        ]))
+    }
+
+    fn respond(&mut self, id: &Json, result: Json) -> io::Result<()> {
+        write_message(&mut self.output, &Json::object([("jsonrpc", Json::from("2.0")), ("id", id.clone()), ("result", result)]))
+    }
+
+    fn fail(&mut self, id: &Json, code: f64, message: &str) -> io::Result<()> {
+        write_message(&mut self.output, &Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("id", id.clone()),
+            ("error", Json::object([("code", Json::Number(code)), ("message", Json::from(message))]))
+        ]))
+    }
+
+    // The next message, a malformed one is answered with a ParseError without an id and skipped
+    fn next_message(&mut self, input: &mut impl BufRead) -> io::Result<Option<Json>> {
+        loop {
+            match read_message(input) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => self.fail(&Json::Null, PARSE_ERROR, &format!("Malformed message: {err}"))?,
+                message => return message
+            }
+        }
+    }
+}
+
+fn tokens(text: &str) -> Vec<Token> {
+    Lexer::new(text).lexing()
+}
+
+// The tree of a document that parses and resolves, the language features need it
+fn parse(text: &str) -> Option<Program> {
+    let source: String = text.to_string();
+    let mut program: Program = Parser::new(Lexer::new(&source).lexing(), SharedData::new(&source)).parse().ok()?;
+    resolve(&mut program).ok()?;
+    Some(program)
+}
+
+fn diagnostics(text: &str) -> Vec<CompileError> {
+    let source: String = text.to_string();
+    let mut lexer: Lexer = Lexer::new(&source);
+    let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+
+    Compiler::new(lexer.lexing(), SharedData::new(&source), SharedData::new(&chunk)).compile_with_errors().err().unwrap_or_default()
+}
+
+// Five numbers per token: line and start relative to the previous token, length, type and modifiers
+fn semantic_tokens(text: &str) -> Vec<u32> {
+    let mut data: Vec<u32> = Vec::new();
+    let mut previous: (u32, u32) = (0, 0);
+
+    for token in tokens(text) {
+        let Some(kind) = token_kind(token.token_type) else {
+            continue;
+        };
+
+        // multiline strings are split into one token per line, not every client supports tokens across lines
+        let mut start: usize = token.range.0 as usize;
+        let end: usize = (token.range.1 as usize).min(text.len());
+        while start < end {
+            let line_end: usize = text[start..end].find('\n').map_or(end, |newline| start + newline);
+            let (line, character): (u32, u32) = position(text, start);
+            let length: u32 = utf16_length(&text[start..line_end]);
+
+            if length > 0 {
+                let delta: u32 = if line == previous.0 { character - previous.1 } else { character };
+                data.extend([line - previous.0, delta, length, kind, 0]);
+                previous = (line, character);
+            }
+            start = line_end + 1;
+        }
+    }
+    data
+}
+
+fn token_kind(token_type: TokenType) -> Option<u32> {
+    let name: &str = match token_type {
+        And | Or | True | False | Null | Class | Else | For | Fun | If | Print | Return | Super | This | Var | While => "keyword",
+        Identifier => "function",
+        Text | TextInterpolation | RawText | MultilineText => "string",
+        Integer | Float | Decimal => "number",
+        Minus | Plus | Slash | Star | Percent | SlashSlash | StarStar | Ampersand | Pipe | Caret | Tilde | Bang | BangEqual
+            | Equal | EqualEqual | Greater | GreaterEqual | Less | LessEqual | LessLess | GreaterGreater => "operator",
+        _ => return None
+    };
+    TOKEN_TYPES.iter().position(|kind| *kind == name).map(|kind| kind as u32)
+}
+
+fn hover(text: &str, offset: usize) -> Json {
+    let Some(program) = parse(text) else {
+        return Json::Null;
+    };
+    let Some(expression) = innermost(&program.expression, offset) else {
+        return Json::Null;
+    };
+
+    let contents: String = match &expression.kind {
+        ExprKind::Literal(value) => {
+            let constant: String = format_constant(value);
+            let kind: &str = constant.split('(').next().unwrap_or_default();
+            format!("{kind} literal `{constant}`")
+        },
+        ExprKind::Interpolation(_) => "String interpolation".to_string(),
+        ExprKind::Call { native: Some(native), .. } => format!("native `{}` with {} arguments", NATIVES[*native].name, NATIVES[*native].arity),
+        _ => return Json::Null
+    };
+    Json::object([
+        ("contents", Json::object([("kind", Json::from("markdown")), ("value", Json::from(contents))])),
+        ("range", range(text, expression.span))
+    ])
+}
+
+// The name under offset, the tokens still have it when the document doesn't parse
+fn name_at(text: &str, offset: usize) -> Option<&str> {
+    tokens(text).into_iter()
+        .find(|token| token.token_type == Identifier && (token.range.0 as usize..=token.range.1 as usize).contains(&offset))
+        .map(|token| &text[token.get_range()])
+}
+
+fn references(text: &str, name: &str) -> Vec<Span> {
+    tokens(text).into_iter()
+        .filter(|token| token.token_type == Identifier && &text[token.get_range()] == name)
+        .map(|token| Span::from_token(&token))
+        .collect()
+}
+
+// The name of the native in its entry of NATIVES, native("name", arity, function)
+fn definition(name: &str) -> Option<Json> {
+    find_native(name)?;
+    let start: usize = NATIVES_SOURCE.find(&format!("native(\"{name}\""))? + "native(".len();
+    let line: u32 = NATIVES_SOURCE[..start].matches('\n').count() as u32 + 1;
+    let span: Span = Span { start: start as u32, end: (start + name.len() + 2) as u32, line };
+    Some(Json::object([("uri", Json::from(NATIVES_URI)), ("range", range(NATIVES_SOURCE, span))]))
+}
+
+fn document_symbols(text: &str) -> Vec<Json> {
+    let Some(program) = parse(text) else {
+        return Vec::new();
+    };
+
+    let mut calls: Vec<(&str, Span, Span)> = Vec::new();
+    collect_calls(&program.expression, &mut calls);
+    let children: Vec<Json> = calls.into_iter()
+        .map(|(name, span, callee)| symbol(text, name, "native", span, callee, Vec::new()))
+        .collect();
+    vec![symbol(text, "main", "program", program.expression.span, program.expression.span, children)]
+}
+
+fn symbol(text: &str, name: &str, detail: &str, span: Span, selection: Span, children: Vec<Json>) -> Json {
+    Json::object([
+        ("name", Json::from(name)),
+        ("detail", Json::from(detail)),
+        ("kind", Json::from(SYMBOL_FUNCTION)),
+        ("range", range(text, span)),
+        ("selectionRange", range(text, selection)),
+        ("children", Json::from(children))
+    ])
+}
+
+fn collect_calls<'a>(expression: &'a Expr, calls: &mut Vec<(&'a str, Span, Span)>) {
+    if let ExprKind::Call { name, callee, .. } = &expression.kind {
+        calls.push((name, expression.span, *callee));
+    }
+    children(expression).into_iter().for_each(|child| collect_calls(child, calls));
+}
+
+fn innermost(expression: &Expr, offset: usize) -> Option<&Expr> {
+    if !(expression.span.start as usize..=expression.span.end as usize).contains(&offset) {
+        return None;
+    }
+    children(expression).into_iter().find_map(|child| innermost(child, offset)).or(Some(expression))
+}
+
+fn children(expression: &Expr) -> Vec<&Expr> {
+    match &expression.kind {
+        ExprKind::Literal(_) => Vec::new(),
+        ExprKind::Interpolation(parts) => parts.iter().collect(),
+        ExprKind::Grouping(inner) => vec![inner],
+        ExprKind::Unary { operand, .. } => vec![operand],
+        ExprKind::Binary { left, right, .. } => vec![left, right],
+        ExprKind::Call { arguments, .. } => arguments.iter().collect()
+    }
+}
+
+// An error without a span covers its whole line
+fn error_range(text: &str, error: &CompileError) -> Json {
+    match error.span {
+        Some(span) => range(text, span),
+        None => {
+            let line: u32 = error.line.saturating_sub(1);
+            let length: u32 = text.lines().nth(line as usize).map_or(0, utf16_length);
+            Json::object([("start", location(line, 0)), ("end", location(line, length))])
+        }
+    }
+}
+
+fn range(text: &str, span: Span) -> Json {
+    let (start, end): ((u32, u32), (u32, u32)) = (position(text, span.start as usize), position(text, span.end as usize));
+    Json::object([("start", location(start.0, start.1)), ("end", location(end.0, end.1))])
+}
+
+fn location(line: u32, character: u32) -> Json {
+    Json::object([("line", Json::from(line)), ("character", Json::from(character))])
+}
+
+// The line and UTF-16 character of a byte offset
+fn position(text: &str, offset: usize) -> (u32, u32) {
+    let before: &str = &text[..offset.min(text.len())];
+    let line_start: usize = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() as u32, utf16_length(&before[line_start..]))
+}
+
+// The byte offset of an LSP position, positions past the end of a line stay on that line
+fn offset(text: &str, position: &Json) -> usize {
+    let (line, character): (usize, u64) = (position.get("line").as_u64().unwrap_or(0) as usize, position.get("character").as_u64().unwrap_or(0));
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units: u64 = 0;
+
+    for (index, next) in text[line_start.min(text.len())..].char_indices() {
+        if units >= character || next == '\n' {
+            return line_start + index;
+        }
+        units += next.len_utf16() as u64;
+    }
+    text.len()
+}
+
+fn utf16_length(text: &str) -> u32 {
+    text.chars().map(|character| character.len_utf16() as u32).sum()
+}
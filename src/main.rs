@@ -1,29 +1,288 @@
+mod assembler;
+mod bench;
 mod chunk;
-mod common;
 mod compiler;
-mod data_structures;
-mod lexer;
+mod coverage;
+mod dap;
+mod debugger;
+mod disassembler;
+mod formatter;
+mod generator;
+mod json;
+mod lsp;
+#[cfg(feature = "nan-boxing")]
+mod nan_value;
+mod native;
 mod opcode;
-mod precedence;
+mod optimizer;
+mod profiler;
+mod resolver;
+mod serialization;
 mod test;
-mod token;
-mod value;
+mod trace;
+mod verifier;
 mod vm;
 
+use eos::{ast, common, data_structures, lexer, parser, precedence, token, value};
 
-use common::{DEBUG_BYTECODE, ENABLE_TESTING};
+use std::env::args;
+use std::fs::{read, read_to_string, write};
+use std::io::{stderr, stdin, stdout};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::exit;
+
+use assembler::assemble;
+use bench::{run_benchmarks, DEFAULT_RUNS};
+use chunk::Chunk;
+use common::{SharedData, DEFAULT_STACK_CAPACITY, ENABLE_TESTING};
+use compiler::Compiler;
+use coverage::Coverage;
+use debugger::Debugger;
+use disassembler::disassemble;
+use formatter::{format, FormatConfig};
+use lexer::Lexer;
+use profiler::Profile;
+use vm::VM;
 use test::run_tests;
+use trace::Trace;
+use verifier::verify;
 use data_structures::initialize_interned_string;
 
 fn main(){
     initialize_interned_string();
+
+    let args: Vec<String> = args().skip(1).collect();
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("bench"), runs) => {
+            let runs: usize = runs.and_then(|runs| runs.parse().ok()).unwrap_or(DEFAULT_RUNS);
+            run_benchmarks(runs);
+            return;
+        },
+        (Some("compile"), Some(path)) => {
+            if let Err(err) = compile_file(path) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("run"), Some(path)) => {
+            if let Err(err) = run_file(path, None) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("trace"), Some(path)) => {
+            let trace: Result<Trace, String> = match args.get(2) {
+                Some(lines) => parse_lines(lines).map(|lines| Trace::new(stderr()).with_lines(lines)),
+                None => Ok(Trace::new(stderr()))
+            };
+            if let Err(err) = trace.and_then(|trace| run_file(path, Some(trace))) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("dap"), _) => {
+            if let Err(err) = dap::serve(stdin().lock(), stdout().lock()) {
+                eprintln!("The debug adapter failed: {err}");
+            }
+            return;
+        },
+        (Some("lsp"), _) => {
+            if let Err(err) = lsp::serve(stdin().lock(), stdout().lock()) {
+                eprintln!("The language server failed: {err}");
+            }
+            return;
+        },
+        (Some("debug"), Some(path)) => {
+            if let Err(err) = debug_file(path) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("profile"), Some(path)) => {
+            if let Err(err) = profile_file(path, args.get(2).is_some_and(|flag| flag == "--folded")) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("coverage"), Some(_)) => {
+            if let Err(err) = coverage_files(&args[1..]) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("fmt"), Some(_)) => {
+            // CI relies on the exit code of --check
+            match format_files(&args[1..]) {
+                Ok(true) => {},
+                Ok(false) => exit(1),
+                Err(err) => {
+                    eprintln!("{err}");
+                    exit(1);
+                }
+            }
+            return;
+        },
+        (Some("disassemble"), Some(path)) => {
+            if let Err(err) = disassemble_file(path) {
+                eprintln!("{err}");
+            }
+            return;
+        },
+        (Some("compile" | "run" | "trace" | "debug" | "profile" | "coverage" | "fmt" | "disassemble"), None) => {
+            eprintln!("Usage: eos compile <file.eos> | eos run <file.eos | file.eosc> | eos trace <file> [FIRST..LAST] | eos debug <file> | eos profile <file> [--folded] | eos coverage [--lcov FILE] <file>... | eos fmt [--check] [--indent N] [--width N] <file>... | eos disassemble <file.eos | file.eosc | file.easm>");
+            return;
+        },
+        _ => {}
+    }
+
     if ENABLE_TESTING {
         run_tests();
     }
+}
+
+fn compile_source(path: &str) -> Result<Chunk, String> {
+    let code: String = read_to_string(path).map_err(|err| format!("Can not read {path}: {err}"))?;
+
+    let mut lexer: Lexer = Lexer::new(&code);
+    let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+    let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(&code), SharedData::new(&chunk));
+    compiler.compile().ok_or_else(|| format!("{path} does not compile"))?;
+
+    Ok(chunk)
+}
+
+// Writes the bytecode of file.eos to file.eosc
+fn compile_file(path: &str) -> Result<(), String> {
+    let chunk: Chunk = compile_source(path)?;
+    let output = Path::new(path).with_extension("eosc");
+
+    write(&output, chunk.serialize()).map_err(|err| format!("Can not write {}: {err}", output.display()))
+}
+
+fn load_chunk(path: &str) -> Result<Chunk, String> {
+    match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("eosc") => Chunk::deserialize(&read(path).map_err(|err| format!("Can not read {path}: {err}"))?),
+        Some("easm") => assemble(&read_to_string(path).map_err(|err| format!("Can not read {path}: {err}"))?),
+        _ => compile_source(path)
+    }
+}
+
+// The trace goes to stderr, so the result on stdout stays the same
+fn run_file(path: &str, trace: Option<Trace>) -> Result<(), String> {
+    let chunk: Chunk = load_chunk(path)?;
+    verify(&chunk)?;
+
+    let mut vm: VM = VM::new(&chunk);
+    vm.set_trace(trace);
+    vm.run().ok_or_else(|| format!("{path} failed at runtime"))?;
+
+    if let Some(result) = vm.get_stack().last() {
+        println!("{result}");
+    }
+    Ok(())
+}
+
+// Prints the report, or only the folded stacks for flamegraph tools
+fn profile_file(path: &str, folded: bool) -> Result<(), String> {
+    let chunk: Chunk = load_chunk(path)?;
+    verify(&chunk)?;
+
+    let mut profile: Profile = Profile::new();
+    profile.run(&mut VM::new(&chunk)).ok_or_else(|| format!("{path} failed at runtime"))?;
+
+    match folded {
+        true => print!("{}", profile.folded()),
+        false => print!("{}", profile.report())
+    }
+    Ok(())
+}
+
+// Runs every file once and prints the summary, --lcov FILE adds the coverage to the tracefile FILE
+fn coverage_files(args: &[String]) -> Result<(), String> {
+    let (lcov, paths): (Option<&String>, &[String]) = match args {
+        [flag, lcov, paths @ ..] if flag == "--lcov" => (Some(lcov), paths),
+        paths => (None, paths)
+    };
+
+    let mut coverage: Coverage = match lcov.filter(|lcov| Path::new(lcov).exists()) {
+        Some(lcov) => Coverage::parse_lcov(&read_to_string(lcov).map_err(|err| format!("Can not read {lcov}: {err}"))?)?,
+        None => Coverage::new()
+    };
+    for path in paths {
+        let chunk: Chunk = load_chunk(path)?;
+        verify(&chunk)?;
+        coverage.run(path, &mut VM::new(&chunk)).ok_or_else(|| format!("{path} failed at runtime"))?;
+    }
+
+    if let Some(lcov) = lcov {
+        write(lcov, coverage.lcov()).map_err(|err| format!("Can not write {lcov}: {err}"))?;
+    }
+    print!("{}", coverage.summary());
+    Ok(())
+}
+
+// Formats the files in place, --check only lists the files that aren't formatted. False if there are any
+fn format_files(args: &[String]) -> Result<bool, String> {
+    let mut config: FormatConfig = FormatConfig::default();
+    let mut check: bool = false;
+    let mut paths: Vec<&String> = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--indent" | "--width" => {
+                let value: usize = args.next().and_then(|value| value.parse().ok()).ok_or_else(|| format!("{arg} expects a number"))?;
+                match arg.as_str() {
+                    "--indent" => config.indent = value,
+                    _ => config.width = value
+                }
+            },
+            _ => paths.push(arg)
+        }
+    }
+
+    let mut formatted: bool = true;
+    for path in paths {
+        let source: String = read_to_string(path).map_err(|err| format!("Can not read {path}: {err}"))?;
+        let output: String = format(&source, &config).map_err(|err| format!("{path}: {err}"))?;
+
+        if output != source {
+            formatted = false;
+            match check {
+                true => println!("{path} is not formatted"),
+                false => write(path, output).map_err(|err| format!("Can not write {path}: {err}"))?
+            }
+        }
+    }
+    Ok(formatted || !check)
+}
+
+// Reads the commands of the debugger from stdin, see debugger.rs
+fn debug_file(path: &str) -> Result<(), String> {
+    let chunk: Chunk = load_chunk(path)?;
+    verify(&chunk)?;
+    let source: Option<String> = path.ends_with(".eos").then(|| read_to_string(path).ok()).flatten();
+
+    let mut debugger: Debugger = Debugger::new(&chunk, source.as_deref());
+    debugger.run_cli(stdin().lock(), stdout()).map_err(|err| format!("The debugger failed: {err}"))
+}
+
+// Only the source of an .eos file can be interleaved, the other files don't contain it
+fn disassemble_file(path: &str) -> Result<(), String> {
+    let chunk: Chunk = load_chunk(path)?;
+    let source: Option<String> = path.ends_with(".eos").then(|| read_to_string(path).ok()).flatten();
+
+    print!("{}", disassemble(&chunk, path, source.as_deref()));
+    Ok(())
+}
 
-    if DEBUG_BYTECODE {
-        //print_chunk(chunk, name)
-    } else {
-        // execute it
+// '3..7' traces the lines 3 to 7, '3' only line 3
+fn parse_lines(lines: &str) -> Result<RangeInclusive<u32>, String> {
+    let (first, last): (&str, &str) = lines.split_once("..").unwrap_or((lines, lines));
+    match (first.parse::<u32>(), last.parse::<u32>()) {
+        (Ok(first), Ok(last)) => Ok(first..=last),
+        _ => Err(format!("Invalid lines '{lines}', expected FIRST..LAST"))
     }
 }
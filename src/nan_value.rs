@@ -0,0 +1,370 @@
+use std::fmt::{self, Debug, Display};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Not, Rem, Shl, Shr, Sub};
+use std::rc::Rc;
+
+use super::value::{floor_div, floor_rem, floor_rem_float, Comparison, Error, FloorDiv, Negate, Pow, Value};
+
+/* NAN BOXING
+
+ A Value squeezed into 8 bytes, enabled with the cargo feature "nan-boxing".
+ Every f64 whose exponent bits are all set and whose two highest mantissa bits are set is a NaN which the
+ hardware never produces, so the remaining 50 bits are free to encode anything else. Floats are stored as they are,
+ NaNs are canonicalized to a quiet NaN outside of that range.
+
+  SIGN | QNAN | TAG 49..48 | PAYLOAD 47..0
+
+   0   | QNAN |    01      | 48-bit signed integer
+   0   | QNAN |    10      | 1 = Null, 2 = false, 3 = true
+   1   | QNAN |    00      | pointer to an Rc<Value>, for integers outside 48 bits and every object
+
+ Pointers have to fit into 48 bits, which holds for user space on x86-64 and aarch64 without 5-level paging or
+ tagged pointers. Boxing a pointer above that panics instead of corrupting the value.
+
+ Integer, Float, Boolean and Null are handled without touching the heap, everything else falls back to the
+ operations of Value, so both representations behave exactly the same.
+
+ */
+
+const QNAN: u64 = 0x7ffc_0000_0000_0000;
+const SIGN_BIT: u64 = 1 << 63;
+const TAG_INTEGER: u64 = 1 << 48;
+const TAG_SINGLETON: u64 = 2 << 48;
+const TAG_MASK: u64 = 3 << 48;
+const PAYLOAD_MASK: u64 = (1 << 48) - 1;
+
+const NULL: u64 = QNAN | TAG_SINGLETON | 1;
+const FALSE: u64 = QNAN | TAG_SINGLETON | 2;
+const TRUE: u64 = QNAN | TAG_SINGLETON | 3;
+
+const MIN_INTEGER: i64 = -(1 << 47);
+const MAX_INTEGER: i64 = (1 << 47) - 1;
+
+pub struct NanValue(u64);
+
+impl NanValue {
+    pub fn from_i64(value: i64) -> Self {
+        match (MIN_INTEGER..=MAX_INTEGER).contains(&value) {
+            true => Self(QNAN | TAG_INTEGER | (value as u64 & PAYLOAD_MASK)),
+            false => Self::boxed(Value::Integer(value))
+        }
+    }
+
+    pub fn from_f64(value: f64) -> Self {
+        match value.is_nan() {
+            true => Self(f64::NAN.to_bits()),
+            false => Self(value.to_bits())
+        }
+    }
+
+    pub fn from_bool(value: bool) -> Self {
+        Self(if value { TRUE } else { FALSE })
+    }
+
+    fn boxed(value: Value) -> Self {
+        let pointer: u64 = Rc::into_raw(Rc::new(value)) as u64;
+        // a larger address would overwrite the tag and turn the pointer into a number, release builds included
+        assert!(pointer & !PAYLOAD_MASK == 0, "NaN boxing needs pointers that fit into 48 bits, got {pointer:#x}");
+        Self(SIGN_BIT | QNAN | pointer)
+    }
+
+    fn is_float(&self) -> bool {
+        self.0 & QNAN != QNAN
+    }
+
+    fn is_boxed(&self) -> bool {
+        self.0 & (SIGN_BIT | QNAN) == SIGN_BIT | QNAN
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match !self.is_boxed() && self.0 & (QNAN | TAG_MASK) == QNAN | TAG_INTEGER {
+            // shifting the payload to the top and back extends its sign
+            true => Some(((self.0 << 16) as i64) >> 16),
+            false => None
+        }
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        self.is_float().then(|| f64::from_bits(self.0))
+    }
+
+    fn as_boxed(&self) -> Option<&Value> {
+        match self.is_boxed() {
+            true => unsafe { Some(&*((self.0 & PAYLOAD_MASK) as *const Value)) },
+            false => None
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        if let Some(value) = self.as_integer() {
+            return Value::Integer(value);
+        }
+        if let Some(value) = self.as_float() {
+            return Value::Float(value);
+        }
+
+        match self.0 {
+            NULL => Value::Null,
+            FALSE => Value::Boolean(false),
+            TRUE => Value::Boolean(true),
+            _ => self.as_boxed().expect("every other encoding is a pointer").clone()
+        }
+    }
+
+    pub fn is_falsey(&self) -> bool {
+        self.0 == NULL || self.0 == FALSE
+    }
+
+    // Both operands are integers or floats, so the operation doesn't need the heap
+    fn numbers(&self, other: &Self) -> Option<(f64, f64)> {
+        let a: f64 = self.as_float().or_else(|| self.as_integer().map(|a| a as f64))?;
+        let b: f64 = other.as_float().or_else(|| other.as_integer().map(|b| b as f64))?;
+        Some((a, b))
+    }
+}
+
+impl From<Value> for NanValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Integer(value) => Self::from_i64(value),
+            Value::Float(value) => Self::from_f64(value),
+            Value::Boolean(value) => Self::from_bool(value),
+            Value::Null => Self(NULL),
+            value => Self::boxed(value)
+        }
+    }
+}
+
+impl From<NanValue> for Value {
+    fn from(value: NanValue) -> Self {
+        value.to_value()
+    }
+}
+
+impl Clone for NanValue {
+    fn clone(&self) -> Self {
+        if self.is_boxed() {
+            unsafe { Rc::increment_strong_count((self.0 & PAYLOAD_MASK) as *const Value) };
+        }
+        Self(self.0)
+    }
+}
+
+impl Drop for NanValue {
+    fn drop(&mut self) {
+        if self.is_boxed() {
+            unsafe { drop(Rc::from_raw((self.0 & PAYLOAD_MASK) as *const Value)) };
+        }
+    }
+}
+
+// Every operation without a fast path goes through Value
+fn fallback(a: &NanValue, b: &NanValue, operation: fn(Value, Value) -> Result<Value, Error>) -> Result<NanValue, Error> {
+    operation(a.to_value(), b.to_value()).map(NanValue::from)
+}
+
+impl Add for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            // two 48-bit integers can't overflow an i64
+            (Some(a), Some(b)) => Ok(Self::from_i64(a + b)),
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64(f64::from_bits(self.0) + f64::from_bits(rhs.0))),
+            _ => fallback(&self, &rhs, Value::add)
+        }
+    }
+}
+
+impl Sub for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_i64(a - b)),
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64(f64::from_bits(self.0) - f64::from_bits(rhs.0))),
+            _ => fallback(&self, &rhs, Value::sub)
+        }
+    }
+}
+
+impl Mul for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            (Some(a), Some(b)) => match a.checked_mul(b) {
+                Some(result) => Ok(Self::from_i64(result)),
+                None => fallback(&self, &rhs, Value::mul)
+            },
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64(f64::from_bits(self.0) * f64::from_bits(rhs.0))),
+            _ => fallback(&self, &rhs, Value::mul)
+        }
+    }
+}
+
+impl Div for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            // a 48-bit integer can't overflow an i64 when divided, only the division by zero has to go through Value
+            (Some(a), Some(b)) if b != 0 => Ok(Self::from_i64(a / b)),
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64(f64::from_bits(self.0) / f64::from_bits(rhs.0))),
+            _ => fallback(&self, &rhs, Value::div)
+        }
+    }
+}
+
+impl Rem for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            (Some(a), Some(b)) if b != 0 => Ok(Self::from_i64(floor_rem(a, b).expect("48-bit integers can't overflow"))),
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64(floor_rem_float(f64::from_bits(self.0), f64::from_bits(rhs.0)))),
+            _ => fallback(&self, &rhs, Value::rem)
+        }
+    }
+}
+
+impl FloorDiv for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn floor_div(self, rhs: Self) -> Self::Output {
+        match (self.as_integer(), rhs.as_integer()) {
+            (Some(a), Some(b)) if b != 0 => Ok(Self::from_i64(floor_div(a, b).expect("48-bit integers can't overflow"))),
+            _ if self.is_float() && rhs.is_float() => Ok(Self::from_f64((f64::from_bits(self.0) / f64::from_bits(rhs.0)).floor())),
+            _ => fallback(&self, &rhs, Value::floor_div)
+        }
+    }
+}
+
+impl Pow for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn pow(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::pow)
+    }
+}
+
+impl BitAnd for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::bitand)
+    }
+}
+
+impl BitOr for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::bitor)
+    }
+}
+
+impl BitXor for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::bitxor)
+    }
+}
+
+impl Shl for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn shl(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::shl)
+    }
+}
+
+impl Shr for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        fallback(&self, &rhs, Value::shr)
+    }
+}
+
+impl Not for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn not(self) -> Self::Output {
+        (!self.to_value()).map(Self::from)
+    }
+}
+
+impl Negate for NanValue {
+    type Output = Result<Self, Error>;
+
+    fn negate(self) -> Self::Output {
+        match (self.as_integer(), self.as_float()) {
+            (Some(a), _) => Ok(Self::from_i64(-a)),
+            (_, Some(a)) => Ok(Self::from_f64(-a)),
+            _ => self.to_value().negate().map(Self::from)
+        }
+    }
+}
+
+impl Comparison for NanValue {
+    fn equal(&self, other: &Self) -> Self {
+        match self.numbers(other) {
+            Some((a, b)) if self.is_float() || other.is_float() => Self::from_bool(a == b),
+            // integers and singletons are equal exactly when their encodings are
+            _ if !self.is_boxed() && !other.is_boxed() && !self.is_float() && !other.is_float() => Self::from_bool(self.0 == other.0),
+            _ => Self::from(self.to_value().equal(&other.to_value()))
+        }
+    }
+
+    fn not_equal(&self, other: &Self) -> Self {
+        Self::from_bool(self.equal(other).0 == FALSE)
+    }
+
+    fn greater(&self, other: &Self) -> Result<Self, Error> {
+        match (self.as_integer(), other.as_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_bool(a > b)),
+            _ => self.to_value().greater(&other.to_value()).map(Self::from)
+        }
+    }
+
+    fn greater_equal(&self, other: &Self) -> Result<Self, Error> {
+        match (self.as_integer(), other.as_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_bool(a >= b)),
+            _ => self.to_value().greater_equal(&other.to_value()).map(Self::from)
+        }
+    }
+
+    fn less(&self, other: &Self) -> Result<Self, Error> {
+        match (self.as_integer(), other.as_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_bool(a < b)),
+            _ => self.to_value().less(&other.to_value()).map(Self::from)
+        }
+    }
+
+    fn less_equal(&self, other: &Self) -> Result<Self, Error> {
+        match (self.as_integer(), other.as_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_bool(a <= b)),
+            _ => self.to_value().less_equal(&other.to_value()).map(Self::from)
+        }
+    }
+}
+
+// The VM tests compare the stack against Values
+impl PartialEq<Value> for NanValue {
+    fn eq(&self, other: &Value) -> bool {
+        self.to_value() == *other
+    }
+}
+
+impl Debug for NanValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.to_value())
+    }
+}
+
+impl Display for NanValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
@@ -0,0 +1,164 @@
+use super::data_structures::{obj_big_int::ObjBigInt, obj_decimal::{ObjDecimal, DIVISION_SCALE, MAX_SCALE}, obj_rational::ObjRational, DynType};
+use super::value::{Error, Value::{self, *}};
+
+pub type NativeFn = fn(&[Value]) -> Result<Value, Error>;
+
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn
+}
+
+const fn native(name: &'static str, arity: usize, function: NativeFn) -> Native {
+    Native { name, arity, function }
+}
+
+/* NATIVES
+
+ Functions implemented in Rust which can be called from Eos, e.g. wrapping_add(a, b).
+ The compiler resolves the name to an index into NATIVES and checks the number of arguments,
+ OpCode::CallNative stores that index as its operand.
+
+ The default integer arithmetic promotes results that don't fit into an i64 to big integers, these
+ functions opt into wrapping (two's complement) or saturating (clamped to i64::MIN..=i64::MAX)
+ semantics on i64 instead.
+
+ rational(a, b) and decimal(x) create the exact number types, which have no literal of their own
+ except for the decimal suffix 0.1d. round(x, places) and to_fixed(x, places) round half to even.
+
+ */
+
+pub const NATIVES: [Native; 19] = [
+    native("wrapping_add", 2, |args| integers("wrapping_add", args, |a, b| Ok(a.wrapping_add(b)))),
+    native("wrapping_sub", 2, |args| integers("wrapping_sub", args, |a, b| Ok(a.wrapping_sub(b)))),
+    native("wrapping_mul", 2, |args| integers("wrapping_mul", args, |a, b| Ok(a.wrapping_mul(b)))),
+    native("wrapping_div", 2, |args| integers("wrapping_div", args, |a, b| non_zero(b).map(|b| a.wrapping_div(b)))),
+    native("wrapping_rem", 2, |args| integers("wrapping_rem", args, |a, b| non_zero(b).map(|b| a.wrapping_rem(b)))),
+    native("wrapping_pow", 2, |args| integers("wrapping_pow", args, |a, b| exponent(b).map(|b| a.wrapping_pow(b)))),
+    native("wrapping_neg", 1, |args| integer("wrapping_neg", args, |a| Ok(a.wrapping_neg()))),
+    native("saturating_add", 2, |args| integers("saturating_add", args, |a, b| Ok(a.saturating_add(b)))),
+    native("saturating_sub", 2, |args| integers("saturating_sub", args, |a, b| Ok(a.saturating_sub(b)))),
+    native("saturating_mul", 2, |args| integers("saturating_mul", args, |a, b| Ok(a.saturating_mul(b)))),
+    native("saturating_div", 2, |args| integers("saturating_div", args, |a, b| non_zero(b).map(|b| a.saturating_div(b)))),
+    native("saturating_pow", 2, |args| integers("saturating_pow", args, |a, b| exponent(b).map(|b| a.saturating_pow(b)))),
+    native("saturating_neg", 1, |args| integer("saturating_neg", args, |a| Ok(a.saturating_neg()))),
+    native("rational", 2, rational),
+    native("numerator", 1, |args| fraction_part("numerator", args, ObjRational::numerator)),
+    native("denominator", 1, |args| fraction_part("denominator", args, ObjRational::denominator)),
+    native("decimal", 1, decimal),
+    native("round", 2, round),
+    native("to_fixed", 2, to_fixed),
+];
+
+pub fn find_native(name: &str) -> Option<usize> {
+    NATIVES.iter().position(|native| native.name == name)
+}
+
+fn integer<F: Fn(i64) -> Result<i64, Error>>(name: &str, args: &[Value], operation: F) -> Result<Value, Error> {
+    match args {
+        [Integer(a)] => Ok(Integer(operation(*a)?)),
+        _ => Err(format!("{name} expects an Integer, got {:?}", args))
+    }
+}
+
+fn integers<F: Fn(i64, i64) -> Result<i64, Error>>(name: &str, args: &[Value], operation: F) -> Result<Value, Error> {
+    match args {
+        [Integer(a), Integer(b)] => Ok(Integer(operation(*a, *b)?)),
+        _ => Err(format!("{name} expects two Integers, got {:?}", args))
+    }
+}
+
+fn non_zero(divisor: i64) -> Result<i64, Error> {
+    match divisor {
+        0 => Err("Division by zero".to_string()),
+        _ => Ok(divisor)
+    }
+}
+
+fn exponent(exponent: i64) -> Result<u32, Error> {
+    u32::try_from(exponent).map_err(|_| format!("The exponent {exponent} has to be in 0..={}", u32::MAX))
+}
+
+fn rational(args: &[Value]) -> Result<Value, Error> {
+    match (exact(&args[0]), exact(&args[1])) {
+        (Some(a), Some(b)) => Ok(Value::from(a.div(&b)?)),
+        _ => Err(format!("rational expects two Integers, Decimals or Rationals, got {:?}", args))
+    }
+}
+
+fn fraction_part(name: &str, args: &[Value], part: fn(&ObjRational) -> &ObjBigInt) -> Result<Value, Error> {
+    match exact(&args[0]) {
+        Some(value) => Ok(Value::from(part(&value).clone())),
+        None => Err(format!("{name} expects an Integer, Decimal or Rational, got {:?}", args))
+    }
+}
+
+// Floats are converted from their shortest representation, decimal(0.1) is 0.1 and not 0.1000000000000000055511151231257827
+fn decimal(args: &[Value]) -> Result<Value, Error> {
+    match &args[0] {
+        Float(value) if value.is_finite() => ObjDecimal::parse(&value.to_string()).map(Value::from),
+        Object(DynType::Rational(value)) => value.to_decimal(DIVISION_SCALE).map(Value::from),
+        value => match exact(value) {
+            Some(value) => value.to_decimal(0).map(Value::from),
+            None => Err(format!("decimal expects a finite number, got {:?}", args))
+        }
+    }
+}
+
+fn round(args: &[Value]) -> Result<Value, Error> {
+    let places: u32 = places("round", &args[1])?;
+    match &args[0] {
+        Integer(_) | Object(DynType::BigInt(_)) => Ok(clone_number(&args[0])),
+        Float(value) => Ok(Float(rounded_float(*value, places))),
+        Object(DynType::Decimal(value)) => value.round(places).map(Value::from),
+        Object(DynType::Rational(value)) => value.to_decimal(places).map(Value::from),
+        _ => Err(format!("round expects a number, got {:?}", args))
+    }
+}
+
+fn to_fixed(args: &[Value]) -> Result<Value, Error> {
+    let places: u32 = places("to_fixed", &args[1])?;
+    let text: String = match &args[0] {
+        Float(value) => format!("{:.*}", places as usize, rounded_float(*value, places)),
+        Object(DynType::Decimal(value)) => value.round(places)?.to_string(),
+        value => match exact(value) {
+            Some(value) => value.to_decimal(places)?.to_string(),
+            None => return Err(format!("to_fixed expects a number, got {:?}", args))
+        }
+    };
+    Ok(Object(DynType::from(text)))
+}
+
+// Every exact number as a fraction, None for Floats and everything else
+fn exact(value: &Value) -> Option<ObjRational> {
+    match value {
+        Integer(value) => Some(ObjRational::from_integer(ObjBigInt::from(*value))),
+        Object(DynType::BigInt(value)) => Some(ObjRational::from_integer(value.as_ref().clone())),
+        Object(DynType::Decimal(value)) => Some(ObjRational::from_decimal(value)),
+        Object(DynType::Rational(value)) => Some(value.as_ref().clone()),
+        _ => None
+    }
+}
+
+fn clone_number(value: &Value) -> Value {
+    match value {
+        Integer(value) => Integer(*value),
+        Object(DynType::BigInt(value)) => Object(DynType::BigInt(value.clone())),
+        _ => unreachable!("only integers are returned unchanged")
+    }
+}
+
+fn places(name: &str, places: &Value) -> Result<u32, Error> {
+    match places {
+        Integer(places) if (0..=MAX_SCALE as i64).contains(places) => Ok(*places as u32),
+        _ => Err(format!("{name} expects the number of decimal places in 0..={MAX_SCALE}, got {:?}", places))
+    }
+}
+
+// Floats are rounded through their decimal representation, so that round(2.675, 2) doesn't see 2.67499999...
+fn rounded_float(value: f64, places: u32) -> f64 {
+    match ObjDecimal::parse(&value.to_string()).and_then(|decimal| decimal.round(places)) {
+        Ok(decimal) => decimal.to_f64(),
+        Err(_) => value
+    }
+}
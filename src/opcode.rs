@@ -1,14 +1,84 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OpCode{
     Return,
     Constant,
     Negate,
+    Not,
     Print,
     Equal,
+    NotEqual,
     Greater,
+    GreaterEqual,
     Less,
+    LessEqual,
     Add,
     Subtract,
     Multiply,
-    Divide
+    Divide,
+    Modulo,
+    FloorDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+    Interpolate,
+    CallNative,
+    Pop,
+    Jump,
+    JumpIfFalse,
+    // superinstructions, only emitted by the peephole optimizer
+    AddConstant,
+    SubtractConstant,
+    EqualJumpIfFalse,
+    GreaterJumpIfFalse,
+    LessJumpIfFalse
+}
+
+// Every opcode at the index of its byte, e.g. for decoding bytecode that was read from a file
+pub const OPCODES: [OpCode; 34] = [
+    OpCode::Return,
+    OpCode::Constant,
+    OpCode::Negate,
+    OpCode::Not,
+    OpCode::Print,
+    OpCode::Equal,
+    OpCode::NotEqual,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::Modulo,
+    OpCode::FloorDivide,
+    OpCode::Power,
+    OpCode::BitAnd,
+    OpCode::BitOr,
+    OpCode::BitXor,
+    OpCode::BitNot,
+    OpCode::ShiftLeft,
+    OpCode::ShiftRight,
+    OpCode::Interpolate,
+    OpCode::CallNative,
+    OpCode::Pop,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::AddConstant,
+    OpCode::SubtractConstant,
+    OpCode::EqualJumpIfFalse,
+    OpCode::GreaterJumpIfFalse,
+    OpCode::LessJumpIfFalse
+];
+
+impl TryFrom<u8> for OpCode {
+    type Error = String;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(byte as usize).copied().ok_or_else(|| format!("Unknown opcode {byte}"))
+    }
 }
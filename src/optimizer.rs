@@ -0,0 +1,107 @@
+use std::collections::{HashMap, HashSet};
+
+use super::chunk::{decode_operand, decode_value, instruction_size, is_jump, jump_target, Chunk, INSTRUCTION_SIZE, OPERAND_SIZE};
+use super::opcode::OpCode::{self, *};
+
+/* PEEPHOLE OPTIMIZER
+
+ optimize runs over a finished chunk and fuses common pairs of instructions into superinstructions,
+ which saves the VM one dispatch per pair:
+
+  CONSTANT VALUE ADD           -> ADD_CONSTANT VALUE
+  CONSTANT VALUE SUBTRACT      -> SUBTRACT_CONSTANT VALUE
+  EQUAL NOT                    -> NOT_EQUAL
+  NOT_EQUAL NOT                -> EQUAL
+  EQUAL JUMP_IF_FALSE n        -> EQUAL_JUMP_IF_FALSE n
+  GREATER JUMP_IF_FALSE n      -> GREATER_JUMP_IF_FALSE n
+  LESS JUMP_IF_FALSE n         -> LESS_JUMP_IF_FALSE n
+
+ A pair is only fused if no jump lands between its two instructions. The fused instruction keeps the line of
+ the instruction that can fail, so runtime errors are reported at the same line as before.
+ Fusing moves all the following instructions, afterwards every jump offset is recomputed from the new positions.
+
+ */
+
+struct Instruction {
+    offset: usize,
+    opcode: OpCode,
+    line: u32
+}
+
+pub fn optimize(chunk: &Chunk) -> Chunk {
+    let code: &[u8] = chunk.code();
+    let instructions: Vec<Instruction> = decode_instructions(chunk);
+
+    let targets: HashSet<usize> = instructions.iter()
+        .filter(|instruction| is_jump(instruction.opcode))
+        .map(|instruction| jump_target(code, instruction.offset))
+        .collect();
+
+    let mut optimized: Chunk = Chunk::new(code.len().max(INSTRUCTION_SIZE));
+    let mut positions: HashMap<usize, usize> = HashMap::new(); // old offset -> new offset
+    let mut jumps: Vec<(usize, usize)> = Vec::new(); // new position of the operand, old target
+
+    let mut index: usize = 0;
+    while let Some(first) = instructions.get(index) {
+        positions.insert(first.offset, optimized.len());
+
+        let fused: Option<(OpCode, u32)> = instructions.get(index + 1)
+            .filter(|second| !targets.contains(&second.offset))
+            .and_then(|second| fuse(first, second));
+
+        let (opcode, line, source): (OpCode, u32, &Instruction) = match fused {
+            Some((opcode, line)) => {
+                index += 1;
+                // the value comes from the first, the jump offset from the second instruction
+                let second: &Instruction = &instructions[index];
+                positions.insert(second.offset, optimized.len());
+                (opcode, line, if first.opcode == Constant { first } else { second })
+            },
+            None => (first.opcode, first.line, first)
+        };
+        index += 1;
+
+        match instruction_size(opcode) - INSTRUCTION_SIZE {
+            0 => optimized.add_opcode(opcode, line),
+            OPERAND_SIZE => {
+                optimized.add_opcode_with_operand(opcode, decode_operand(code, source.offset + INSTRUCTION_SIZE), line);
+                if is_jump(opcode) {
+                    jumps.push((optimized.len() - OPERAND_SIZE, jump_target(code, source.offset)));
+                }
+            },
+            _ => optimized.add_opcode_with_value(opcode, unsafe { decode_value(code, source.offset + INSTRUCTION_SIZE) }, line)
+        }
+    }
+    positions.insert(code.len(), optimized.len());
+
+    for (operand, target) in jumps {
+        let offset: usize = positions[&target] - operand - OPERAND_SIZE;
+        optimized.patch_operand(operand, offset as u32);
+    }
+
+    optimized
+}
+
+fn decode_instructions(chunk: &Chunk) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = Vec::new();
+    let mut offset: usize = 0;
+
+    while let Some((opcode, line)) = chunk.read_opcode(offset) {
+        instructions.push(Instruction { offset, opcode, line });
+        offset += instruction_size(opcode);
+    }
+    instructions
+}
+
+fn fuse(first: &Instruction, second: &Instruction) -> Option<(OpCode, u32)> {
+    match (first.opcode, second.opcode) {
+        (Constant, Add) => Some((AddConstant, second.line)),
+        (Constant, Subtract) => Some((SubtractConstant, second.line)),
+        (Equal, Not) => Some((NotEqual, first.line)),
+        (NotEqual, Not) => Some((Equal, first.line)),
+        (Equal, JumpIfFalse) => Some((EqualJumpIfFalse, first.line)),
+        (Greater, JumpIfFalse) => Some((GreaterJumpIfFalse, first.line)),
+        (Less, JumpIfFalse) => Some((LessJumpIfFalse, first.line)),
+        _ => None
+    }
+}
@@ -0,0 +1,432 @@
+use std::vec::IntoIter;
+
+use super::ast::{BinaryOperator, CompileError, Expr, ExprKind, Program, Span, UnaryOperator};
+use super::common::SharedData;
+use super::precedence::Precedence::{self, *};
+use super::token::{Token, TokenType};
+
+use literals::literal_value;
+
+type PrefixFn = fn(&mut Parser) -> Option<Expr>;
+type InfixFn = fn(&mut Parser, Expr) -> Option<Expr>;
+
+struct ParseRule {
+    prefix: Option<PrefixFn>,
+    infix: Option<InfixFn>,
+    precedence: Precedence
+}
+
+const fn rule(prefix: Option<PrefixFn>, infix: Option<InfixFn>, precedence: Precedence) -> ParseRule {
+    ParseRule{prefix, infix, precedence}
+}
+
+const RULES: [ParseRule; 55] = [
+    rule(Some(Parser::grouping), None, NONE), // TOKEN_LEFT_PAREN
+    rule(None, None, NONE),                     // TOKEN_RIGHT_PAREN
+    rule(None, None, NONE),                     // TOKEN_LEFT_BRACE
+    rule(None, None, NONE),                     // TOKEN_RIGHT_BRACE
+    rule(None, None, NONE),                     // TOKEN_COMMA
+    rule(None, None, NONE),                     // TOKEN_DOT
+    rule(Some(Parser::unary), Some(Parser::binary), TERM), // TOKEN_MINUS
+    rule(None, Some(Parser::binary), TERM),   // TOKEN_PLUS
+    rule(None, None, NONE),                     // TOKEN_SEMICOLON
+    rule(None, Some(Parser::binary), FACTOR), // TOKEN_SLASH
+    rule(None, Some(Parser::binary), FACTOR), // TOKEN_STAR
+    rule(None, Some(Parser::binary), FACTOR), // TOKEN_PERCENT
+    rule(None, Some(Parser::binary), FACTOR), // TOKEN_SLASH_SLASH
+    rule(None, Some(Parser::binary), POWER),  // TOKEN_STAR_STAR
+    rule(None, Some(Parser::binary), BITAND), // TOKEN_AMPERSAND
+    rule(None, Some(Parser::binary), BITOR), // TOKEN_PIPE
+    rule(None, Some(Parser::binary), BITXOR), // TOKEN_CARET
+    rule(Some(Parser::unary), None, NONE),    // TOKEN_TILDE
+    rule(Some(Parser::unary), None, NONE),                     // TOKEN_BANG
+    rule(None, Some(Parser::binary), EQUALITY),                     // TOKEN_BANG_EQUAL
+    rule(None, None, NONE),                     // TOKEN_EQUAL
+    rule(None, Some(Parser::binary), EQUALITY),                     // TOKEN_EQUAL_EQUAL
+    rule(None, Some(Parser::binary), COMPARISON),                     // TOKEN_GREATER
+    rule(None, Some(Parser::binary), COMPARISON),                     // TOKEN_GREATER_EQUAL
+    rule(None, Some(Parser::binary), COMPARISON),                     // TOKEN_LESS
+    rule(None, Some(Parser::binary), COMPARISON),                     // TOKEN_LESS_EQUAL
+    rule(None, Some(Parser::binary), SHIFT),  // TOKEN_LESS_LESS
+    rule(None, Some(Parser::binary), SHIFT),  // TOKEN_GREATER_GREATER
+    rule(Some(Parser::call), None, NONE),     // TOKEN_IDENTIFIER
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_STRING
+    rule(Some(Parser::interpolation), None, NONE),              // TOKEN_STRING_INTERPOLATION
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_RAW_STRING
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_MULTILINE_STRING
+    rule(Some(Parser::literal), None, NONE),   // TOKEN_INTEGER
+    rule(Some(Parser::literal), None, NONE),   //TOKEN_FLOAT
+    rule(Some(Parser::literal), None, NONE),   //TOKEN_DECIMAL
+    rule(None, None, NONE),                     // TOKEN_AND
+    rule(None, None, NONE),                     // TOKEN_CLASS
+    rule(None, None, NONE),                     // TOKEN_ELSE
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_FALSE
+    rule(None, None, NONE),                     // TOKEN_FOR
+    rule(None, None, NONE),                     // TOKEN_FUN
+    rule(None, None, NONE),                     // TOKEN_IF
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_NUL
+    rule(None, None, NONE),                     // TOKEN_OR
+    rule(None, None, NONE),                     // TOKEN_PRINT
+    rule(None, None, NONE),                     // TOKEN_RETURN
+    rule(None, None, NONE),                     // TOKEN_SUPER
+    rule(None, None, NONE),                     // TOKEN_THIS
+    rule(Some(Parser::literal), None, NONE),                     // TOKEN_TRUE
+    rule(None, None, NONE),                     // TOKEN_VAR
+    rule(None, None, NONE),                     // TOKEN_WHILE
+    rule(None, None, NONE),                     // TOKEN_COMMENT
+    rule(None, None, NONE),                     // TOKEN_ERROR
+    rule(None, None, NONE),                     // TOKEN_EOF
+];
+
+fn get_rule<'a>(ttype: TokenType) -> Option<&'a ParseRule>{
+    RULES.get(ttype as usize)
+}
+
+
+// A Pratt parser, which builds the abstract syntax tree. Every parse function returns None after an error
+pub struct Parser {
+    tokens: IntoIter<Token>,
+    source: SharedData<String>,
+    previous: Option<Token>,
+    current: Option<Token>,
+    line: u32,
+    errors: Vec<CompileError>
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>, source: SharedData<String>) -> Self {
+        Self { tokens: tokens.into_iter(), source, previous: None, current: None, line: 0, errors: Vec::new() }
+    }
+
+    // Returns every error in the order it was found, the first one is the most reliable
+    pub fn parse(mut self) -> Result<Program, Vec<CompileError>> {
+        self.advance();
+        let expression: Option<Expr> = self.expression();
+
+        if expression.is_some() {
+            self.consume(TokenType::EndOfFile, "Expected end of expression.");
+        }
+
+        match (expression, self.previous) {
+            (Some(expression), Some(end)) if self.errors.is_empty() => Ok(Program { expression, end: Span::from_token(&end) }),
+            _ => Err(self.errors)
+        }
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) -> Option<Expr> {
+        self.advance();
+
+        let Some(prefix) = self.previous.and_then(|token| get_rule(token.token_type)).and_then(|rule| rule.prefix) else {
+            self.error("Expected an expression");
+            return None;
+        };
+        let mut expression: Expr = prefix(self)?;
+
+        while let Some(token) = self.current {
+            match get_rule(token.token_type) {
+                Some(ParseRule { infix: Some(infix), precedence: infix_precedence, .. }) if *infix_precedence >= precedence => {
+                    self.advance();
+                    expression = infix(self, expression)?;
+                },
+                _ => break // terminates as soon as we encounter something of lower precedence or which isn't an operator
+            }
+        }
+        Some(expression)
+    }
+
+    fn expression(&mut self) -> Option<Expr> {
+        self.parse_precedence(Precedence::ASSIGNMENT)
+    }
+
+    fn grouping(&mut self) -> Option<Expr> {
+        let start: Token = self.previous?;
+        let expression: Expr = self.expression()?;
+        let end: Token = self.consume(TokenType::RightParent, "Expected )")?;
+
+        Some(Expr { kind: ExprKind::Grouping(Box::new(expression)), span: Span::from_token(&start).to(Span::from_token(&end)) })
+    }
+
+    fn literal(&mut self) -> Option<Expr> {
+        let token: Token = self.previous?;
+
+        match literal_value(&token, self.source.as_ref()) {
+            Ok(value) => Some(Expr { kind: ExprKind::Literal(value), span: Span::from_token(&token) }),
+            Err(msg) => {
+                self.error(&msg);
+                None
+            }
+        }
+    }
+
+    fn interpolation(&mut self) -> Option<Expr> {
+        let start: Token = self.previous?;
+        let mut token: Token = start;
+        let mut parts: Vec<Expr> = Vec::new();
+
+        // every part of the string is followed by an embedded expression, the last part ends the string
+        loop {
+            if !token.get_strrange().is_empty() {
+                parts.push(self.literal()?);
+            }
+
+            if token.token_type == TokenType::Text {
+                break;
+            }
+
+            parts.push(self.expression()?);
+
+            match self.current {
+                Some(next) if matches!(next.token_type, TokenType::Text | TokenType::TextInterpolation) => {
+                    self.advance();
+                    token = next;
+                },
+                _ => {
+                    self.error("Expected } to close the embedded expression");
+                    return None;
+                }
+            }
+        }
+
+        Some(Expr { kind: ExprKind::Interpolation(parts), span: Span::from_token(&start).to(Span::from_token(&token)) })
+    }
+
+    fn call(&mut self) -> Option<Expr> {
+        let token: Token = self.previous?;
+        let name: String = self.source.as_ref()[token.get_range()].to_string();
+
+        self.consume(TokenType::LeftParent, "Expected ( after the function name")?;
+
+        let mut arguments: Vec<Expr> = Vec::new();
+        if !matches!(self.current, Some(current) if current.token_type == TokenType::RightParent) {
+            loop {
+                arguments.push(self.expression()?);
+
+                match self.current {
+                    Some(current) if current.token_type == TokenType::Comma => self.advance(),
+                    _ => break
+                }
+            }
+        }
+        let end: Token = self.consume(TokenType::RightParent, "Expected ) after the arguments")?;
+
+        let callee: Span = Span::from_token(&token);
+        Some(Expr { kind: ExprKind::Call { name, callee, arguments, native: None }, span: callee.to(Span::from_token(&end)) })
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        let token: Token = self.previous?;
+
+        let operator: UnaryOperator = match token.token_type {
+            TokenType::Minus => UnaryOperator::Negate,
+            TokenType::Bang => UnaryOperator::Not,
+            TokenType::Tilde => UnaryOperator::BitNot,
+            operator => {
+                self.error(&format!("Expected a unary operator but got {:?}", operator));
+                return None;
+            }
+        };
+        let operand: Expr = self.parse_precedence(Precedence::UNARY)?;
+
+        let operator_span: Span = Span::from_token(&token);
+        Some(Expr { span: operator_span.to(operand.span), kind: ExprKind::Unary { operator, operator_span, operand: Box::new(operand) } })
+    }
+
+    fn binary(&mut self, left: Expr) -> Option<Expr> {
+        let token: Token = self.previous?;
+
+        let operator: BinaryOperator = match token.token_type {
+            TokenType::BangEqual => BinaryOperator::NotEqual,
+            TokenType::EqualEqual => BinaryOperator::Equal,
+            TokenType::Greater => BinaryOperator::Greater,
+            TokenType::GreaterEqual => BinaryOperator::GreaterEqual,
+            TokenType::Less => BinaryOperator::Less,
+            TokenType::LessEqual => BinaryOperator::LessEqual,
+            TokenType::Plus => BinaryOperator::Add,
+            TokenType::Minus => BinaryOperator::Subtract,
+            TokenType::Star => BinaryOperator::Multiply,
+            TokenType::Slash => BinaryOperator::Divide,
+            TokenType::Percent => BinaryOperator::Modulo,
+            TokenType::SlashSlash => BinaryOperator::FloorDivide,
+            TokenType::StarStar => BinaryOperator::Power,
+            TokenType::Ampersand => BinaryOperator::BitAnd,
+            TokenType::Pipe => BinaryOperator::BitOr,
+            TokenType::Caret => BinaryOperator::BitXor,
+            TokenType::LessLess => BinaryOperator::ShiftLeft,
+            TokenType::GreaterGreater => BinaryOperator::ShiftRight,
+            _ => {
+                self.error("Encountered invalid operator for binary operation");
+                return None;
+            }
+        };
+
+        let rule: &ParseRule = get_rule(token.token_type).expect("Check Tokentypes");
+        let right: Expr = match operator {
+            BinaryOperator::Power => self.parse_precedence(rule.precedence)?, // right associative: 2 ** 3 ** 2 == 2 ** (3 ** 2)
+            _ => self.parse_precedence(rule.precedence.next())?
+        };
+
+        Some(Expr { span: left.span.to(right.span), kind: ExprKind::Binary { operator, operator_span: Span::from_token(&token), left: Box::new(left), right: Box::new(right) } })
+    }
+
+
+    fn advance(&mut self) {
+        self.previous = self.current;
+
+        while let Some(token) = &self.tokens.next() {
+            self.line = token.line;
+            self.current = Some(*token);
+
+            match token.token_type {
+                TokenType::Error => self.error_at(*token, describe_invalid_token(&self.source.as_ref()[token.get_range()])),
+                _ => break
+            }
+        }
+    }
+
+    fn consume(&mut self, ttype: TokenType, error_msg: &str) -> Option<Token> {
+        match self.current {
+            Some(token) if token.token_type == ttype => {
+                self.advance();
+                Some(token)
+            },
+            _ => {
+                self.error(error_msg);
+                None
+            }
+        }
+    }
+
+    fn error(&mut self, msg: &str) {
+        match self.previous {
+            Some(token) => self.error_at(token, msg),
+            None => self.errors.push(CompileError { message: msg.to_string(), span: None, line: self.line })
+        }
+    }
+
+    fn error_at(&mut self, token: Token, msg: &str) {
+        self.errors.push(CompileError::at(Span::from_token(&token), msg));
+    }
+}
+
+fn describe_invalid_token(lexeme: &str) -> &'static str {
+    match lexeme.as_bytes().first() {
+        Some(b'0'..=b'9') => "Malformed number literal",
+        Some(b'"' | b'r') => "Unterminated string",
+        _ => "Unexpected character"
+    }
+}
+
+mod literals {
+    use std::ops::Range;
+    use crate::data_structures::{obj_big_int::ObjBigInt, obj_decimal::ObjDecimal, DynType};
+    use crate::token::{Token, TokenType};
+    use crate::value::Value;
+
+    pub fn literal_value(token: &Token, source: &str) -> Result<Value, String> {
+        match token.token_type {
+            TokenType::Integer => extract_integer(&source[token.get_range()]),
+            TokenType::Float => Ok(Value::Float(extract_float(&source[token.get_range()])?)),
+            TokenType::Decimal => extract_decimal(&source[token.get_range()]),
+            TokenType::True => Ok(Value::Boolean(true)),
+            TokenType::False => Ok(Value::Boolean(false)),
+            TokenType::Null => Ok(Value::Null),
+            TokenType::Text | TokenType::TextInterpolation | TokenType::RawText | TokenType::MultilineText => Ok(Value::Object(DynType::from::<String>(extract_string(source, token)?))),
+            _ => Err("Expected a literal".to_string())
+        }
+    }
+
+    fn extract_string(source: &str, token: &Token) -> Result<String, String> {
+        match token.token_type {
+            TokenType::RawText => {
+                let range: Range<usize> = token.get_range();
+                let hashes: usize = source[range.start + 1..].bytes().take_while(|byte| *byte == b'#').count();
+
+                Ok(source[range.start + hashes + 2..range.end - hashes - 1].to_string())
+            },
+            TokenType::MultilineText => unescape(&dedent(&source[token.get_strrange()])),
+            // backslashes in ordinary strings are just text, like they have always been
+            _ => Ok(source[token.get_strrange()].to_string())
+        }
+    }
+
+    fn unescape(text: &str) -> Result<String, String> {
+        let mut output: String = String::with_capacity(text.len());
+        let mut chars = text.chars();
+
+        while let Some(next_char) = chars.next() {
+            if next_char != '\\' {
+                output.push(next_char);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => output.push('\n'),
+                Some('t') => output.push('\t'),
+                Some('r') => output.push('\r'),
+                Some('0') => output.push('\0'),
+                Some(escaped @ ('\\' | '"' | '$')) => output.push(escaped),
+                Some(escaped) => return Err(format!("Unknown escape sequence '\\{escaped}'")),
+                None => return Err("Expected a character after '\\'".to_string())
+            }
+        }
+        Ok(output)
+    }
+
+    // Removes the line break after the opening quotes, the indentation of the closing quotes and the indentation all lines have in common
+    fn dedent(text: &str) -> String {
+        let mut lines: Vec<&str> = text.split('\n').collect();
+
+        if lines.len() > 1 && lines[0].trim().is_empty() {
+            lines.remove(0);
+        }
+        if lines.len() > 1 && lines[lines.len() - 1].trim().is_empty() {
+            lines.pop();
+        }
+
+        let indentation: usize = lines.iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+            .min()
+            .unwrap_or(0);
+
+        lines.iter()
+            .map(|line| line.get(indentation..).unwrap_or(""))
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    // Literals which don't fit into an i64 become big integers
+    fn extract_integer(literal: &str) -> Result<Value, String> {
+        let (digits, radix): (&str, u32) = match literal.get(..2) {
+            Some("0x" | "0X") => (&literal[2..], 16),
+            Some("0b" | "0B") => (&literal[2..], 2),
+            Some("0o" | "0O") => (&literal[2..], 8),
+            _ => (literal, 10)
+        };
+
+        let digits: String = digits.replace('_', "");
+
+        match i64::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(Value::Integer(value)),
+            Err(_) => ObjBigInt::parse(&digits, radix)
+                .map(|value| Value::Object(DynType::from(value)))
+                .ok_or_else(|| format!("Invalid integer literal '{literal}'"))
+        }
+    }
+
+    fn extract_float(literal: &str) -> Result<f64, String> {
+        match literal.replace('_', "").parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("Float literal '{literal}' is out of range for a 64-bit float"))
+        }
+    }
+
+    fn extract_decimal(literal: &str) -> Result<Value, String> {
+        let digits: String = literal.trim_end_matches('d').replace('_', "");
+
+        match ObjDecimal::parse(&digits) {
+            Ok(value) => Ok(Value::from(value)),
+            Err(err) => Err(format!("Invalid decimal literal '{literal}': {err}"))
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use Precedence::*;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, PartialOrd, Clone, Copy)]
 pub enum Precedence {
     NONE,
@@ -8,9 +9,14 @@ pub enum Precedence {
     AND,
     EQUALITY, // ==
     COMPARISON, // < > <= >=
+    BITOR, // |
+    BITXOR, // ^
+    BITAND, // &
+    SHIFT, // << >>
     TERM,
     FACTOR,
     UNARY,
+    POWER, // **
     CALL,
     PRIMARY,
 }
@@ -23,10 +29,15 @@ impl Precedence{
             OR => AND,
             AND => EQUALITY,
             EQUALITY => COMPARISON,
-            COMPARISON => TERM,
+            COMPARISON => BITOR,
+            BITOR => BITXOR,
+            BITXOR => BITAND,
+            BITAND => SHIFT,
+            SHIFT => TERM,
             TERM => FACTOR,
             FACTOR => UNARY,
-            UNARY => CALL,
+            UNARY => POWER,
+            POWER => CALL,
             CALL => PRIMARY,
             PRIMARY => PRIMARY
         }
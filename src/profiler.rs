@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use super::chunk::{decode_line, decode_opcode};
+use super::opcode::{OpCode, OPCODES};
+use super::vm::{Status, VM};
+
+/* PROFILER
+
+ Profile::run executes a VM with run_until and counts every instruction before it runs: per opcode, per
+ source line and per function, and it measures the wall-clock time of every function. Profiles add up, so one
+ Profile can collect several runs.
+
+ report writes the counts as tables sorted by the number of instructions, folded writes them in the folded
+ stack format of flamegraph tools, one line per stack with the number of instructions as its weight:
+
+  main;line 2;Add 3
+
+ The language has no functions yet, so every instruction belongs to the function main and its time is the
+ time of the whole run. Counting costs a few hashes per instruction, the times include that overhead.
+
+ */
+
+const MAIN: &str = "main";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub instructions: u64,
+    pub time: Duration
+}
+
+#[derive(Debug, Default)]
+pub struct Profile {
+    opcodes: BTreeMap<usize, u64>, // by the position of the opcode in OPCODES
+    lines: BTreeMap<u32, u64>,
+    stacks: BTreeMap<(u32, usize), u64>, // by line and opcode
+    functions: BTreeMap<String, FunctionProfile>
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Runs the VM to the end, None if it fails like VM::run
+    pub fn run(&mut self, vm: &mut VM) -> Option<()> {
+        let code: &[u8] = vm.get_chunk().code();
+        let mut instructions: u64 = 0;
+        let start: Instant = Instant::now();
+
+        let status: Option<Status> = vm.run_until(|ip| {
+            // the VM reports the invalid opcode right after
+            let Ok(opcode) = decode_opcode(code, ip) else {
+                return false;
+            };
+            let (opcode, line): (usize, u32) = (opcode as usize, decode_line(code, ip));
+            *self.opcodes.entry(opcode).or_default() += 1;
+            *self.lines.entry(line).or_default() += 1;
+            *self.stacks.entry((line, opcode)).or_default() += 1;
+            instructions += 1;
+            false
+        });
+
+        let function: &mut FunctionProfile = self.functions.entry(MAIN.to_string()).or_default();
+        function.instructions += instructions;
+        function.time += start.elapsed();
+        status.map(|_| ())
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.lines.values().sum()
+    }
+
+    pub fn opcode_count(&self, opcode: OpCode) -> u64 {
+        self.opcodes.get(&(opcode as usize)).copied().unwrap_or(0)
+    }
+
+    pub fn line_count(&self, line: u32) -> u64 {
+        self.lines.get(&line).copied().unwrap_or(0)
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.get(name)
+    }
+
+    pub fn report(&self) -> String {
+        let total: u64 = self.instructions();
+        let percent = |count: u64| count as f64 * 100.0 / total.max(1) as f64;
+        let mut output: String = String::new();
+
+        let _ = writeln!(output, "{:<20} {:>12} {:>12}", "Function", "Instructions", "Time");
+        for (name, function) in sorted(self.functions.iter().map(|(name, function)| (name.clone(), function)), |function| function.instructions) {
+            let _ = writeln!(output, "{name:<20} {:>12} {:>12}", function.instructions, format!("{:.2?}", function.time));
+        }
+
+        let _ = writeln!(output, "\n{:<20} {:>12} {:>8}", "Opcode", "Instructions", "Percent");
+        for (opcode, count) in sorted(self.opcodes.iter().map(|(opcode, count)| (format!("{:?}", OPCODES[*opcode]), count)), |count| **count) {
+            let _ = writeln!(output, "{opcode:<20} {count:>12} {:>7.1}%", percent(*count));
+        }
+
+        let _ = writeln!(output, "\n{:<20} {:>12} {:>8}", "Line", "Instructions", "Percent");
+        for (line, count) in sorted(self.lines.iter().map(|(line, count)| (*line, count)), |count| **count) {
+            let _ = writeln!(output, "{line:<20} {count:>12} {:>7.1}%", percent(*count));
+        }
+        output
+    }
+
+    pub fn folded(&self) -> String {
+        self.stacks.iter()
+            .map(|((line, opcode), count)| format!("{MAIN};line {line};{:?} {count}\n", OPCODES[*opcode]))
+            .collect()
+    }
+}
+
+// The most executed first, ties keep the order of the keys
+fn sorted<K, V>(rows: impl Iterator<Item = (K, V)>, count: impl Fn(&V) -> u64) -> Vec<(K, V)> {
+    let mut rows: Vec<(K, V)> = rows.collect();
+    rows.sort_by_key(|(_, value)| std::cmp::Reverse(count(value)));
+    rows
+}
@@ -0,0 +1,39 @@
+use super::ast::{CompileError, Expr, ExprKind, Program};
+use super::native::{find_native, NATIVES};
+
+// The second pass: binds every call to its native and checks the number of arguments
+pub fn resolve(program: &mut Program) -> Result<(), Vec<CompileError>> {
+    let mut errors: Vec<CompileError> = Vec::new();
+    resolve_expression(&mut program.expression, &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn resolve_expression(expression: &mut Expr, errors: &mut Vec<CompileError>) {
+    match &mut expression.kind {
+        ExprKind::Literal(_) => {},
+        ExprKind::Interpolation(parts) => parts.iter_mut().for_each(|part| resolve_expression(part, errors)),
+        ExprKind::Grouping(inner) => resolve_expression(inner, errors),
+        ExprKind::Unary { operand, .. } => resolve_expression(operand, errors),
+        ExprKind::Binary { left, right, .. } => {
+            resolve_expression(left, errors);
+            resolve_expression(right, errors);
+        },
+        ExprKind::Call { name, callee, arguments, native } => {
+            match find_native(name) {
+                None => errors.push(CompileError::at(*callee, &format!("Undefined function '{name}'"))),
+                Some(index) if arguments.len() != NATIVES[index].arity => {
+                    let msg: String = format!("{} expects {} arguments, but got {}", NATIVES[index].name, NATIVES[index].arity, arguments.len());
+                    errors.push(CompileError::at(*callee, &msg));
+                },
+                Some(index) => *native = Some(index)
+            }
+
+            arguments.iter_mut().for_each(|argument| resolve_expression(argument, errors));
+        }
+    }
+}
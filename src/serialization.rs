@@ -0,0 +1,281 @@
+use super::chunk::{decode_operand, instruction_size, Chunk, INSTRUCTION_SIZE, OPERAND_SIZE};
+use super::data_structures::{obj_big_int::ObjBigInt, obj_decimal::{ObjDecimal, MAX_SCALE}, obj_rational::ObjRational, DynType};
+use super::opcode::OpCode;
+use super::value::Value;
+use super::verifier::verify;
+
+/* EOSC FILE FORMAT
+
+ A chunk in memory contains raw Values and native-endian numbers, so it can't be written to a file as it is.
+ Chunk::serialize writes it in a portable format instead, every number is little-endian:
+
+  HEADER     "EOSC" u16 VERSION
+  CONSTANTS  u32 COUNT, COUNT times: u8 TAG DATA
+  CODE       u32 COUNT, COUNT times: u8 OPCODE [u32 OPERAND | u32 CONSTANT INDEX]
+  LINES      u32 COUNT, COUNT times: u32 LINE u32 INSTRUCTIONS
+
+ The lines are run-length encoded, each run gives the line of the next INSTRUCTIONS instructions.
+ An opcode is stored as its position in the enum OpCode, adding or reordering opcodes requires a new VERSION.
+
+  TAG        DATA
+  NULL       -
+  BOOLEAN    u8
+  INTEGER    i64
+  FLOAT      u64 bits of the f64
+  STRING     u32 LENGTH, LENGTH bytes of UTF-8
+  BIG_INT    u8 SIGN, u32 COUNT, COUNT u32 limbs (least significant first)
+  DECIMAL    i128 MANTISSA, u32 SCALE
+  RATIONAL   BIG_INT numerator, BIG_INT denominator (without their tags)
+  FUNCTION   reserved for the chunks of functions, the language doesn't have them yet
+
+ Chunk::deserialize checks the whole file, interns the strings again and verifies the chunk, see verifier.rs.
+
+ */
+
+const MAGIC: &[u8; 4] = b"EOSC";
+pub const VERSION: u16 = 1;
+
+const TAG_NULL: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_INTEGER: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_BIG_INT: u8 = 5;
+const TAG_DECIMAL: u8 = 6;
+const TAG_RATIONAL: u8 = 7;
+const TAG_FUNCTION: u8 = 8;
+
+impl Chunk {
+    pub fn serialize(&self) -> Vec<u8> {
+        let code: &[u8] = self.code();
+
+        let mut constants: Vec<u8> = Vec::new();
+        let mut instructions: Vec<u8> = Vec::new();
+        let mut lines: Vec<(u32, u32)> = Vec::new();
+        let (mut constant_count, mut instruction_count): (u32, u32) = (0, 0);
+
+        let mut index: usize = 0;
+        while let Some((opcode, line)) = self.read_opcode(index) {
+            instructions.push(opcode as u8);
+
+            match instruction_size(opcode) - INSTRUCTION_SIZE {
+                0 => {},
+                OPERAND_SIZE => instructions.extend(decode_operand(code, index + INSTRUCTION_SIZE).to_le_bytes()),
+                _ => {
+                    let (value, _) = self.read_value(index + INSTRUCTION_SIZE).expect("every value of an instruction is a constant of the chunk");
+                    write_constant(&mut constants, &value);
+                    instructions.extend(constant_count.to_le_bytes());
+                    constant_count += 1;
+                }
+            }
+
+            match lines.last_mut() {
+                Some((last, count)) if *last == line => *count += 1,
+                _ => lines.push((line, 1))
+            }
+
+            instruction_count += 1;
+            index += instruction_size(opcode);
+        }
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(constants.len() + instructions.len() + 8 * lines.len() + 18);
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(constant_count.to_le_bytes());
+        bytes.extend(constants);
+        bytes.extend(instruction_count.to_le_bytes());
+        bytes.extend(instructions);
+        bytes.extend((lines.len() as u32).to_le_bytes());
+        for (line, count) in lines {
+            bytes.extend(line.to_le_bytes());
+            bytes.extend(count.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Chunk, String> {
+        let mut reader: Reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not an eosc file".to_string());
+        }
+        let version: u16 = u16::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            return Err(format!("Unsupported eosc version {version}, expected {VERSION}"));
+        }
+
+        let constant_count: u32 = reader.u32()?;
+        let mut constants: Vec<Option<Value>> = Vec::new();
+        for _ in 0..constant_count {
+            constants.push(Some(read_constant(&mut reader)?));
+        }
+
+        let instruction_count: u32 = reader.u32()?;
+        let mut instructions: Vec<(OpCode, Option<u32>)> = Vec::new();
+        for _ in 0..instruction_count {
+            let opcode: OpCode = OpCode::try_from(reader.u8()?)?;
+            let operand: Option<u32> = match instruction_size(opcode) - INSTRUCTION_SIZE {
+                0 => None,
+                _ => Some(reader.u32()?)
+            };
+            instructions.push((opcode, operand));
+        }
+
+        let run_count: u32 = reader.u32()?;
+        let mut lines: Vec<u32> = Vec::new();
+        for _ in 0..run_count {
+            let (line, count): (u32, u32) = (reader.u32()?, reader.u32()?);
+            if lines.len() + count as usize > instructions.len() {
+                return Err("More lines than instructions".to_string());
+            }
+            lines.extend(std::iter::repeat_n(line, count as usize));
+        }
+
+        if lines.len() != instructions.len() {
+            return Err("Every instruction needs a line".to_string());
+        }
+        if reader.position != bytes.len() {
+            return Err(format!("Unexpected data after the end at byte {}", reader.position));
+        }
+
+        let mut chunk: Chunk = Chunk::new(bytes.len().max(INSTRUCTION_SIZE));
+        for ((opcode, operand), line) in instructions.into_iter().zip(lines) {
+            match (instruction_size(opcode) - INSTRUCTION_SIZE, operand) {
+                (0, _) => chunk.add_opcode(opcode, line),
+                (OPERAND_SIZE, Some(operand)) => chunk.add_opcode_with_operand(opcode, operand, line),
+                (_, Some(index)) => {
+                    // every constant belongs to exactly one instruction
+                    let value: Value = constants.get_mut(index as usize).and_then(Option::take)
+                        .ok_or_else(|| format!("Invalid constant index {index}"))?;
+                    chunk.add_opcode_with_value(opcode, value, line);
+                },
+                (_, None) => return Err(format!("{opcode:?} is missing its operand"))
+            }
+        }
+
+        if constants.iter().any(Option::is_some) {
+            return Err("Unused constants".to_string());
+        }
+
+        verify(&chunk)?;
+        Ok(chunk)
+    }
+}
+
+fn write_constant(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => bytes.push(TAG_NULL),
+        Value::Boolean(boolean) => bytes.extend([TAG_BOOLEAN, *boolean as u8]),
+        Value::Integer(integer) => {
+            bytes.push(TAG_INTEGER);
+            bytes.extend(integer.to_le_bytes());
+        },
+        Value::Float(float) => {
+            bytes.push(TAG_FLOAT);
+            bytes.extend(float.to_bits().to_le_bytes());
+        },
+        Value::Object(DynType::Text(text)) => {
+            let data: &[u8] = text.get_data().as_bytes();
+            bytes.push(TAG_STRING);
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(data);
+        },
+        Value::Object(DynType::BigInt(big)) => {
+            bytes.push(TAG_BIG_INT);
+            write_big_int(bytes, big);
+        },
+        Value::Object(DynType::Decimal(decimal)) => {
+            bytes.push(TAG_DECIMAL);
+            bytes.extend(decimal.mantissa().to_le_bytes());
+            bytes.extend(decimal.scale().to_le_bytes());
+        },
+        Value::Object(DynType::Rational(rational)) => {
+            bytes.push(TAG_RATIONAL);
+            write_big_int(bytes, rational.numerator());
+            write_big_int(bytes, rational.denominator());
+        }
+    }
+}
+
+fn write_big_int(bytes: &mut Vec<u8>, big: &ObjBigInt) {
+    let (negative, limbs): (bool, &[u32]) = big.to_limbs();
+
+    bytes.push(negative as u8);
+    bytes.extend((limbs.len() as u32).to_le_bytes());
+    for limb in limbs {
+        bytes.extend(limb.to_le_bytes());
+    }
+}
+
+fn read_constant(reader: &mut Reader) -> Result<Value, String> {
+    match reader.u8()? {
+        TAG_NULL => Ok(Value::Null),
+        TAG_BOOLEAN => match reader.u8()? {
+            0 => Ok(Value::Boolean(false)),
+            1 => Ok(Value::Boolean(true)),
+            byte => Err(format!("Invalid boolean {byte}"))
+        },
+        TAG_INTEGER => Ok(Value::Integer(i64::from_le_bytes(reader.array()?))),
+        TAG_FLOAT => Ok(Value::Float(f64::from_bits(u64::from_le_bytes(reader.array()?)))),
+        TAG_STRING => {
+            let length: usize = reader.u32()? as usize;
+            let text: String = String::from_utf8(reader.take(length)?.to_vec()).map_err(|_| "Invalid UTF-8 in a string".to_string())?;
+            Ok(Value::Object(DynType::from(text)))
+        },
+        TAG_BIG_INT => Ok(Value::from(read_big_int(reader)?)),
+        TAG_DECIMAL => {
+            let mantissa: i128 = i128::from_le_bytes(reader.array()?);
+            let scale: u32 = reader.u32()?;
+            if scale > MAX_SCALE {
+                return Err(format!("The scale {scale} of a decimal exceeds {MAX_SCALE}"));
+            }
+            Ok(Value::from(ObjDecimal::new(mantissa, scale)))
+        },
+        TAG_RATIONAL => {
+            let numerator: ObjBigInt = read_big_int(reader)?;
+            let denominator: ObjBigInt = read_big_int(reader)?;
+            Ok(Value::from(ObjRational::new(numerator, denominator)?))
+        },
+        TAG_FUNCTION => Err("Functions are not supported yet".to_string()),
+        tag => Err(format!("Unknown constant tag {tag}"))
+    }
+}
+
+fn read_big_int(reader: &mut Reader) -> Result<ObjBigInt, String> {
+    let negative: bool = reader.u8()? != 0;
+    let count: usize = reader.u32()? as usize;
+
+    let limbs: Vec<u32> = reader.take(count.checked_mul(4).ok_or_else(|| "Too many limbs".to_string())?)?
+        .chunks_exact(4)
+        .map(|limb| u32::from_le_bytes(limb.try_into().unwrap()))
+        .collect();
+    Ok(ObjBigInt::from_limbs(negative, limbs))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl <'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end: usize = self.position.checked_add(count).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("Unexpected end of the file at byte {}", self.position))?;
+
+        let bytes: &'a [u8] = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
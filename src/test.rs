@@ -4,26 +4,96 @@ pub fn run_tests() {
     runtime_error();
     compiler_string_manipulation();
     compiler_strings_comparison();
+    compiler_string_interpolation();
+    compiler_multiline_raw_string();
     compiler_boolean_expression();
     compiler_boolean();
     compiler_calculating();
+    compiler_operators();
+    compiler_comparison();
+    compiler_constant_folding();
+    runtime_error_after_folding();
+    runtime_error_bitwise_float();
+    compiler_integer_promotion();
+    compiler_big_integer();
+    compiler_exact_numbers();
+    runtime_error_division_by_zero();
+    compiler_natives();
     compiler_error_message();
+    compiler_number_literals();
+    compiler_number_overflow();
+    serialization_round_trip();
+    serialization_errors();
+    verifier_accepts_compiled_programs();
+    verifier_rejects_invalid_chunks();
+    assembler_round_trip();
+    assembler_golden_listing();
+    assembler_errors();
+    disassembler_golden_listing();
+    disassembler_without_source();
+    formatter_golden_output();
+    formatter_idempotent();
+    vm_trace();
+    debugger_stepping();
+    debugger_cli();
+    json_round_trip();
+    dap_session();
+    dap_malformed_messages();
+    lsp_session();
+    lsp_malformed_messages();
+    profiler_counts();
+    coverage_lines_and_branches();
+    parser_syntax_tree();
+    resolver_natives();
+    parser_non_ascii();
     lexer_integer_float();
+    lexer_number_literals();
     lexer_keyword_identifier();
     lexer_string_parsing();
+    lexer_string_interpolation();
+    lexer_multiline_raw_string();
     lexer_one_lookahed_token();
     lexer_whitespace_comment();
+    lexer_comment_trivia();
     vm_binary_operations();
     vm_negate();
+    vm_invalid_bytecode();
+    vm_not();
+    optimizer_superinstructions();
+    optimizer_jump_targets();
+    big_int_arithmetic();
+    exact_number_arithmetic();
+    comparison_semantics();
+    #[cfg(feature = "nan-boxing")]
+    nan_value_encoding();
 }
 
 
 mod testing {
-    use std::fs::read_to_string;
-    use crate::data_structures::DynType;
+    use std::cmp::Ordering;
+    use std::fs::{read_dir, read_to_string};
+    use std::io::Write;
+    use std::rc::Rc;
+    use crate::data_structures::{obj_big_int::ObjBigInt, obj_decimal::ObjDecimal, obj_rational::ObjRational, DynType};
     use crate::token::Token;
+    use crate::trace::Trace;
     use crate::{lexer::Lexer, token::TokenType};
-    use crate::{vm::VM, chunk::Chunk, opcode::OpCode, value::Value, compiler::Compiler, common::{DEFAULT_STACK_CAPACITY, SharedData}};
+    use crate::assembler::assemble;
+    use crate::{dap, lsp};
+    use crate::debugger::{Debugger, Stop};
+    use crate::disassembler::disassemble;
+    use crate::formatter::{format, FormatConfig};
+    use crate::coverage::Coverage;
+    use crate::ast::{CompileError, Expr, ExprKind, Program};
+    use crate::json::{read_message, write_message, Json};
+    use crate::native::find_native;
+    use crate::opcode::OPCODES;
+    use crate::optimizer::optimize;
+    use crate::parser::Parser;
+    use crate::profiler::Profile;
+    use crate::resolver::resolve;
+    use crate::verifier::verify;
+    use crate::{vm::VM, chunk::{decode_opcode, format_constant, instruction_size, listing, Chunk, INSTRUCTION_SIZE, OPERAND_SIZE, VALUE_SIZE}, opcode::OpCode, value::{Comparison, Value}, compiler::Compiler, common::{DEFAULT_STACK_CAPACITY, SharedData}};
 
 
     pub fn runtime_error(){
@@ -38,10 +108,11 @@ mod testing {
             chunk.add_opcode(OpCode::Print, 0);
             let mut vm: VM = VM::new(&chunk);
 
-            let _ = vm.run();
-
+            assert!(vm.run().is_none());
+            assert_eq!(vm.get_error(), Some("Integer(1) and Null can not be added"));
+            assert_eq!(vm.get_line(), 1);
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
         }
     }
 
@@ -60,7 +131,7 @@ mod testing {
 
             assert_eq!(vm.get_stack(), &vec![Value::Boolean(true)])//&vec![Value::Obj(ObjectString::new("Hallo Welt, anscheinend hat das funktioniert!".to_string()))])
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
         }
     }
 
@@ -79,7 +150,46 @@ mod testing {
 
             assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>("String".to_string()))])
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_string_interpolation(){
+        let code: String = read_to_string("src/tests/testing_string_interpolation.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>("total: 3, nested true!".to_string()))])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_multiline_raw_string(){
+        let code: String = read_to_string("src/tests/testing_multiline_raw_string.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: &str = "SELECT *\n  FROM \"table\"\tC:\\path{\"key\": \"${value}\"}C:\\new!";
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>(expected.to_string()))])
+        } else {
+            panic!("Expected the code to compile");
         }
     }
 
@@ -99,7 +209,7 @@ mod testing {
 
             assert_eq!(vm.get_stack(), vec![Value::Boolean(true)])
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
         }
     }
 
@@ -119,7 +229,7 @@ mod testing {
             //println!("{:?}", vm);
             assert_eq!(vm.get_stack(), vec![Value::Boolean(false)])
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
         }
     }
 
@@ -139,7 +249,241 @@ mod testing {
 
             assert_eq!(vm.get_stack(), vec![Value::Integer(8)])
         } else {
-            assert!(false);
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_operators(){
+        let code: String = read_to_string("src/tests/testing_compiler_operators.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            assert_eq!(vm.get_stack(), vec![Value::Integer(2 - 40 + 512 + 11 - 4 - 4)])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_comparison(){
+        let code: String = read_to_string("src/tests/testing_comparison.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: &str = "false true false false true true true true true false true true false true";
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>(expected.to_string()))])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    // Compiles the code with or without constant folding, returns the result or the line and message of the runtime error
+    fn run_folded(code: &String, fold_constants: bool) -> (Result<String, (u32, String)>, usize) {
+        let mut lexer: Lexer = Lexer::new(code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(code), SharedData::new(&chunk));
+        compiler.set_constant_folding(fold_constants);
+
+        if compiler.compile().is_none() {
+            panic!("Expected {code} to compile");
+        }
+        let mut vm: VM = VM::new(&chunk);
+
+        match vm.run() {
+            Some(()) => (Ok(format!("{:?}", vm.get_stack())), chunk.len()),
+            None => (Err((vm.get_line(), vm.get_error().unwrap_or_default().to_string())), chunk.len())
+        }
+    }
+
+    pub fn compiler_constant_folding(){
+        let programs: String = read_to_string("src/tests/testing_constant_folding.eos").unwrap();
+
+        for program in programs.lines() {
+            let code: String = program.to_string();
+            let (folded, folded_size) = run_folded(&code, true);
+            let (unfolded, unfolded_size) = run_folded(&code, false);
+
+            assert_eq!(folded, unfolded, "{code}");
+            assert!(folded_size <= unfolded_size, "{code}");
+        }
+
+        // 1 + 2 * 4 - 1 becomes a single constant
+        let code: String = read_to_string("src/tests/testing_compiler.eos").unwrap();
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(&code), SharedData::new(&chunk));
+
+        compiler.compile().unwrap();
+        assert_eq!(opcodes(&chunk), vec![OpCode::Constant, OpCode::Return]);
+    }
+
+    pub fn runtime_error_after_folding(){
+        // 2 * 3 is folded, the failing addition still reports the line of its operator
+        let code: String = "2 * 3\n+\nNull\n".to_string();
+
+        let expected: (u32, String) = (2, "Integer(6) and Null can not be added".to_string());
+        assert_eq!(run_folded(&code, true).0, Err(expected.clone()));
+        assert_eq!(run_folded(&code, false).0, Err(expected));
+    }
+
+    pub fn runtime_error_bitwise_float(){
+        let code: String = read_to_string("src/tests/testing_runtime_bitwise_float.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            assert!(vm.run().is_none());
+            assert_eq!(vm.get_error(), Some("& is only available for integers, not for Float(1.5) and Integer(1)"));
+            assert_eq!(vm.get_line(), 2);
+        } else {
+            panic!("Expected the code to compile");
+        }
+
+        // errors show the type and the value, not the Rust representation of an object
+        let code: String = "\"text\" < 2 ** 70".to_string();
+        let expected: String = "< is only available for numbers and strings, not for String(text) and BigInt(1180591620717411303424)".to_string();
+        assert_eq!(run_folded(&code, false).0, Err((1, expected)));
+    }
+
+    pub fn compiler_integer_promotion(){
+        let code: String = read_to_string("src/tests/testing_runtime_overflow.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: ObjBigInt = ObjBigInt::from(i64::MAX).add(&ObjBigInt::from(1i64));
+            assert_eq!(vm.get_stack(), vec![Value::Object(DynType::from(expected))])
+        } else {
+            panic!("Expected the code to compile");
+        }
+
+        // bits shifted out of an i64 promote it instead of getting lost
+        let big = |digits: &str| match digits.strip_prefix('-') {
+            Some(digits) => Value::from(ObjBigInt::parse(digits, 10).unwrap().neg()),
+            None => Value::from(ObjBigInt::parse(digits, 10).unwrap())
+        };
+        assert_eq!(Value::Integer(1) << Value::Integer(63), Ok(big("9223372036854775808")));
+        assert_eq!(Value::Integer(1) << Value::Integer(64), Ok(big("18446744073709551616")));
+        assert_eq!(Value::Integer(-1) << Value::Integer(63), Ok(Value::Integer(i64::MIN)));
+        assert_eq!(big("18446744073709551616") >> Value::Integer(63), Ok(Value::Integer(2)));
+        assert_eq!(Value::Integer(-5) >> Value::Integer(70), Ok(Value::Integer(-1)));
+        assert_eq!(Value::Integer(1) << Value::Integer(-1), Err("Can not shift by -1, the shift amount can't be negative".to_string()));
+        assert_eq!(Value::Integer(1) << Value::Integer(1 << 30), Err("The result of 1 << 1073741824 has more than 16777216 bits".to_string()));
+
+        // big integers take part in the bitwise operators like any other integer
+        assert_eq!(big("1180591620717411303425") & Value::Integer(3), Ok(Value::Integer(1)));
+        assert_eq!(!big("1180591620717411303424"), Ok(big("-1180591620717411303425")));
+        assert_eq!(big("-1180591620717411303424") | Value::Integer(1), Ok(big("-1180591620717411303423")));
+        assert_eq!(big("1180591620717411303424") ^ big("1180591620717411303424"), Ok(Value::Integer(0)));
+
+        let code: String = "1 << 63".to_string();
+        assert_eq!(run_folded(&code, true).0, run_folded(&code, false).0);
+        assert_eq!(run_folded(&code, false).0, Ok(format!("{:?}", vec![big("9223372036854775808")])));
+    }
+
+    pub fn compiler_big_integer(){
+        let code: String = read_to_string("src/tests/testing_big_integer.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: &str = "170141183460469231731687303715884105728 -56713727820156410577229101238628035243 976371285 true 5";
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>(expected.to_string()))])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_exact_numbers(){
+        let code: String = read_to_string("src/tests/testing_exact_numbers.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: &str = "true false 3.30 0.333333333333333333 1/2 1/4 0.6667 2 2.68 1.5 true -1/3 1.5 1500";
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>(expected.to_string()))])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn runtime_error_division_by_zero(){
+        let code: String = read_to_string("src/tests/testing_runtime_division_by_zero.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            assert!(vm.run().is_none());
+            assert_eq!(vm.get_error(), Some("Division by zero"));
+            assert_eq!(vm.get_line(), 2);
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_natives(){
+        let code: String = read_to_string("src/tests/testing_natives.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            let expected: String = format!("{} {} {} {}", i64::MIN, i64::MIN, i64::MIN, i64::MAX);
+            assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from::<String>(expected))])
+        } else {
+            panic!("Expected the code to compile");
         }
     }
 
@@ -154,6 +498,863 @@ mod testing {
         compiler.compile();
     }
 
+    pub fn compiler_number_literals(){
+        let code: String = read_to_string("src/tests/testing_number_literals.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            assert_eq!(vm.get_stack(), vec![Value::Integer(31 + 5 + 15 + 1_000_000 + 6)])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    pub fn compiler_number_overflow(){
+        let code: String = read_to_string("src/tests/testing_number_overflow.eos").unwrap();
+        let shared_code: SharedData<String> = SharedData::new(&code);
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), shared_code, SharedData::new(&chunk));
+
+        if compiler.compile().is_some(){
+            let mut vm: VM = VM::new(&chunk);
+
+            let _ = vm.run();
+
+            assert_eq!(vm.get_stack(), vec![Value::Integer(i64::MAX)])
+        } else {
+            panic!("Expected the code to compile");
+        }
+    }
+
+    fn run_chunk(chunk: &Chunk) -> Result<String, u32> {
+        let mut vm: VM = VM::new(chunk);
+
+        match vm.run() {
+            Some(()) => Ok(format!("{:?}", vm.get_stack())),
+            None => Err(vm.get_line())
+        }
+    }
+
+    // Every program in src/tests that compiles, the others test the lexer or compile errors
+    fn compiled_test_programs() -> Vec<(String, Chunk)> {
+        let mut paths: Vec<String> = read_dir("src/tests").unwrap()
+            .map(|entry| entry.unwrap().path().display().to_string())
+            .filter(|path| path.ends_with(".eos"))
+            .collect();
+        paths.sort();
+
+        paths.into_iter().filter_map(|path| {
+            let code: String = read_to_string(&path).unwrap();
+
+            let mut lexer: Lexer = Lexer::new(&code);
+            let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+            let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(&code), SharedData::new(&chunk));
+            compiler.compile().map(|_| (path, chunk))
+        }).collect()
+    }
+
+    pub fn serialization_round_trip(){
+        let programs: Vec<(String, Chunk)> = compiled_test_programs();
+
+        for (path, chunk) in &programs {
+            let bytes: Vec<u8> = chunk.serialize();
+            let loaded: Chunk = Chunk::deserialize(&bytes).unwrap_or_else(|err| panic!("{path}: {err}"));
+
+            assert_eq!(loaded.serialize(), bytes, "{path}");
+            assert_eq!(loaded.code().len(), chunk.code().len(), "{path}");
+            assert_eq!(run_chunk(&loaded), run_chunk(chunk), "{path}");
+        }
+        assert!(programs.len() >= 20);
+
+        // strings are interned again while loading
+        let mut chunk: Chunk = Chunk::new(1);
+        chunk.add_value(Value::Object(DynType::from("interned".to_string())), 1);
+        chunk.add_value(Value::from(ObjDecimal::parse("-12.345").unwrap()), 2);
+        chunk.add_value(Value::from(ObjRational::new(ObjBigInt::from(-1i64 << 40).pow(3), ObjBigInt::from(3i64)).unwrap()), 3);
+        chunk.add_value(Value::Float(f64::NAN), 4);
+        chunk.add_opcode(OpCode::Return, 5);
+
+        let loaded: Chunk = Chunk::deserialize(&chunk.serialize()).unwrap();
+        let (Some((Value::Object(DynType::Text(original)), _)), Some((Value::Object(DynType::Text(interned)), _))) = (chunk.read_value(INSTRUCTION_SIZE), loaded.read_value(INSTRUCTION_SIZE)) else {
+            panic!("Expected a string constant");
+        };
+        assert!(Rc::ptr_eq(&original, &interned));
+        assert_eq!(loaded.serialize(), chunk.serialize());
+
+        // a value that ends the chunk can be read, bytes that are not a value can't
+        let mut chunk: Chunk = Chunk::new(1);
+        chunk.add_value(Value::Integer(7), 1);
+        assert_eq!(chunk.code().len(), INSTRUCTION_SIZE + VALUE_SIZE);
+        assert!(matches!(chunk.read_value(INSTRUCTION_SIZE), Some((Value::Integer(7), 1))));
+        assert!(chunk.read_value(0).is_none());
+        assert!(chunk.read_value(INSTRUCTION_SIZE + 1).is_none());
+        assert!(Chunk::deserialize(&chunk.serialize()).is_ok());
+
+        for (index, opcode) in OPCODES.iter().enumerate() {
+            assert_eq!(*opcode as usize, index);
+            assert_eq!(OpCode::try_from(index as u8), Ok(*opcode));
+        }
+    }
+
+    pub fn serialization_errors(){
+        let code: String = read_to_string("src/tests/testing_exact_numbers.eos").unwrap();
+        let mut lexer: Lexer = Lexer::new(&code);
+        let chunk: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+        let mut compiler: Compiler = Compiler::new(lexer.lexing(), SharedData::new(&code), SharedData::new(&chunk));
+        compiler.compile().unwrap();
+
+        let bytes: Vec<u8> = chunk.serialize();
+
+        // every truncated file is rejected instead of being read out of bounds
+        for length in 0..bytes.len() {
+            assert!(Chunk::deserialize(&bytes[..length]).is_err());
+        }
+
+        let mut wrong_magic: Vec<u8> = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert_eq!(Chunk::deserialize(&wrong_magic).unwrap_err(), "Not an eosc file");
+
+        let mut wrong_version: Vec<u8> = bytes.clone();
+        wrong_version[4] = 99;
+        assert_eq!(Chunk::deserialize(&wrong_version).unwrap_err(), "Unsupported eosc version 99, expected 1");
+
+        let mut trailing: Vec<u8> = bytes.clone();
+        trailing.push(0);
+        assert!(Chunk::deserialize(&trailing).is_err());
+
+        // EOSC 1, no constants, one instruction with an unknown opcode on line 1
+        let mut unknown_opcode: Vec<u8> = b"EOSC".to_vec();
+        unknown_opcode.extend(1u16.to_le_bytes());
+        unknown_opcode.extend(0u32.to_le_bytes());
+        unknown_opcode.extend(1u32.to_le_bytes());
+        unknown_opcode.push(255);
+        unknown_opcode.extend([1u32, 1, 1].iter().flat_map(|number| number.to_le_bytes()));
+        assert_eq!(Chunk::deserialize(&unknown_opcode).unwrap_err(), "Unknown opcode 255");
+    }
+
+    pub fn verifier_accepts_compiled_programs(){
+        for (path, chunk) in compiled_test_programs() {
+            assert_eq!(verify(&chunk), Ok(()), "{path}");
+        }
+    }
+
+    pub fn verifier_rejects_invalid_chunks(){
+        // 1 + with a single value on the stack
+        let mut underflow: Chunk = Chunk::new(1);
+        underflow.add_value(Value::Integer(1), 1);
+        underflow.add_opcode(OpCode::Add, 1);
+        assert_eq!(verify(&underflow).unwrap_err(), format!("{:06} Add needs 2 values on the stack, but there are 1", INSTRUCTION_SIZE + VALUE_SIZE));
+
+        // the stack holds two values if the jump isn't taken and one if it is
+        let mut unbalanced: Chunk = Chunk::new(1);
+        unbalanced.add_value(Value::Boolean(true), 1);
+        unbalanced.add_opcode_with_operand(OpCode::JumpIfFalse, (INSTRUCTION_SIZE + VALUE_SIZE) as u32, 1);
+        unbalanced.add_value(Value::Integer(1), 1);
+        unbalanced.add_opcode(OpCode::Return, 1);
+        assert!(verify(&unbalanced).unwrap_err().contains("the stack holds"));
+
+        // a jump into the value of the constant after it
+        let mut misaligned: Chunk = Chunk::new(1);
+        misaligned.add_opcode_with_operand(OpCode::Jump, INSTRUCTION_SIZE as u32, 1);
+        misaligned.add_value(Value::Integer(1), 1);
+        assert!(verify(&misaligned).unwrap_err().contains("is not the start of an instruction"));
+
+        let mut past_the_end: Chunk = Chunk::new(1);
+        past_the_end.add_opcode_with_operand(OpCode::Jump, 1000, 1);
+        assert!(verify(&past_the_end).is_err());
+
+        let mut unknown_native: Chunk = Chunk::new(1);
+        unknown_native.add_opcode_with_operand(OpCode::CallNative, 1000, 1);
+        assert_eq!(verify(&unknown_native).unwrap_err(), "000000 CallNative: unknown native 1000");
+
+        let mut too_many_parts: Chunk = Chunk::new(1);
+        too_many_parts.add_value(Value::Integer(1), 1);
+        too_many_parts.add_opcode_with_operand(OpCode::Interpolate, 2, 1);
+        assert!(verify(&too_many_parts).is_err());
+
+        // a Constant whose value has been cut off by truncate
+        let mut missing_value: Chunk = Chunk::new(1);
+        missing_value.add_value(Value::Integer(1), 1);
+        missing_value.add_value(Value::Integer(2), 1);
+        missing_value.truncate(INSTRUCTION_SIZE + VALUE_SIZE);
+        missing_value.add_opcode(OpCode::Constant, 1);
+        missing_value.add_opcode_with_operand(OpCode::Interpolate, 0, 1);
+        missing_value.add_opcode_with_operand(OpCode::Interpolate, 0, 1);
+        assert!(verify(&missing_value).unwrap_err().contains("no value has been written"));
+
+        // a file that decodes fine but would underflow the stack is rejected while loading
+        assert_eq!(Chunk::deserialize(&underflow.serialize()).unwrap_err(), verify(&underflow).unwrap_err());
+    }
+
+    fn to_sexpr(expression: &Expr, source: &str) -> String {
+        match &expression.kind {
+            ExprKind::Literal(_) => source[expression.span.range()].to_string(),
+            ExprKind::Interpolation(parts) => format!("(interpolate {})", parts.iter().map(|part| to_sexpr(part, source)).collect::<Vec<String>>().join(" ")),
+            ExprKind::Grouping(inner) => format!("(group {})", to_sexpr(inner, source)),
+            ExprKind::Unary { operator, operand, .. } => format!("({operator:?} {})", to_sexpr(operand, source)),
+            ExprKind::Binary { operator, left, right, .. } => format!("({operator:?} {} {})", to_sexpr(left, source), to_sexpr(right, source)),
+            ExprKind::Call { name, arguments, .. } => format!("(call {name} {})", arguments.iter().map(|argument| to_sexpr(argument, source)).collect::<Vec<String>>().join(" "))
+        }
+    }
+
+
+    pub fn assembler_round_trip(){
+        let programs: Vec<(String, Chunk)> = compiled_test_programs();
+
+        for (path, chunk) in &programs {
+            let text: String = listing(chunk, path);
+            let assembled: Chunk = assemble(&text).unwrap_or_else(|err| panic!("{path}: {err}\n{text}"));
+
+            assert_eq!(assembled.serialize(), chunk.serialize(), "{path}");
+            assert_eq!(listing(&assembled, path), text, "{path}");
+        }
+
+        // constants that no test program contains
+        let mut chunk: Chunk = Chunk::new(1);
+        chunk.add_value(Value::Null, 1);
+        chunk.add_value(Value::Float(f64::NEG_INFINITY), 1);
+        chunk.add_value(Value::Float(-0.0), 2);
+        chunk.add_value(Value::Object(DynType::from("tab\t \"quote\" ; \\ é \u{301}".to_string())), 2);
+        chunk.add_value(Value::from(ObjDecimal::parse("-0.050").unwrap()), 7);
+        chunk.add_value(Value::from(ObjRational::new(ObjBigInt::from(-2i64), ObjBigInt::from(6i64)).unwrap()), 7);
+        chunk.add_opcode(OpCode::Return, 7);
+
+        let text: String = listing(&chunk, "constants");
+        assert_eq!(assemble(&text).unwrap().serialize(), chunk.serialize(), "{text}");
+    }
+
+    pub fn assembler_golden_listing(){
+        let source: String = read_to_string("src/tests/testing_assembler.easm").unwrap();
+        let golden: String = read_to_string("src/tests/testing_assembler.golden").unwrap();
+
+        let chunk: Chunk = assemble(&source).unwrap();
+        assert_eq!(listing(&chunk, "hand-written"), golden);
+        assert_eq!(verify(&chunk), Ok(()));
+
+        let sum: ObjRational = ObjRational::new(ObjBigInt::parse("299999999999999999999", 10).unwrap().neg(), ObjBigInt::from(3i64)).unwrap();
+        let mut vm: VM = VM::new(&chunk);
+        vm.run().unwrap();
+        assert_eq!(vm.get_stack(), &vec![Value::Object(DynType::from("size: small; \"quoted\"".to_string())), Value::from(sum), Value::Integer(-5)]);
+    }
+
+    pub fn assembler_errors(){
+        let errors: [(&str, &str); 10] = [
+            ("Push", "Assembly line 1: Unknown opcode 'Push'"),
+            ("Constant", "Assembly line 1: Constant needs an operand"),
+            ("Add | 1", "Assembly line 1: Add doesn't take an operand"),
+            ("Constant | Integer(one)", "Assembly line 1: Invalid constant 'Integer(one)'"),
+            ("CallNative | print", "Assembly line 1: Unknown native 'print'"),
+            ("Return\n000000 Return", "Assembly line 2: The instruction is at offset 000005, not 000000"),
+            ("Jump -> nowhere", "Assembly line 1: Undefined label 'nowhere'"),
+            ("start:\nJump -> start", "Assembly line 2: The jump to 000000 goes backwards, jumps only go forward"),
+            ("Jump | 3 -> end\nend:", "Assembly line 1: The offset 3 doesn't lead to 000009"),
+            ("a:\na:", "Assembly line 2: The label 'a' is defined twice")
+        ];
+        for (source, error) in errors {
+            assert_eq!(assemble(source).unwrap_err(), error, "{source}");
+        }
+
+        // the assembler doesn't verify, so invalid chunks can be written on purpose
+        let underflow: Chunk = assemble(".line 4\nAdd").unwrap();
+        assert_eq!(verify(&underflow).unwrap_err(), "000000 Add needs 2 values on the stack, but there are 0");
+    }
+
+    pub fn disassembler_golden_listing(){
+        let path: &str = "src/tests/testing_disassembler.eos";
+        let source: String = read_to_string(path).unwrap();
+        let golden: String = read_to_string("src/tests/testing_disassembler.golden").unwrap();
+
+        let (_, chunk): (String, Chunk) = compiled_test_programs().into_iter().find(|(program, _)| program == path).unwrap();
+        assert_eq!(disassemble(&chunk, "code", Some(&source)), golden);
+    }
+
+    pub fn disassembler_without_source(){
+        let chunk: Chunk = assemble("Constant | Boolean(true)\nJumpIfFalse -> end\n.line 2\nPop\nCallNative | wrapping_neg\nend:").unwrap();
+        let expected: &str = concat!(
+            "    === code ===\n",
+            "    000000    1 Constant           Boolean(true)\n",
+            "+-- 000021    | JumpIfFalse        14 -> 000044\n",
+            "|   000030    2 Pop\n",
+            "|   000035    | CallNative         wrapping_neg\n",
+            "+-> 000044      end\n"
+        );
+        assert_eq!(disassemble(&chunk, "code", None), expected);
+
+        // without jumps there is no gutter
+        assert_eq!(disassemble(&assemble("Return").unwrap(), "code", Some("")), "=== code ===\n000000    1 Return\n");
+    }
+
+    pub fn formatter_golden_output(){
+        let source: String = read_to_string("src/tests/testing_formatter.eos").unwrap();
+        let golden: String = read_to_string("src/tests/testing_formatter.golden").unwrap();
+        let config: FormatConfig = FormatConfig::default();
+
+        assert_eq!(format(&source, &config), Ok(golden.clone()));
+        assert_eq!(format(&golden, &config), Ok(golden));
+
+        // a comment inside of a group breaks it, a narrow width breaks the arguments
+        let narrow: FormatConfig = FormatConfig { indent: 2, width: 20 };
+        assert_eq!(format("(1 + # one\n 2)", &config), Ok("(\n    1\n        + # one\n        2\n)\n".to_string()));
+        assert_eq!(format("wrapping_add(123456, 654321)", &narrow), Ok("wrapping_add(\n  123456,\n  654321\n)\n".to_string()));
+        assert_eq!(format("round(1.5,0)", &narrow), Ok("round(1.5, 0)\n".to_string()));
+        assert_eq!(format("1 + )", &config), Err("At line 1: ')' -> Expected an expression".to_string()));
+    }
+
+    pub fn formatter_idempotent(){
+        let config: FormatConfig = FormatConfig { indent: 4, width: 30 };
+
+        for (path, chunk) in compiled_test_programs() {
+            let formatted: String = format(&read_to_string(&path).unwrap(), &config).unwrap();
+            assert_eq!(format(&formatted, &config).as_ref(), Ok(&formatted), "{path}");
+
+            // the formatted program compiles to the same instructions
+            let mut lexer: Lexer = Lexer::new(&formatted);
+            let reformatted: Chunk = Chunk::new(DEFAULT_STACK_CAPACITY);
+            Compiler::new(lexer.lexing(), SharedData::new(&formatted), SharedData::new(&reformatted)).compile_with_errors().unwrap();
+            let opcodes = |chunk: &Chunk| {
+                let mut opcodes: Vec<OpCode> = Vec::new();
+                let mut index: usize = 0;
+                while let Some((opcode, _)) = chunk.read_opcode(index) {
+                    opcodes.push(opcode);
+                    index += instruction_size(opcode);
+                }
+                opcodes
+            };
+            assert_eq!(opcodes(&reformatted), opcodes(&chunk), "{path}");
+        }
+    }
+
+    pub fn vm_trace(){
+        let chunk: Chunk = assemble(".line 1\nConstant | Integer(1)\n.line 2\nAddConstant | Integer(2)\n.line 3\nConstant | String(\"a\")\nPop").unwrap();
+        let trace = |lines: Option<std::ops::RangeInclusive<u32>>| {
+            let mut output: Vec<u8> = Vec::new();
+            let mut vm: VM = VM::new(&chunk);
+            let trace: Trace = Trace::new(&mut output);
+            vm.set_trace(Some(match lines {
+                Some(lines) => trace.with_lines(lines),
+                None => trace
+            }));
+            vm.run().unwrap();
+            assert_eq!(vm.get_stack(), &vec![Value::Integer(3)]);
+            drop(vm);
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(trace(None), concat!(
+            "          (empty)\n",
+            "000000    1 Constant           Integer(1)\n",
+            "          [ Integer(1) ]\n",
+            "000021    2 AddConstant        Integer(2)\n",
+            "          [ Integer(3) ]\n",
+            "000042    3 Constant           String(\"a\")\n",
+            "          [ Integer(3) ][ String(\"a\") ]\n",
+            "000063    3 Pop\n"
+        ));
+        assert_eq!(trace(Some(2..=2)), "          [ Integer(1) ]\n000021    2 AddConstant        Integer(2)\n");
+        assert_eq!(trace(Some(4..=9)), "");
+    }
+
+    pub fn debugger_stepping(){
+        let path: &str = "src/tests/testing_debugger.eos";
+        let (_, chunk): (String, Chunk) = compiled_test_programs().into_iter().find(|(program, _)| program == path).unwrap();
+
+        // the operators are on the lines before their right operands: 1 1 1 2 2 2 3 2 1 4
+        let mut debugger: Debugger = Debugger::new(&chunk, None);
+        assert_eq!(debugger.location(), Some((0, 1)));
+        assert_eq!(debugger.set_breakpoint(5), Err("There is no code on line 5".to_string()));
+        assert_eq!(debugger.set_breakpoint(3), Ok(()));
+
+        assert_eq!(debugger.resume_until_breakpoint(), Stop::Breakpoint(3));
+        assert_eq!(debugger.location(), Some((102, 3)));
+        assert_eq!(debugger.stack(), vec![Value::Integer(3), Value::Integer(12)]);
+        assert_eq!(debugger.step_instruction(), Stop::Step);
+        assert_eq!(debugger.location(), Some((123, 2)));
+        assert_eq!(debugger.step_line(), Stop::Step);
+        assert_eq!(debugger.location(), Some((128, 1)));
+        assert_eq!(debugger.step_over(), Stop::Step);
+        assert_eq!(debugger.location(), Some((133, 4)));
+        assert_eq!(debugger.step_line(), Stop::Finished);
+        assert_eq!(debugger.location(), None);
+        assert_eq!(debugger.stack(), vec![Value::Integer(27)]);
+        assert_eq!(debugger.step_instruction(), Stop::Finished);
+
+        // a breakpoint is hit every time its line is entered
+        let mut debugger: Debugger = Debugger::new(&chunk, None);
+        debugger.set_breakpoint(2).unwrap();
+        assert_eq!(debugger.resume_until_breakpoint(), Stop::Breakpoint(2));
+        assert_eq!(debugger.location(), Some((51, 2)));
+        assert_eq!(debugger.resume_until_breakpoint(), Stop::Breakpoint(2));
+        assert_eq!(debugger.location(), Some((123, 2)));
+        assert!(debugger.clear_breakpoint(2));
+        assert_eq!(debugger.step_out(), Stop::Finished);
+
+        assert_eq!(debugger.evaluate("wrapping_add(1, 2) * 2"), Ok(Value::Integer(6)));
+        assert_eq!(debugger.evaluate("1 +"), Err("At line 1: '' -> Expected an expression".to_string()));
+
+        let failing: Chunk = assemble(".line 1\nConstant | Integer(1)\n.line 2\nConstant | Integer(0)\nDivide").unwrap();
+        let mut debugger: Debugger = Debugger::new(&failing, None);
+        assert_eq!(debugger.step_line(), Stop::Step);
+        assert_eq!(debugger.step_line(), Stop::Error(2));
+        assert_eq!(debugger.location(), None);
+        assert_eq!(debugger.error(), Some("Division by zero"));
+    }
+
+    pub fn debugger_cli(){
+        let path: &str = "src/tests/testing_debugger.eos";
+        let source: String = read_to_string(path).unwrap();
+        let (_, chunk): (String, Chunk) = compiled_test_programs().into_iter().find(|(program, _)| program == path).unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        let mut debugger: Debugger = Debugger::new(&chunk, Some(&source));
+        debugger.run_cli("b 3\nb x\nbreakpoints\nc\nstack\nsi\nglobals\np 2 ** 10\njump\nc\nq\nstack\n".as_bytes(), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), concat!(
+            "   1 | wrapping_add(1, 2) +\n",
+            "000000    1 Constant           Integer(1)\n",
+            "(eos) Breakpoint on line 3\n",
+            "(eos) Invalid line 'x'\n",
+            "(eos) line 3\n",
+            "(eos) Breakpoint on line 3\n",
+            "   3 | 2\n",
+            "000102    3 Constant           Integer(2)\n",
+            "(eos) [ Integer(3) ][ Integer(12) ]\n",
+            "(eos)    2 | wrapping_mul(3, 4) *\n",
+            "000123    2 Multiply\n",
+            "(eos) No globals\n",
+            "(eos) Integer(1024)\n",
+            "(eos) Unknown command 'jump', try help\n",
+            "(eos) Finished with Integer(27)\n",
+            "(eos) "
+        ));
+    }
+
+    pub fn json_round_trip(){
+        let text: &str = r#"{"seq":1,"list":[true,false,null,-2.5,1e3,[]],"text":"a\"b\\c\né😀","nested":{"empty":{}}}"#;
+        let json: Json = Json::parse(text).unwrap();
+
+        assert_eq!(json.get("seq").as_u64(), Some(1));
+        assert_eq!(json.get("text").as_str(), Some("a\"b\\c\né😀"));
+        assert_eq!(json.get("list").as_array().map(<[Json]>::len), Some(6));
+        assert_eq!(json.get("missing").get("deeper"), &Json::Null);
+        assert_eq!(json.to_string(), r#"{"seq":1,"list":[true,false,null,-2.5,1000,[]],"text":"a\"b\\c\né😀","nested":{"empty":{}}}"#);
+        assert_eq!(Json::parse(&json.to_string()), Ok(json));
+
+        assert_eq!(Json::from("\u{1}\t").to_string(), r#""\u0001\t""#);
+        assert_eq!(Json::parse(r#""\u00e9\ud83d\ude00""#), Ok(Json::from("é😀")));
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("\"open").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+
+    pub fn dap_session(){
+        let request = |seq: u32, command: &str, arguments: Json| Json::object([
+            ("seq", Json::from(seq)), ("type", Json::from("request")), ("command", Json::from(command)), ("arguments", arguments)
+        ]);
+        let program: &str = "src/tests/testing_debugger.eos";
+        let breakpoints: Json = Json::from(vec![Json::object([("line", Json::from(3u32))]), Json::object([("line", Json::from(5u32))])]);
+        let script: [Json; 11] = [
+            request(1, "initialize", Json::object([("adapterID", Json::from("eos"))])),
+            request(2, "launch", Json::object([("program", Json::from(program))])),
+            request(3, "setBreakpoints", Json::object([("source", Json::object([("path", Json::from(program))])), ("breakpoints", breakpoints)])),
+            request(4, "configurationDone", Json::Null),
+            request(5, "stackTrace", Json::object([("threadId", Json::from(1u32))])),
+            request(6, "variables", Json::object([("variablesReference", Json::from(1u32))])),
+            request(7, "evaluate", Json::object([("expression", Json::from("1 + 1"))])),
+            request(8, "next", Json::object([("threadId", Json::from(1u32))])),
+            request(9, "stackTrace", Json::object([("threadId", Json::from(1u32))])),
+            request(10, "continue", Json::object([("threadId", Json::from(1u32))])),
+            request(11, "disconnect", Json::Null)
+        ];
+
+        // the scripted client writes every request up front, the adapter answers into the second pipe
+        let (requests, mut client) = std::io::pipe().unwrap();
+        let (mut answers, adapter) = std::io::pipe().unwrap();
+        for message in &script {
+            write_message(&mut client, message).unwrap();
+        }
+        drop(client);
+        dap::serve(std::io::BufReader::new(requests), adapter).unwrap();
+
+        let mut answers: std::io::BufReader<_> = std::io::BufReader::new(&mut answers);
+        let mut messages: Vec<Json> = Vec::new();
+        while let Some(message) = read_message(&mut answers).unwrap() {
+            messages.push(message);
+        }
+
+        let summary: Vec<String> = messages.iter().map(|message| match message.get("type").as_str() {
+            Some("response") => format!("{} {}", message.get("command").as_str().unwrap(), message.get("success").as_bool().unwrap()),
+            _ => format!("event {} {}", message.get("event").as_str().unwrap(), message.get("body").get("reason").as_str().unwrap_or_default())
+        }).collect();
+        assert_eq!(summary, [
+            "initialize true", "launch true", "event initialized ", "setBreakpoints true", "configurationDone true", "event stopped breakpoint",
+            "stackTrace true", "variables true", "evaluate true", "next true", "event stopped step", "stackTrace true", "continue true",
+            "event output ", "event exited ", "event terminated ", "disconnect true"
+        ]);
+
+        let seqs: Vec<u64> = messages.iter().filter_map(|message| message.get("seq").as_u64()).collect();
+        assert_eq!(seqs, (1..=messages.len() as u64).collect::<Vec<u64>>());
+
+        let verified: Vec<Option<bool>> = messages[3].get("body").get("breakpoints").as_array().unwrap().iter().map(|breakpoint| breakpoint.get("verified").as_bool()).collect();
+        assert_eq!(verified, [Some(true), Some(false)]);
+        let frame: &Json = &messages[6].get("body").get("stackFrames").as_array().unwrap()[0];
+        assert_eq!((frame.get("line").as_u64(), frame.get("source").get("path").as_str()), (Some(3), Some(program)));
+        assert_eq!(messages[7].get("body").get("variables").to_string(),
+            r#"[{"name":"[0]","value":"Integer(3)","variablesReference":0},{"name":"[1]","value":"Integer(12)","variablesReference":0}]"#);
+        assert_eq!(messages[8].get("body").get("result").as_str(), Some("Integer(2)"));
+        assert_eq!(messages[11].get("body").get("stackFrames").as_array().unwrap()[0].get("line").as_u64(), Some(2));
+        assert_eq!(messages[13].get("body").get("output").as_str(), Some("27\n"));
+        assert_eq!(messages[7].get("request_seq").as_u64(), Some(6));
+    }
+
+    pub fn dap_malformed_messages(){
+        let request = |seq: u32, command: &str, arguments: Json| Json::object([
+            ("seq", Json::from(seq)), ("type", Json::from("request")), ("command", Json::from(command)), ("arguments", arguments)
+        ]);
+        let program: &str = "src/tests/testing_runtime_division_by_zero.eos";
+
+        // a body that isn't JSON and a message without a Content-Length, the session goes on after both
+        let (requests, mut client) = std::io::pipe().unwrap();
+        let (mut answers, adapter) = std::io::pipe().unwrap();
+        client.write_all(b"Content-Length: 9\r\n\r\n{\"seq\": 1").unwrap();
+        write_message(&mut client, &request(2, "initialize", Json::Null)).unwrap();
+        client.write_all(b"Content-Type: application/json\r\n\r\n{\"seq\":3}").unwrap();
+        for message in [request(4, "launch", Json::object([("program", Json::from(program))])), request(5, "configurationDone", Json::Null), request(6, "disconnect", Json::Null)] {
+            write_message(&mut client, &message).unwrap();
+        }
+        drop(client);
+        dap::serve(std::io::BufReader::new(requests), adapter).unwrap();
+
+        let mut answers: std::io::BufReader<_> = std::io::BufReader::new(&mut answers);
+        let mut messages: Vec<Json> = Vec::new();
+        while let Some(message) = read_message(&mut answers).unwrap() {
+            messages.push(message);
+        }
+
+        let summary: Vec<String> = messages.iter().map(|message| match message.get("type").as_str() {
+            Some("response") => format!("{} {} {}", message.get("request_seq"), message.get("command").as_str().unwrap(), message.get("success").as_bool().unwrap()),
+            _ => format!("event {}", message.get("event").as_str().unwrap())
+        }).collect();
+        assert_eq!(summary, [
+            "0  false", "2 initialize true", "0  false", "4 launch true", "event initialized", "5 configurationDone true",
+            "event output", "event exited", "event terminated", "6 disconnect true"
+        ]);
+        assert_eq!(messages[0].get("message").as_str(), Some("Malformed message: Expected ',' at byte 9"));
+        assert_eq!(messages[2].get("message").as_str(), Some("Malformed message: A message without a Content-Length"));
+
+        // the runtime error comes with its message
+        assert_eq!(messages[6].get("body").get("output").as_str(), Some("Runtime error on line 2: Division by zero\n"));
+        assert_eq!(messages[7].get("body").get("exitCode").as_u64(), Some(1));
+    }
+
+    pub fn lsp_session(){
+        let message = |id: Option<u32>, method: &str, params: Json| match id {
+            Some(id) => Json::object([("jsonrpc", Json::from("2.0")), ("id", Json::from(id)), ("method", Json::from(method)), ("params", params)]),
+            None => Json::object([("jsonrpc", Json::from("2.0")), ("method", Json::from(method)), ("params", params)])
+        };
+        let uri: &str = "file:///test.eos";
+        let document = || Json::object([("uri", Json::from(uri))]);
+        let at = |line: u32, character: u32| Json::object([
+            ("textDocument", document()), ("position", Json::object([("line", Json::from(line)), ("character", Json::from(character))]))
+        ]);
+        let text: &str = "wrapping_add(1, 2) > 2 !=\n    \"😀\" == \"😀\" !=  wrapping_add(0.5d, 1) == Null";
+        let changes: Json = Json::from(vec![Json::object([("text", Json::from("wrapping_add(1) + true"))])]);
+        let script: [Json; 12] = [
+            message(Some(1), "initialize", Json::object([("capabilities", Json::object([]))])),
+            message(None, "initialized", Json::object([])),
+            message(None, "textDocument/didOpen", Json::object([("textDocument", Json::object([("uri", Json::from(uri)), ("languageId", Json::from("eos")), ("version", Json::from(1u32)), ("text", Json::from(text))]))])),
+            message(Some(2), "textDocument/semanticTokens/full", Json::object([("textDocument", document())])),
+            message(Some(3), "textDocument/hover", at(1, 35)),
+            message(Some(4), "textDocument/references", at(0, 3)),
+            message(Some(8), "textDocument/references", Json::object([
+                ("textDocument", document()), ("position", Json::object([("line", Json::from(1u32)), ("character", Json::from(25u32))])),
+                ("context", Json::object([("includeDeclaration", Json::Bool(true))]))
+            ])),
+            message(Some(5), "textDocument/documentSymbol", Json::object([("textDocument", document())])),
+            message(Some(6), "textDocument/definition", at(0, 3)),
+            message(None, "textDocument/didChange", Json::object([("textDocument", document()), ("contentChanges", changes)])),
+            message(Some(7), "textDocument/formatting", Json::object([("textDocument", document())])),
+            message(None, "exit", Json::Null)
+        ];
+
+        // the scripted client writes every message up front, the server answers into the second pipe
+        let (requests, mut client) = std::io::pipe().unwrap();
+        let (mut answers, server) = std::io::pipe().unwrap();
+        for message in &script {
+            write_message(&mut client, message).unwrap();
+        }
+        drop(client);
+        lsp::serve(std::io::BufReader::new(requests), server).unwrap();
+
+        let mut answers: std::io::BufReader<_> = std::io::BufReader::new(&mut answers);
+        let mut messages: Vec<Json> = Vec::new();
+        while let Some(message) = read_message(&mut answers).unwrap() {
+            messages.push(message);
+        }
+        let summary: Vec<String> = messages.iter().map(|message| match message.get("method").as_str() {
+            Some(method) => format!("{method} {}", message.get("params").get("diagnostics").as_array().unwrap().len()),
+            None => format!("{} {}", message.get("id"), message.get("error") == &Json::Null)
+        }).collect();
+        assert_eq!(summary, [
+            "1 true", "textDocument/publishDiagnostics 0", "2 true", "3 true", "4 true", "8 true", "5 true", "6 true",
+            "textDocument/publishDiagnostics 1", "7 false"
+        ]);
+
+        let capabilities: &Json = messages[0].get("result").get("capabilities");
+        assert_eq!((capabilities.get("definitionProvider"), capabilities.get("referencesProvider")), (&Json::Bool(true), &Json::Bool(true)));
+
+        // line 1 starts with 4 spaces, "😀" takes up 4 UTF-16 code units
+        let tokens: Vec<u64> = messages[2].get("result").get("data").as_array().unwrap().iter().map(|number| number.as_u64().unwrap()).collect();
+        assert_eq!(tokens[..30], [0, 0, 12, 1, 0, 0, 13, 1, 3, 0, 0, 3, 1, 3, 0, 0, 3, 1, 4, 0, 0, 2, 1, 3, 0, 0, 2, 2, 4, 0]);
+        assert_eq!(tokens[30..40], [1, 4, 4, 2, 0, 0, 5, 2, 4, 0]);
+        assert_eq!(tokens.len(), 75);
+
+        assert_eq!(messages[3].get("result").to_string(),
+            r#"{"contents":{"kind":"markdown","value":"Decimal literal `Decimal(0.5)`"},"range":{"start":{"line":1,"character":34},"end":{"line":1,"character":38}}}"#);
+
+        // "😀" takes up 2 UTF-16 code units, so the second call starts at character 21
+        let ranges = |message: &Json| -> Vec<String> {
+            message.get("result").as_array().unwrap().iter().map(|location| format!("{} {}", location.get("uri"), location.get("range").get("start"))).collect()
+        };
+        assert_eq!(ranges(&messages[4]), [
+            format!("{:?} {}", uri, r#"{"line":0,"character":0}"#), format!("{:?} {}", uri, r#"{"line":1,"character":21}"#)
+        ]);
+
+        // natives are defined in Rust, the definition is their entry in NATIVES
+        let line: usize = include_str!("native.rs").lines().position(|line| line.contains("native(\"wrapping_add\"")).unwrap();
+        let definition: &Json = messages[7].get("result");
+        assert_eq!(definition.get("uri").as_str(), Some(concat!("file://", env!("CARGO_MANIFEST_DIR"), "/src/native.rs")));
+        assert_eq!(definition.get("range").to_string(), format!(r#"{{"start":{{"line":{line},"character":11}},"end":{{"line":{line},"character":25}}}}"#));
+        assert_eq!(ranges(&messages[5]).len(), 3);
+        assert_eq!(messages[5].get("result").as_array().unwrap()[0], *definition);
+
+        let main: &Json = &messages[6].get("result").as_array().unwrap()[0];
+        assert_eq!((main.get("name").as_str(), main.get("range").get("end").to_string()), (Some("main"), r#"{"line":1,"character":50}"#.to_string()));
+        let calls: Vec<Option<&str>> = main.get("children").as_array().unwrap().iter().map(|call| call.get("name").as_str()).collect();
+        assert_eq!(calls, [Some("wrapping_add"), Some("wrapping_add")]);
+
+        assert_eq!(messages[8].get("params").get("diagnostics").to_string(), concat!(
+            r#"[{"range":{"start":{"line":0,"character":0},"end":{"line":0,"character":12}},"#,
+            r#""severity":1,"source":"eos","message":"wrapping_add expects 2 arguments, but got 1"}]"#
+        ));
+    }
+
+    pub fn lsp_malformed_messages(){
+        let message = |id: Option<u32>, method: &str, params: Json| match id {
+            Some(id) => Json::object([("jsonrpc", Json::from("2.0")), ("id", Json::from(id)), ("method", Json::from(method)), ("params", params)]),
+            None => Json::object([("jsonrpc", Json::from("2.0")), ("method", Json::from(method)), ("params", params)])
+        };
+        let document = || Json::object([("uri", Json::from("file:///test.eos"))]);
+        let opened: Json = Json::object([("uri", Json::from("file:///test.eos")), ("text", Json::from("1 + é"))]);
+
+        // a body that isn't JSON and a message without a Content-Length, the server answers both and goes on
+        let (requests, mut client) = std::io::pipe().unwrap();
+        let (mut answers, server) = std::io::pipe().unwrap();
+        client.write_all(b"Content-Length: 4\r\n\r\n{id:").unwrap();
+        write_message(&mut client, &message(Some(1), "initialize", Json::object([]))).unwrap();
+        client.write_all(b"Content-Type: application/json\r\n\r\n{\"id\":2}").unwrap();
+        for message in [
+            message(None, "textDocument/didOpen", Json::object([("textDocument", opened)])),
+            message(Some(3), "textDocument/semanticTokens/full", Json::object([("textDocument", document())])),
+            message(None, "exit", Json::Null)
+        ] {
+            write_message(&mut client, &message).unwrap();
+        }
+        drop(client);
+        lsp::serve(std::io::BufReader::new(requests), server).unwrap();
+
+        let mut answers: std::io::BufReader<_> = std::io::BufReader::new(&mut answers);
+        let mut messages: Vec<Json> = Vec::new();
+        while let Some(message) = read_message(&mut answers).unwrap() {
+            messages.push(message);
+        }
+        let summary: Vec<String> = messages.iter().map(|message| match message.get("method").as_str() {
+            Some(method) => format!("{method} {}", message.get("params").get("diagnostics").as_array().unwrap().len()),
+            None => format!("{} {}", message.get("id"), message.get("error").get("code"))
+        }).collect();
+        assert_eq!(summary, ["null -32700", "1 null", "null -32700", "textDocument/publishDiagnostics 2", "3 null"]);
+        assert_eq!(messages[2].get("error").get("message").as_str(), Some("Malformed message: A message without a Content-Length"));
+
+        // 'é' is one UTF-16 code unit, the unexpected character isn't a semantic token
+        assert_eq!(messages[3].get("params").get("diagnostics").as_array().unwrap()[0].get("range").to_string(),
+            r#"{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}"#);
+        assert_eq!(messages[4].get("result").get("data").to_string(), "[0,0,1,3,0,0,2,1,4,0]");
+    }
+
+    pub fn profiler_counts(){
+        let path: &str = "src/tests/testing_debugger.eos";
+        let (_, chunk): (String, Chunk) = compiled_test_programs().into_iter().find(|(program, _)| program == path).unwrap();
+
+        // two runs add up
+        let mut profile: Profile = Profile::new();
+        for _ in 0..2 {
+            let mut vm: VM = VM::new(&chunk);
+            assert_eq!(profile.run(&mut vm), Some(()));
+            assert_eq!(vm.get_stack(), &vec![Value::Integer(27)]);
+        }
+
+        assert_eq!(profile.instructions(), 20);
+        assert_eq!(profile.opcode_count(OpCode::Constant), 10);
+        assert_eq!(profile.opcode_count(OpCode::Divide), 0);
+        assert_eq!((profile.line_count(1), profile.line_count(2), profile.line_count(3), profile.line_count(4)), (8, 8, 2, 2));
+        assert_eq!(profile.function("main").map(|function| function.instructions), Some(20));
+
+        assert_eq!(profile.folded(), concat!(
+            "main;line 1;Constant 4\n", "main;line 1;Add 2\n", "main;line 1;CallNative 2\n",
+            "main;line 2;Constant 4\n", "main;line 2;Multiply 2\n", "main;line 2;CallNative 2\n",
+            "main;line 3;Constant 2\n", "main;line 4;Return 2\n"
+        ));
+
+        // the time of a function differs from run to run
+        let report: String = profile.report();
+        let (functions, rest): (&str, &str) = report.split_once("\n\n").unwrap();
+        assert!(functions.starts_with("Function             Instructions         Time\nmain                           20 "));
+        assert_eq!(rest, concat!(
+            "Opcode               Instructions  Percent\n",
+            "Constant                       10    50.0%\n",
+            "CallNative                      4    20.0%\n",
+            "Return                          2    10.0%\n",
+            "Add                             2    10.0%\n",
+            "Multiply                        2    10.0%\n",
+            "\n",
+            "Line                 Instructions  Percent\n",
+            "1                               8    40.0%\n",
+            "2                               8    40.0%\n",
+            "3                               2    10.0%\n",
+            "4                               2    10.0%\n"
+        ));
+
+        // a failing run counts the instructions up to the error
+        let failing: Chunk = assemble("Constant | Integer(1)\nConstant | Integer(0)\nDivide\nReturn").unwrap();
+        let mut profile: Profile = Profile::new();
+        assert_eq!(profile.run(&mut VM::new(&failing)), None);
+        assert_eq!(profile.instructions(), 3);
+    }
+
+    pub fn coverage_lines_and_branches(){
+        let path: &str = "coverage.easm";
+        let chunk: Chunk = assemble(concat!(
+            ".line 1\n", "Constant | Integer(0)\n", "Constant | Integer(0)\n", "CallNative | wrapping_add\n", "Constant | Integer(1)\n",
+            "EqualJumpIfFalse -> end\n", "Pop\n",
+            ".line 2\n", "Constant | Integer(1)\n", "Constant | Integer(2)\n", "CallNative | wrapping_add\n", "Constant | Integer(2)\n", "Greater\n",
+            "end:\n", ".line 3\n", "Return\n"
+        )).unwrap();
+
+        // 0 == 1 is false, so the jump on line 1 skips line 2
+        let mut coverage: Coverage = Coverage::new();
+        let mut vm: VM = VM::new(&chunk);
+        assert_eq!(coverage.run(path, &mut vm), Some(()));
+        assert_eq!(vm.get_stack(), &vec![Value::Boolean(false)]);
+
+        let lcov: String = coverage.lcov();
+        assert_eq!(lcov, concat!(
+            "TN:\n", "SF:coverage.easm\n",
+            "BRDA:1,72,0,1\n", "BRDA:1,72,1,0\n", "BRF:2\n", "BRH:1\n",
+            "DA:1,1\n", "DA:2,0\n", "DA:3,1\n", "LF:3\n", "LH:2\n", "end_of_record\n"
+        ));
+        assert_eq!(Coverage::parse_lcov(&lcov), Ok(coverage.clone()));
+
+        // a second run and a merged tracefile add up
+        coverage.run(path, &mut VM::new(&chunk));
+        let mut merged: Coverage = Coverage::parse_lcov(&lcov).unwrap();
+        merged.merge(&Coverage::parse_lcov(&lcov).unwrap());
+        assert_eq!(merged, coverage);
+        let file = coverage.file(path).unwrap();
+        assert_eq!(file.lines.values().copied().collect::<Vec<u64>>(), vec![2, 0, 2]);
+        assert_eq!(file.branches.values().copied().collect::<Vec<u64>>(), vec![2, 0]);
+
+        assert_eq!(coverage.summary(), concat!(
+            "File                                        Lines         Branches  Missed lines\n",
+            "coverage.easm                         66.7% (2/3)      50.0% (1/2)  2\n",
+            "Total                                 66.7% (2/3)      50.0% (1/2)\n"
+        ));
+
+        // a branch point that never ran has no counts
+        let skipped: Chunk = assemble("Constant | Boolean(true)\nJump -> end\nJumpIfFalse -> end\nend:\nReturn").unwrap();
+        let mut coverage: Coverage = Coverage::new();
+        assert_eq!(coverage.run("skipped", &mut VM::new(&skipped)), Some(()));
+        assert!(coverage.lcov().contains("BRDA:1,30,0,-\nBRDA:1,30,1,-\nBRF:2\nBRH:0\n"));
+
+        assert_eq!(Coverage::parse_lcov("SF:a.eos\nDA:1,x\nend_of_record\n"), Err("Invalid record 'DA:1,x' on line 2".to_string()));
+        assert_eq!(Coverage::parse_lcov("SF:a.eos\nDA:1,1\n"), Err("The record of a.eos has no end_of_record".to_string()));
+    }
+
+    pub fn parser_syntax_tree(){
+        let code: String = read_to_string("src/tests/testing_syntax_tree.eos").unwrap();
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let mut program: Program = Parser::new(lexer.lexing(), SharedData::new(&code)).parse().unwrap();
+        resolve(&mut program).unwrap();
+
+        let expected: &str = "(NotEqual (Equal (Multiply (group (Add 1 2)) (Power 3 (Power 2 1))) (Not false)) (call wrapping_neg (Negate 4)))";
+        assert_eq!(to_sexpr(&program.expression, &code), expected);
+
+        // the spans point back into the source
+        assert_eq!(program.expression.span.range(), 0..code.trim_end().len());
+        assert_eq!(program.end.line, 3);
+
+        let ExprKind::Binary { operator_span, right, .. } = &program.expression.kind else {
+            panic!("Expected != at the root");
+        };
+        assert_eq!((&code[operator_span.range()], operator_span.line), ("!=", 2));
+
+        let ExprKind::Call { callee, native, .. } = &right.kind else {
+            panic!("Expected a call on the right of !=");
+        };
+        assert_eq!(&code[callee.range()], "wrapping_neg");
+        assert_eq!(*native, find_native("wrapping_neg"));
+        assert_eq!(&code[right.span.range()], "wrapping_neg(-4)");
+    }
+
+    pub fn resolver_natives(){
+        let code: String = "round(1) + nope(2) * wrapping_neg(1, 2, 3)\n".to_string();
+
+        let mut lexer: Lexer = Lexer::new(&code);
+        let mut program: Program = Parser::new(lexer.lexing(), SharedData::new(&code)).parse().unwrap();
+        let errors: Vec<CompileError> = resolve(&mut program).unwrap_err();
+
+        let messages: Vec<String> = errors.iter().map(|error| error.describe(&code)).collect();
+        assert_eq!(messages, vec![
+            "At line 1: 'round' -> round expects 2 arguments, but got 1",
+            "At line 1: 'nope' -> Undefined function 'nope'",
+            "At line 1: 'wrapping_neg' -> wrapping_neg expects 1 arguments, but got 3"
+        ]);
+
+        // parse errors come with the token they were found at
+        let code: String = "1 ++ 2\n".to_string();
+        let mut lexer: Lexer = Lexer::new(&code);
+        let errors: Vec<CompileError> = Parser::new(lexer.lexing(), SharedData::new(&code)).parse().unwrap_err();
+        assert_eq!(errors[0].describe(&code), "At line 1: '+' -> Expected an expression");
+    }
+
+    pub fn parser_non_ascii(){
+        // a character outside of ASCII is reported whole instead of splitting it
+        for (code, expected) in [
+            ("1 + é\n", "At line 1: 'é' -> Unexpected character"),
+            ("2 * 😀 + 1\n", "At line 1: '😀' -> Unexpected character"),
+            ("r#é\n", "At line 1: 'r#é' -> Unterminated string")
+        ] {
+            let code: String = code.to_string();
+            let mut lexer: Lexer = Lexer::new(&code);
+            let errors: Vec<CompileError> = Parser::new(lexer.lexing(), SharedData::new(&code)).parse().unwrap_err();
+            assert_eq!(errors[0].describe(&code), expected);
+        }
+
+        // inside of a string they are just text
+        let code: String = "\"é\" + \"😀\"\n".to_string();
+        assert!(run_folded(&code, false).0.unwrap().contains("\"é😀\""));
+    }
+
     pub fn lexer_integer_float(){
         let code: String = read_to_string("src/tests/testing_int_float.eos").unwrap();
 
@@ -164,6 +1365,24 @@ mod testing {
         assert_eq!(lexer.next_token().token_type, TokenType::Error);
     }
 
+    pub fn lexer_number_literals(){
+        let code: String = read_to_string("src/tests/testing_number_lexing.eos").unwrap();
+
+        let mut lexer: Lexer = Lexer::new(&code);
+
+        let expected: [TokenType; 18] = [
+            TokenType::Integer, TokenType::Plus, TokenType::Integer,
+            TokenType::LeftParent, TokenType::Integer, TokenType::RightParent,
+            TokenType::Float, TokenType::Float, TokenType::Integer, TokenType::Float, TokenType::Dot,
+            TokenType::Error, TokenType::Error, TokenType::Error, TokenType::Error, TokenType::Error,
+            TokenType::EndOfFile, TokenType::EndOfFile
+        ];
+
+        for token_type in expected {
+            assert_eq!(lexer.next_token().token_type, token_type);
+        }
+    }
+
     pub fn lexer_keyword_identifier(){
         let code: String = read_to_string("src/tests/testing_keyword_identifier.eos").unwrap();
 
@@ -176,6 +1395,14 @@ mod testing {
         assert_eq!(lexer.next_token().token_type, TokenType::This);
         assert_eq!(lexer.next_token().token_type, TokenType::True);
         assert_eq!(lexer.next_token().token_type, TokenType::Identifier);
+
+        // keywords, identifiers and operators at the very end of the source
+        for (code, expected) in [("true", TokenType::True), ("Null", TokenType::Null), ("abs", TokenType::Identifier), ("1 >=", TokenType::GreaterEqual)] {
+            let tokens: Vec<TokenType> = Lexer::new(code).lexing().iter().map(|token| token.token_type).collect();
+            assert_eq!(tokens[tokens.len() - 2..], [expected, TokenType::EndOfFile], "{code}");
+        }
+        assert_eq!(run_folded(&"true".to_string(), false).0, run_folded(&"true\n".to_string(), false).0);
+        assert_eq!(run_folded(&"-false".to_string(), false).0, Err((1, "Boolean(false) CANNOT BE NEGATED".to_string())));
     }
 
     pub fn lexer_string_parsing(){
@@ -188,6 +1415,47 @@ mod testing {
         assert_eq!(lexer.next_token().token_type, TokenType::Error);
     }
 
+    pub fn lexer_string_interpolation(){
+        let code: String = read_to_string("src/tests/testing_string_interpolation.eos").unwrap();
+
+        let mut lexer: Lexer = Lexer::new(&code);
+
+        assert_eq!(lexer.next_token().token_type, TokenType::TextInterpolation);
+        assert_eq!(lexer.next_token().token_type, TokenType::Integer);
+        assert_eq!(lexer.next_token().token_type, TokenType::Plus);
+        assert_eq!(lexer.next_token().token_type, TokenType::Integer);
+        assert_eq!(lexer.next_token().token_type, TokenType::TextInterpolation);
+        assert_eq!(lexer.next_token().token_type, TokenType::TextInterpolation);
+        assert_eq!(lexer.next_token().token_type, TokenType::Bang);
+        assert_eq!(lexer.next_token().token_type, TokenType::False);
+        assert_eq!(lexer.next_token().token_type, TokenType::Text);
+        assert_eq!(lexer.next_token().token_type, TokenType::Text);
+        assert_eq!(lexer.next_token().token_type, TokenType::EndOfFile);
+    }
+
+    pub fn lexer_multiline_raw_string(){
+        let code: String = read_to_string("src/tests/testing_multiline_raw_string.eos").unwrap();
+
+        let mut lexer: Lexer = Lexer::new(&code);
+
+        let expected: [(TokenType, u32); 10] = [
+            (TokenType::MultilineText, 1),
+            (TokenType::Plus, 4),
+            (TokenType::RawText, 4),
+            (TokenType::Plus, 4),
+            (TokenType::RawText, 4),
+            (TokenType::Plus, 4),
+            (TokenType::Text, 4),
+            (TokenType::Plus, 5),
+            (TokenType::Text, 5),
+            (TokenType::EndOfFile, 6)
+        ];
+
+        for (token_type, line) in expected {
+            let token: Token = lexer.next_token();
+            assert_eq!((token.token_type, token.line), (token_type, line));
+        }
+    }
 
     pub fn lexer_one_lookahed_token(){
         let code: String = read_to_string("src/tests/testing_one_lookahead.eos").unwrap();
@@ -211,6 +1479,20 @@ mod testing {
         assert_eq!(lexer.next_token().token_type, TokenType::EndOfFile);
     }
 
+    pub fn lexer_comment_trivia(){
+        let code: &str = "# one\n# two\n1 + # three\n2";
+        let types = |tokens: Vec<Token>| tokens.iter().map(|token| token.token_type).collect::<Vec<TokenType>>();
+
+        // several comments in a row are skipped
+        assert_eq!(types(Lexer::new(code).lexing()), [TokenType::Integer, TokenType::Plus, TokenType::Integer, TokenType::EndOfFile]);
+
+        let tokens: Vec<Token> = Lexer::new(code).with_comments().lexing();
+        assert_eq!(types(tokens.clone()), [
+            TokenType::Comment, TokenType::Comment, TokenType::Integer, TokenType::Plus, TokenType::Comment, TokenType::Integer, TokenType::EndOfFile
+        ]);
+        assert_eq!((&code[tokens[4].get_range()], tokens[4].line, tokens[5].line), ("# three", 3, 4));
+    }
+
     pub fn vm_binary_operations(){
         let mut chunk: Chunk = Chunk::new(1);
 
@@ -223,7 +1505,7 @@ mod testing {
         let mut vm: VM = VM::new(&chunk);
         vm.run().unwrap();
 
-        assert_eq!(*vm.get_stack().get(0).unwrap(), Value::Integer(180 * 4));
+        assert_eq!(*vm.get_stack().first().unwrap(), Value::Integer(180 * 4));
     }
 
     pub fn vm_negate(){
@@ -231,12 +1513,454 @@ mod testing {
 
         chunk.add_value(Value::Integer(90), 0);
         chunk.add_opcode(OpCode::Negate, 0);
+        chunk.add_value(Value::Float(0.5), 0);
+        chunk.add_opcode(OpCode::Negate, 0);
+
+        let mut vm: VM = VM::new(&chunk);
+        vm.run().unwrap();
+
+        assert_eq!(vm.get_stack(), [Value::Integer(-90), Value::Float(-0.5)]);
+
+        let mut chunk: Chunk = Chunk::new(1);
         chunk.add_value(Value::Boolean(false), 0);
         chunk.add_opcode(OpCode::Negate, 0);
 
         let mut vm: VM = VM::new(&chunk);
+        assert!(vm.run().is_none(), "- has to reject booleans");
+        assert_eq!(vm.get_error(), Some("Boolean(false) CANNOT BE NEGATED"));
+    }
+
+    pub fn vm_invalid_bytecode(){
+        // the jump lands on the line of Return, whose bytes are no opcode
+        let mut chunk: Chunk = Chunk::new(1);
+        chunk.add_opcode_with_operand(OpCode::Jump, 1, 1);
+        chunk.add_opcode(OpCode::Return, u32::from_ne_bytes([200; 4]));
+
+        let mut vm: VM = VM::new(&chunk);
+        assert!(vm.run().is_none());
+        assert_eq!(vm.get_error(), Some("Unknown opcode 200"));
+        assert_eq!(decode_opcode(chunk.code(), INSTRUCTION_SIZE + OPERAND_SIZE + 1), Err("Unknown opcode 200".to_string()));
+
+        // every instruction that pops more than was pushed fails instead of panicking
+        for opcode in [OpCode::Add, OpCode::Less, OpCode::Negate, OpCode::Print, OpCode::Pop] {
+            let mut chunk: Chunk = Chunk::new(1);
+            chunk.add_opcode(opcode, 3);
+
+            let mut vm: VM = VM::new(&chunk);
+            assert!(vm.run().is_none(), "{opcode:?}");
+            assert_eq!((vm.get_error(), vm.get_line()), (Some("EXPECTED A NON-EMPTY STACK"), 3), "{opcode:?}");
+        }
+    }
+
+    pub fn vm_not(){
+        let mut chunk: Chunk = Chunk::new(1);
+        let values: [Value; 5] = [Value::Boolean(false), Value::Boolean(true), Value::Null, Value::Integer(0), Value::Object(DynType::from(String::new()))];
+
+        for value in &values {
+            chunk.add_value(value.clone(), 0);
+            chunk.add_opcode(OpCode::Not, 0);
+        }
+        chunk.add_value(Value::Integer(5), 0);
+        chunk.add_opcode(OpCode::Not, 0);
+        chunk.add_opcode(OpCode::Not, 0);
+
+        let mut vm: VM = VM::new(&chunk);
+        vm.run().unwrap();
+
+        let expected: [bool; 6] = [true, false, true, false, false, true];
+        assert_eq!(vm.get_stack(), expected.map(Value::Boolean));
+        assert!(values.iter().zip(expected).all(|(value, falsey)| value.is_falsey() == falsey));
+    }
+
+    fn opcodes(chunk: &Chunk) -> Vec<OpCode> {
+        let mut opcodes: Vec<OpCode> = Vec::new();
+        let mut index: usize = 0;
+
+        while index < chunk.len() {
+            let opcode: OpCode = decode_opcode(chunk.code(), index).unwrap();
+            opcodes.push(opcode);
+            index += instruction_size(opcode);
+        }
+        opcodes
+    }
+
+    // (left + 2 - 3 < 1 or fallback) and (5 != 5 or 7 - 1), the way short-circuit operators would compile
+    fn superinstruction_chunk(left: i64, fallback: Value) -> Chunk {
+        assemble(&format!(concat!(
+            ".line 1\n",
+            "Constant | Integer({left})\n", "Constant | Integer(2)\n", "Add\n", "Constant | Integer(3)\n", "Subtract\n",
+            "Constant | Integer(1)\n", "Less\n", "JumpIfFalse -> first_else\n", "Jump -> first_end\n",
+            "first_else:\n", "Pop\n", "Constant | {fallback}\n",
+            "first_end:\n", ".line 2\n", "JumpIfFalse -> end\n", "Pop\n",
+            "Constant | Integer(5)\n", "Constant | Integer(5)\n", "Equal\n", "Not\n", "JumpIfFalse -> second_else\n", "Jump -> end\n",
+            "second_else:\n", "Pop\n", "Constant | Integer(7)\n", "Constant | Integer(1)\n", "Subtract\n",
+            "end:\n", "Return"
+        ), left = left, fallback = format_constant(&fallback))).unwrap()
+    }
+
+    pub fn optimizer_superinstructions(){
+        use OpCode::*;
+
+        // every case takes a different path through the jumps
+        let cases: [(i64, Value, Value); 3] = [
+            (1, Value::Null, Value::Integer(6)),
+            (5, Value::Integer(10), Value::Integer(6)),
+            (5, Value::Boolean(false), Value::Boolean(false))
+        ];
+        for (left, fallback, expected) in cases {
+            let chunk: Chunk = superinstruction_chunk(left, fallback);
+            let optimized: Chunk = optimize(&chunk);
+
+            assert_eq!(opcodes(&optimized), vec![
+                Constant, AddConstant, SubtractConstant, Constant, LessJumpIfFalse, Jump, Pop, Constant,
+                JumpIfFalse, Pop, Constant, Constant, NotEqual, JumpIfFalse, Jump, Pop, Constant, SubtractConstant, Return
+            ]);
+
+            let mut vm: VM = VM::new(&chunk);
+            vm.run().unwrap();
+            let mut optimized_vm: VM = VM::new(&optimized);
+            optimized_vm.run().unwrap();
+
+            assert_eq!(vm.get_stack(), std::slice::from_ref(&expected));
+            assert_eq!(optimized_vm.get_stack(), &[expected]);
+        }
+
+        // the comparison and the jump become one instruction
+        let chunk: Chunk = assemble(concat!(
+            "Constant | Integer(2)\n", "Constant | Integer(1)\n", "Greater\n", "JumpIfFalse -> end\n", "Pop\n",
+            "Constant | Integer(1)\n", "Constant | Integer(1)\n", "Equal\n", "JumpIfFalse -> end\n",
+            "end:\n", "Return"
+        )).unwrap();
+
+        let optimized: Chunk = optimize(&chunk);
+        assert_eq!(opcodes(&optimized), vec![Constant, Constant, GreaterJumpIfFalse, Pop, Constant, Constant, EqualJumpIfFalse, Return]);
+
+        let mut vm: VM = VM::new(&optimized);
         vm.run().unwrap();
+        assert_eq!(vm.get_stack(), &[Value::Boolean(true)]);
+    }
+
+    pub fn optimizer_jump_targets(){
+        use OpCode::*;
+
+        // the jump lands on Not, so Equal and Not must stay apart
+        let chunk: Chunk = assemble(concat!(
+            "Constant | Boolean(false)\n", "Jump -> target\n", "Constant | Integer(1)\n", "Constant | Integer(2)\n", "Equal\n",
+            "target:\n", "Not\n", ".line 2\n", "Constant | Integer(3)\n", ".line 3\n", "Add"
+        )).unwrap();
+
+        let optimized: Chunk = optimize(&chunk);
+        assert_eq!(opcodes(&optimized), vec![Constant, Jump, Constant, Constant, Equal, Not, AddConstant]);
+
+        // the fused instruction keeps the line of Add, true + 3 fails at runtime
+        let fused: usize = optimized.len() - instruction_size(AddConstant);
+        assert_eq!(optimized.read_opcode(fused), Some((AddConstant, 3)));
+
+        let mut vm: VM = VM::new(&optimized);
+        assert!(vm.run().is_none());
+        assert_eq!((vm.get_error(), vm.get_line()), (Some("Boolean(true) and Integer(3) can not be added"), 3));
+        assert_eq!(vm.get_stack(), &[Value::Integer(0)]);
+    }
+
+    pub fn big_int_arithmetic(){
+        // compares against i128, the operands are chosen so that every result fits into it
+        let samples: [i128; 10] = [0, 1, -1, 7, -13, i64::MAX as i128, i64::MIN as i128, 1 << 90, -(1 << 77) + 12345, 98765432109876543210];
+        let big = |value: i128| -> ObjBigInt {
+            ObjBigInt::parse(&value.unsigned_abs().to_string(), 10).map(|big| if value < 0 { big.neg() } else { big }).unwrap()
+        };
+
+        for a in samples {
+            for b in samples {
+                assert_eq!(big(a).add(&big(b)), big(a + b));
+                assert_eq!(big(a).sub(&big(b)), big(a - b));
+                assert_eq!(big(a).cmp(&big(b)), a.cmp(&b));
+                if let Some(product) = a.checked_mul(b) {
+                    assert_eq!(big(a).mul(&big(b)), big(product));
+                }
+                if b != 0 {
+                    let (quotient, remainder) = big(a).div_rem(&big(b)).unwrap();
+                    assert_eq!((quotient, remainder), (big(a / b), big(a % b)));
+                }
+                assert_eq!(big(a).bitand(&big(b)), big(a & b));
+                assert_eq!(big(a).bitor(&big(b)), big(a | b));
+                assert_eq!(big(a).bitxor(&big(b)), big(a ^ b));
+            }
+            assert_eq!(big(a).not(), big(!a));
+            assert_eq!(big(a).to_string(), a.to_string());
+
+            for amount in [0, 1, 31, 32, 33] {
+                assert_eq!(big(a).shl(amount), big(a << amount));
+                assert_eq!(big(a).shr(amount), big(a >> amount));
+            }
+            assert_eq!(big(a).shr(200), big(a >> 127));
+        }
+    }
+
+    pub fn exact_number_arithmetic(){
+        let decimal = |literal: &str| -> ObjDecimal { ObjDecimal::parse(literal).unwrap() };
+        let rational = |numerator: i64, denominator: i64| -> ObjRational {
+            ObjRational::new(ObjBigInt::from(numerator), ObjBigInt::from(denominator)).unwrap()
+        };
+
+        assert_eq!(decimal("1.5e-3").to_string(), "0.0015");
+        assert_eq!(decimal("12.50").mul(&decimal("2")).unwrap().to_string(), "25.00");
+        assert_eq!(decimal("2").div(&decimal("3")).unwrap().to_string(), "0.666666666666666667");
+        assert_eq!(decimal("-0.125").round(2).unwrap().to_string(), "-0.12");
+        assert_eq!(decimal("0.10"), decimal("0.1"));
+        assert!(decimal("170141183460469231731687303715884105727").add(&decimal("1")).is_err());
+
+        // i128::MIN / -1 and scales that can't be rounded are errors instead of panics
+        let overflow: String = "Decimal overflow: the result has more than 38 significant digits".to_string();
+        assert_eq!(ObjDecimal::new(i128::MIN, 0).div(&decimal("-1")), Err(overflow.clone()));
+        assert_eq!(ObjDecimal::parse("0.1e-2147483648"), Err("Decimal scale out of range: the number has 2147483649 decimal places".to_string()));
+        assert_eq!(ObjDecimal::parse("1e-2000000000"), Err("Decimal scale out of range: the number has 2000000000 decimal places".to_string()));
+        assert_eq!(decimal("1e-66").to_string(), "0.0000000000000000000000000000");
+
+        let code: String = "(-17014118346.0469231731687303715884105727d - 0.0000000000000000000000000001d) / -1".to_string();
+        assert_eq!(run_folded(&code, true).0, Err((1, overflow.clone())));
+        assert_eq!(run_folded(&code, false).0, Err((1, overflow)));
+
+        assert_eq!(rational(6, -4).to_string(), "-3/2");
+        assert_eq!(rational(-7, 2).floor_rem(&rational(2, 1)).unwrap(), rational(1, 2));
+        assert_eq!(rational(1, 8).to_decimal(2).unwrap().to_string(), "0.12");
+        assert_eq!(rational(3, 8).to_decimal(2).unwrap().to_string(), "0.38");
+        assert_eq!(ObjRational::from_f64(0.1).unwrap(), rational(3602879701896397, 36028797018963968));
+        assert_eq!(ObjRational::from_f64(-2.5e20).unwrap(), ObjRational::from_integer(ObjBigInt::from(-250000000000000000000i128)));
+        assert!(ObjRational::new(ObjBigInt::from(1i64), ObjBigInt::from(0i64)).is_err());
+    }
+
+    pub fn comparison_semantics(){
+        // the edge cases against each other, then random pairs that are shrunk to a minimal failing pair
+        let mut edges: Vec<Sample> = Vec::new();
+        edges.extend([0, 1, -1, 7, i64::MAX, i64::MIN].map(Sample::Integer));
+        edges.extend([i64::MAX as i128 + 1, i64::MIN as i128 - 1].map(Sample::Big));
+        edges.extend([(5, 1), (-25, 1), (70, 1), (0, 2)].map(|(mantissa, scale)| Sample::Decimal(mantissa, scale)));
+        edges.extend([(1, 3), (-7, 2), (1, 2)].map(|(numerator, denominator)| Sample::Rational(numerator, denominator)));
+        edges.extend([0.0, -0.0, 0.5, -2.5, 7.0, i64::MAX as f64, 1e300, f64::INFINITY, f64::NEG_INFINITY, f64::NAN].map(Sample::Float));
+        edges.extend(["", "a", "abc", "abd", "\u{e9}"].map(|text| Sample::Text(text.to_string())));
+        edges.extend([Sample::Boolean(true), Sample::Boolean(false), Sample::Null]);
+
+        for a in &edges {
+            for b in &edges {
+                if let Err(message) = compare_like_rust(a, b) {
+                    panic!("{message}");
+                }
+            }
+        }
+
+        let mut random: Random = Random(0x2545f4914f6cdd1d);
+        for _ in 0..5000 {
+            let (a, b): (Sample, Sample) = (Sample::generate(&mut random), Sample::generate(&mut random));
+
+            if compare_like_rust(&a, &b).is_err() {
+                let (a, b) = shrink(a, b, |a, b| compare_like_rust(a, b).is_err());
+                panic!("{}", compare_like_rust(&a, &b).unwrap_err());
+            }
+        }
+    }
 
-        assert_eq!(vm.get_stack(), [Value::Integer(-90), Value::Boolean(true)]);
+    // A xorshift generator, the random tests are reproducible because they always start from the same seed
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn one_in(&mut self, n: u64) -> bool {
+            self.next().is_multiple_of(n)
+        }
+
+        // low..high
+        fn range(&mut self, low: i128, high: i128) -> i128 {
+            low + (self.next() as u128 % (high - low) as u128) as i128
+        }
+    }
+
+    // A value that can be generated, shrunk and compared by Rust through its model
+    #[derive(Debug, Clone)]
+    enum Sample { Integer(i64), Big(i128), Decimal(i128, u32), Rational(i128, i128), Float(f64), Text(String), Boolean(bool), Null }
+
+    // Numbers are exact fractions or floats
+    #[derive(Clone, Copy)]
+    enum Model<'a> { Exact(i128, i128), Float(f64), Text(&'a str), Boolean(bool), Null }
+
+    impl Sample {
+        // Small numbers are likely so that equal values show up, floats are dyadic so the model compares them exactly
+        fn generate(random: &mut Random) -> Self {
+            match random.range(0, 8) {
+                0 if random.one_in(4) => Sample::Integer(random.next() as i64),
+                0 => Sample::Integer(random.range(-100, 100) as i64),
+                1 if random.one_in(2) => Sample::Big(i64::MAX as i128 + random.range(1, 1 << 40)),
+                1 => Sample::Big(i64::MIN as i128 - random.range(1, 1 << 40)),
+                2 => Sample::Decimal(random.range(-1_000_000_000_000, 1_000_000_000_000), random.range(0, 5) as u32),
+                3 => Sample::Rational(random.range(-1000, 1000), random.range(1, 1000) * if random.one_in(2) { 1 } else { -1 }),
+                4 if random.one_in(4) => Sample::Float([0.0, -0.0, f64::INFINITY, f64::NEG_INFINITY, f64::NAN][random.range(0, 5) as usize]),
+                4 => Sample::Float(random.range(-(1 << 20), 1 << 20) as f64 / (1 << random.range(0, 11)) as f64),
+                5 => Sample::Text((0..random.range(0, 4)).map(|_| ['a', 'b', '\u{e9}'][random.range(0, 3) as usize]).collect()),
+                6 => Sample::Boolean(random.one_in(2)),
+                _ => Sample::Null
+            }
+        }
+
+        // Simpler samples of the same kind
+        fn shrink(&self) -> Vec<Sample> {
+            let candidates: Vec<Sample> = match self {
+                Sample::Integer(a) => [0, a / 2, a - a.signum()].map(Sample::Integer).to_vec(),
+                Sample::Big(a) => [a / 2, a - a.signum()].into_iter().filter(|a| i64::try_from(*a).is_err()).map(Sample::Big).collect(),
+                Sample::Decimal(mantissa, scale) => [(0, 0), (mantissa / 2, *scale), (mantissa - mantissa.signum(), *scale), (*mantissa, scale.saturating_sub(1))]
+                    .map(|(mantissa, scale)| Sample::Decimal(mantissa, scale))
+                    .to_vec(),
+                Sample::Rational(numerator, denominator) => [(0, 1), (numerator / 2, *denominator), (numerator - numerator.signum(), *denominator), (*numerator, denominator / 2)].into_iter()
+                    .filter(|(_, denominator)| *denominator != 0)
+                    .map(|(numerator, denominator)| Sample::Rational(numerator, denominator))
+                    .collect(),
+                Sample::Float(a) => [0.0, a.trunc(), a / 2.0].map(Sample::Float).to_vec(),
+                Sample::Text(text) => (0..text.chars().count()).map(|skip| Sample::Text(text.chars().enumerate().filter(|(index, _)| *index != skip).map(|(_, c)| c).collect())).collect(),
+                Sample::Boolean(true) => vec![Sample::Boolean(false)],
+                Sample::Boolean(false) | Sample::Null => Vec::new()
+            };
+            // the debug output tells -0.0 from 0.0 and treats NaN like any other float
+            candidates.into_iter().filter(|candidate| format!("{candidate:?}") != format!("{self:?}")).collect()
+        }
+
+        fn value(&self) -> Value {
+            match self {
+                Sample::Integer(a) => Value::Integer(*a),
+                Sample::Big(a) => Value::from(ObjBigInt::from(*a)),
+                Sample::Decimal(mantissa, scale) => Value::from(ObjDecimal::new(*mantissa, *scale)),
+                Sample::Rational(numerator, denominator) => Value::from(ObjRational::new(ObjBigInt::from(*numerator), ObjBigInt::from(*denominator)).unwrap()),
+                Sample::Float(a) => Value::Float(*a),
+                Sample::Text(text) => Value::Object(DynType::from(text.clone())),
+                Sample::Boolean(a) => Value::Boolean(*a),
+                Sample::Null => Value::Null
+            }
+        }
+
+        fn model(&self) -> Model<'_> {
+            match self {
+                Sample::Integer(a) => Model::Exact(*a as i128, 1),
+                Sample::Big(a) => Model::Exact(*a, 1),
+                Sample::Decimal(mantissa, scale) => Model::Exact(*mantissa, 10i128.pow(*scale)),
+                Sample::Rational(numerator, denominator) => Model::Exact(numerator * denominator.signum(), denominator.abs()),
+                Sample::Float(a) => Model::Float(*a),
+                Sample::Text(text) => Model::Text(text),
+                Sample::Boolean(a) => Model::Boolean(*a),
+                Sample::Null => Model::Null
+            }
+        }
+    }
+
+    // Replaces a or b by a simpler sample as long as the pair still fails
+    fn shrink(mut a: Sample, mut b: Sample, fails: impl Fn(&Sample, &Sample) -> bool) -> (Sample, Sample) {
+        'shrinking: loop {
+            for candidate in a.shrink() {
+                if fails(&candidate, &b) {
+                    a = candidate;
+                    continue 'shrinking;
+                }
+            }
+            for candidate in b.shrink() {
+                if fails(&a, &candidate) {
+                    b = candidate;
+                    continue 'shrinking;
+                }
+            }
+            return (a, b);
+        }
+    }
+
+    // Checks every comparison operator against the comparison of the models in Rust
+    fn compare_like_rust(a_sample: &Sample, b_sample: &Sample) -> Result<(), String> {
+        // the floats are dyadic and the denominators small, so multiplying by them is exact
+        let float_ordering = |numerator: i128, denominator: i128, float: f64| -> Option<Ordering> {
+            (numerator as f64).partial_cmp(&(float * denominator as f64))
+        };
+        let ordering = |a: Model, b: Model| -> Result<Option<Ordering>, ()> {
+            match (a, b) {
+                (Model::Exact(a, a_denominator), Model::Exact(b, b_denominator)) => Ok(Some((a * b_denominator).cmp(&(b * a_denominator)))),
+                (Model::Exact(a, 1), Model::Float(b)) => Ok((a as f64).partial_cmp(&b).map(|ordering| ordering.then_with(|| a.cmp(&(b as i128))))),
+                (Model::Exact(a, denominator), Model::Float(b)) => Ok(float_ordering(a, denominator, b)),
+                (Model::Float(a), Model::Exact(b, 1)) => Ok((b as f64).partial_cmp(&a).map(|ordering| ordering.then_with(|| b.cmp(&(a as i128))).reverse())),
+                (Model::Float(a), Model::Exact(b, denominator)) => Ok(float_ordering(b, denominator, a).map(Ordering::reverse)),
+                (Model::Float(a), Model::Float(b)) => Ok(a.partial_cmp(&b)),
+                (Model::Text(a), Model::Text(b)) => Ok(a.partial_cmp(b)),
+                _ => Err(())
+            }
+        };
+        let equal = |a: Model, b: Model| -> bool {
+            match (a, b) {
+                (Model::Text(a), Model::Text(b)) => a == b,
+                (Model::Boolean(a), Model::Boolean(b)) => a == b,
+                (Model::Null, Model::Null) => true,
+                _ => ordering(a, b) == Ok(Some(Ordering::Equal))
+            }
+        };
+
+        let (a, b): (Value, Value) = (a_sample.value(), b_sample.value());
+        let (a_model, b_model): (Model, Model) = (a_sample.model(), b_sample.model());
+        let expected = |operation: fn(Ordering) -> bool| -> Result<Value, ()> {
+            ordering(a_model, b_model).map(|ordering| Value::Boolean(ordering.is_some_and(operation)))
+        };
+        let results = [
+            ("==", Ok(a.equal(&b)), Ok(Value::Boolean(equal(a_model, b_model)))),
+            ("!=", Ok(a.not_equal(&b)), Ok(Value::Boolean(!equal(a_model, b_model)))),
+            (">", a.greater(&b).map_err(|_| ()), expected(Ordering::is_gt)),
+            (">=", a.greater_equal(&b).map_err(|_| ()), expected(Ordering::is_ge)),
+            ("<", a.less(&b).map_err(|_| ()), expected(Ordering::is_lt)),
+            ("<=", a.less_equal(&b).map_err(|_| ()), expected(Ordering::is_le))
+        ];
+
+        match results.into_iter().find(|(_, result, expected)| result != expected) {
+            Some((operator, result, expected)) => Err(format!("{a:?} {operator} {b:?} from {a_sample:?} and {b_sample:?} is {result:?} instead of {expected:?}")),
+            None => Ok(())
+        }
+    }
+
+    #[cfg(feature = "nan-boxing")]
+    pub fn nan_value_encoding(){
+        use crate::nan_value::NanValue;
+        use crate::value::{FloorDiv, Negate};
+
+        let samples = || -> Vec<Value> {
+            vec![
+                Value::Integer(0), Value::Integer(-1), Value::Integer(7), Value::Integer((1 << 47) - 1), Value::Integer(-(1 << 47)),
+                Value::Integer(1 << 47), Value::Integer(i64::MAX), Value::Integer(i64::MIN),
+                Value::Float(0.0), Value::Float(-0.0), Value::Float(2.5), Value::Float(f64::INFINITY), Value::Float(f64::NAN),
+                Value::Boolean(true), Value::Boolean(false), Value::Null,
+                Value::Object(DynType::from("text".to_string())), Value::from(ObjBigInt::from(i64::MAX).add(&ObjBigInt::from(1i64)))
+            ]
+        };
+        // NaN != NaN, so results are compared by their debug representation
+        let same = |nan_value: Result<NanValue, String>, value: Result<Value, String>| {
+            assert_eq!(format!("{:?}", nan_value), format!("{:?}", value));
+        };
+
+        for (a, a_value) in samples().into_iter().zip(samples()) {
+            let boxed: NanValue = NanValue::from(a.clone());
+            assert_eq!(format!("{:?}", boxed.clone()), format!("{:?}", a));
+            assert_eq!(boxed.is_falsey(), a.is_falsey());
+            same(boxed.clone().negate(), a_value.negate());
+
+            for b in samples() {
+                let (x, y) = (NanValue::from(a.clone()), NanValue::from(b.clone()));
+                same(x.clone() + y.clone(), a.clone() + b.clone());
+                same(x.clone() - y.clone(), a.clone() - b.clone());
+                same(x.clone() * y.clone(), a.clone() * b.clone());
+                same(x.clone() / y.clone(), a.clone() / b.clone());
+                same(x.clone() % y.clone(), a.clone() % b.clone());
+                same(x.clone().floor_div(y.clone()), a.clone().floor_div(b.clone()));
+                same(Ok(x.equal(&y)), Ok(a.equal(&b)));
+                same(Ok(x.not_equal(&y)), Ok(a.not_equal(&b)));
+                same(x.greater(&y), a.greater(&b));
+                same(x.greater_equal(&y), a.greater_equal(&b));
+                same(x.less(&y), a.less(&b));
+                same(x.less_equal(&y), a.less_equal(&b));
+            }
+        }
+        assert_eq!(std::mem::size_of::<NanValue>(), 8);
     }
 }
@@ -14,7 +14,12 @@ impl Token {
     }
 
     pub fn get_strrange(&self) -> Range<usize> {
-        self.range.0 as usize + 1..self.range.1 as usize - 1
+        match self.token_type {
+            // the part of an interpolated string ends with '${' instead of '"'
+            TokenType::TextInterpolation => self.range.0 as usize + 1..self.range.1 as usize - 2,
+            TokenType::MultilineText => self.range.0 as usize + 3..self.range.1 as usize - 3,
+            _ => self.range.0 as usize + 1..self.range.1 as usize - 1
+        }
     }
 }
 
@@ -31,6 +36,13 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    SlashSlash,
+    StarStar,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
     Bang,
     BangEqual,
     Equal,
@@ -39,10 +51,16 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
     Identifier,
     Text,
+    TextInterpolation,
+    RawText,
+    MultilineText,
     Integer,
     Float,
+    Decimal,
     And,
     Class,
     Else,
@@ -59,6 +77,7 @@ pub enum TokenType {
     True,
     Var,
     While,
+    Comment,
     Error,
     EndOfFile
 }
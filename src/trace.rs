@@ -0,0 +1,57 @@
+use std::fmt;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use super::chunk::{decode_line, format_constant, Chunk};
+use super::disassembler::format_instruction;
+use super::vm::{as_values, StackValue};
+
+/* TRACING
+
+ A VM with a Trace writes every instruction before executing it, preceded by the stack it works on:
+
+            [ Integer(1) ][ Integer(2) ]
+  000042    1 Add
+
+ The rows are the ones of the disassembler. The sink can be anything that implements Write (stderr, a file or
+ a Vec<u8> in tests) and lines limits the trace to the instructions of those source lines. Errors of the sink
+ are ignored, tracing never stops the program.
+
+ */
+
+pub struct Trace<'a> {
+    sink: Box<dyn Write + 'a>,
+    lines: Option<RangeInclusive<u32>>
+}
+
+impl <'a> Trace<'a> {
+    pub fn new(sink: impl Write + 'a) -> Self {
+        Self { sink: Box::new(sink), lines: None }
+    }
+
+    // Only trace the instructions on these lines
+    pub fn with_lines(mut self, lines: RangeInclusive<u32>) -> Self {
+        self.lines = Some(lines);
+        self
+    }
+
+    pub fn step(&mut self, chunk: &Chunk, index: usize, stack: &[StackValue]) {
+        let code: &[u8] = chunk.code();
+        if self.lines.as_ref().is_some_and(|lines| !lines.contains(&decode_line(code, index))) {
+            return;
+        }
+
+        let stack: String = match stack.is_empty() {
+            true => "(empty)".to_string(),
+            false => as_values(stack).iter().map(|value| format!("[ {} ]", format_constant(value))).collect()
+        };
+        let _ = writeln!(self.sink, "          {stack}");
+        let _ = writeln!(self.sink, "{}", format_instruction(chunk, index, false));
+    }
+}
+
+impl fmt::Debug for Trace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trace").field("lines", &self.lines).finish_non_exhaustive()
+    }
+}
@@ -1,10 +1,13 @@
-use std::{fmt::{Debug, Display}, ops::{Add, Div, Mul, Sub}};
+use std::{borrow::Cow, cmp::Ordering, fmt::{Debug, Display, Write}, ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Not, Rem, Shl, Shr, Sub}};
 use Value::*;
-use super::data_structures::DynType;
+use super::data_structures::{obj_big_int::ObjBigInt, obj_decimal::ObjDecimal, obj_rational::ObjRational, DynType};
+
+// Upper bound for the size of a big integer created by **, so that a typo can't allocate gigabytes
+const MAX_POWER_BITS: u64 = 1 << 24;
 
 pub type Error = String;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i64),
     Float(f64),
@@ -13,22 +16,58 @@ pub enum Value {
     Null,
 }
 
+impl Value {
+    // Only Null and false are falsey, 0, "" and every other object are truthy
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Null | Boolean(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Integer(_) => "Integer",
+            Float(_) => "Float",
+            Boolean(_) => "Boolean",
+            Null => "Null",
+            Object(DynType::Text(_)) => "String",
+            Object(DynType::BigInt(_)) => "BigInt",
+            Object(DynType::Decimal(_)) => "Decimal",
+            Object(DynType::Rational(_)) => "Rational"
+        }
+    }
+}
+
+// How a value appears in an error message, e.g. Integer(3) or String(abc)
+fn describe(value: &Value) -> String {
+    match value {
+        Null => "Null".to_string(),
+        _ => format!("{}({value})", value.type_name())
+    }
+}
+
 pub trait Negate {
     type Output;
     fn negate(self) -> Self::Output;
 }
 
+pub trait FloorDiv<Rhs = Self> {
+    type Output;
+    fn floor_div(self, rhs: Rhs) -> Self::Output;
+}
+
+pub trait Pow<Rhs = Self> {
+    type Output;
+    fn pow(self, rhs: Rhs) -> Self::Output;
+}
+
 impl Add for Value {
     type Output = Result<Self, Error>;
 
     fn add(self, rhs: Self) -> Self::Output {
         match (&self, &rhs) {
-            (Integer(a), Integer(b)) => Ok(Integer(a + b)),
-            (Integer(a), Float(b)) => Ok(Float(*a as f64 + b)),
-            (Float(a), Integer(b)) => Ok(Float(a + *b as f64)),
+            (Integer(a), Integer(b)) => a.checked_add(*b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &ADDITION)),
             (Float(a), Float(b)) => Ok(Float(a + b)),
-            (Object(a), Object(b)) => Ok(Object(a.add(b)?)),
-            _ => Err(format!("{:?} and {:?} can not be added", self, rhs))
+            (Object(a @ DynType::Text(_)), Object(b @ DynType::Text(_))) => Ok(Object(a.add(b)?)),
+            _ => arithmetic(&self, &rhs, &ADDITION)
         }
     }
 }
@@ -38,11 +77,9 @@ impl Sub for Value {
 
     fn sub(self, rhs: Self) -> Self::Output {
         match (&self, &rhs) {
-            (Integer(a), Integer(b)) => Ok(Integer(a - b)),
-            (Integer(a), Float(b)) => Ok(Float(*a as f64 - b)),
-            (Float(a), Integer(b)) => Ok(Float(a - *b as f64)),
+            (Integer(a), Integer(b)) => a.checked_sub(*b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &SUBTRACTION)),
             (Float(a), Float(b)) => Ok(Float(a - b)),
-            _ => Err(format!("{:?} and {:?} can not be subtracted", self, rhs))
+            _ => arithmetic(&self, &rhs, &SUBTRACTION)
         }
     }
 }
@@ -52,11 +89,9 @@ impl Mul for Value {
 
     fn mul(self, rhs: Self) -> Self::Output {
         match (&self, &rhs) {
-            (Integer(a), Integer(b)) => Ok(Integer(a * b)),
-            (Integer(a), Float(b)) => Ok(Float(*a as f64 * b)),
-            (Float(a), Integer(b)) => Ok(Float(a * *b as f64)),
+            (Integer(a), Integer(b)) => a.checked_mul(*b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &MULTIPLICATION)),
             (Float(a), Float(b)) => Ok(Float(a * b)),
-            _ => Err(format!("{:?} and {:?} can not be multiplied", self, rhs))
+            _ => arithmetic(&self, &rhs, &MULTIPLICATION)
         }
     }
 }
@@ -66,56 +101,473 @@ impl Div for Value {
 
     fn div(self, rhs: Self) -> Self::Output {
         match (&self, &rhs) {
-            (Integer(a), Integer(b)) => Ok(Integer(a / b)),
-            (Integer(a), Float(b)) => Ok(Float(*a as f64 / b)),
-            (Float(a), Integer(b)) => Ok(Float(a / *b as f64)),
+            (Integer(a), Integer(b)) => a.checked_div(*b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &DIVISION)),
             (Float(a), Float(b)) => Ok(Float(a / b)),
-            _ => Err(format!("{:?} and {:?} can not be divided", self, rhs))
+            _ => arithmetic(&self, &rhs, &DIVISION)
+        }
+    }
+}
+
+// % and // round towards negative infinity, so that a == (a // b) * b + a % b holds for every sign
+impl Rem for Value {
+    type Output = Result<Self, Error>;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => floor_rem(*a, *b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &REMAINDER)),
+            _ => arithmetic(&self, &rhs, &REMAINDER)
+        }
+    }
+}
+
+impl FloorDiv for Value {
+    type Output = Result<Self, Error>;
+
+    fn floor_div(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => floor_div(*a, *b).map(|result| Ok(Integer(result))).unwrap_or_else(|| arithmetic(&self, &rhs, &FLOOR_DIVISION)),
+            _ => arithmetic(&self, &rhs, &FLOOR_DIVISION)
+        }
+    }
+}
+
+impl Pow for Value {
+    type Output = Result<Self, Error>;
+
+    fn pow(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) if *b < 0 => Ok(Float((*a as f64).powf(*b as f64))),
+            (Integer(a), Integer(b)) => match u32::try_from(*b) {
+                Ok(exponent) => match a.checked_pow(exponent) {
+                    Some(result) => Ok(Integer(result)),
+                    None => big_pow(&ObjBigInt::from(*a), exponent)
+                },
+                Err(_) => Err(format!("The exponent {b} is too large"))
+            },
+            (Object(DynType::BigInt(a)), Integer(b)) if *b < 0 => Ok(Float(a.to_f64().powf(*b as f64))),
+            (Object(DynType::BigInt(a)), Integer(b)) => match u32::try_from(*b) {
+                Ok(exponent) => big_pow(a, exponent),
+                Err(_) => Err(format!("The exponent {b} is too large"))
+            },
+            (Object(DynType::Decimal(a)), Integer(b)) => decimal_pow(a, *b),
+            (Object(DynType::Rational(a)), Integer(b)) => rational_pow(a, *b),
+            _ => match promote_operands(&self, &rhs)? {
+                Some(_) => Ok(Float(to_f64(&self).powf(to_f64(&rhs)))),
+                None => Err(format!("{} and {} can not be exponentiated", describe(&self), describe(&rhs)))
+            }
+        }
+    }
+}
+
+impl BitAnd for Value {
+    type Output = Result<Self, Error>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => Ok(Integer(a & b)),
+            _ if is_integer(&self) && is_integer(&rhs) => Ok(Value::from(to_big(&self).bitand(&to_big(&rhs)))),
+            _ => Err(bitwise_error("&", &self, &rhs))
         }
     }
 }
 
+impl BitOr for Value {
+    type Output = Result<Self, Error>;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => Ok(Integer(a | b)),
+            _ if is_integer(&self) && is_integer(&rhs) => Ok(Value::from(to_big(&self).bitor(&to_big(&rhs)))),
+            _ => Err(bitwise_error("|", &self, &rhs))
+        }
+    }
+}
+
+impl BitXor for Value {
+    type Output = Result<Self, Error>;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => Ok(Integer(a ^ b)),
+            _ if is_integer(&self) && is_integer(&rhs) => Ok(Value::from(to_big(&self).bitxor(&to_big(&rhs)))),
+            _ => Err(bitwise_error("^", &self, &rhs))
+        }
+    }
+}
+
+impl Shl for Value {
+    type Output = Result<Self, Error>;
+
+    // bits shifted out of an i64 promote it to a big integer
+    fn shl(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => {
+                let amount: u64 = shift_amount(*b)?;
+                match u32::try_from(amount).ok().and_then(|amount| a.checked_shl(amount)).filter(|shifted| shifted >> amount == *a) {
+                    Some(shifted) => Ok(Integer(shifted)),
+                    None => big_shl(&ObjBigInt::from(*a), amount)
+                }
+            },
+            (Object(DynType::BigInt(a)), Integer(b)) => big_shl(a, shift_amount(*b)?),
+            _ => Err(bitwise_error("<<", &self, &rhs))
+        }
+    }
+}
+
+impl Shr for Value {
+    type Output = Result<Self, Error>;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        match (&self, &rhs) {
+            (Integer(a), Integer(b)) => Ok(Integer(a >> shift_amount(*b)?.min(i64::BITS as u64 - 1))),
+            (Object(DynType::BigInt(a)), Integer(b)) => Ok(Value::from(a.shr(shift_amount(*b)?))),
+            _ => Err(bitwise_error(">>", &self, &rhs))
+        }
+    }
+}
+
+// ~ in Eos, Rust uses ! for the bitwise complement of integers
+impl Not for Value {
+    type Output = Result<Self, Error>;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Integer(a) => Ok(Integer(!a)),
+            Object(DynType::BigInt(a)) => Ok(Value::from(a.not())),
+            _ => Err(format!("~ is only available for integers, not for {}", describe(&self)))
+        }
+    }
+}
+
+// None on overflow, i64::MIN // -1 is the only case
+pub fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let quotient: i64 = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        return Some(quotient - 1);
+    }
+    Some(quotient)
+}
+
+pub fn floor_rem(a: i64, b: i64) -> Option<i64> {
+    let remainder: i64 = a.checked_rem(b)?;
+    if remainder != 0 && (remainder < 0) != (b < 0) {
+        return Some(remainder + b);
+    }
+    Some(remainder)
+}
+
+// The numeric tower: both operands are converted to the first of Integer, Decimal, Rational and Float which can hold them
+enum Promoted<'a> {
+    Integers(Cow<'a, ObjBigInt>, Cow<'a, ObjBigInt>),
+    Decimals(ObjDecimal, ObjDecimal),
+    Rationals(Cow<'a, ObjRational>, Cow<'a, ObjRational>),
+    Floats(f64, f64)
+}
+
+struct Arithmetic {
+    description: &'static str,
+    integer: fn(&ObjBigInt, &ObjBigInt) -> Result<ObjBigInt, Error>,
+    decimal: fn(&ObjDecimal, &ObjDecimal) -> Result<ObjDecimal, Error>,
+    rational: fn(&ObjRational, &ObjRational) -> Result<ObjRational, Error>,
+    float: fn(f64, f64) -> f64
+}
+
+const ADDITION: Arithmetic = Arithmetic {
+    description: "added",
+    integer: |a, b| Ok(a.add(b)),
+    decimal: ObjDecimal::add,
+    rational: |a, b| Ok(a.add(b)),
+    float: |a, b| a + b
+};
+
+const SUBTRACTION: Arithmetic = Arithmetic {
+    description: "subtracted",
+    integer: |a, b| Ok(a.sub(b)),
+    decimal: ObjDecimal::sub,
+    rational: |a, b| Ok(a.sub(b)),
+    float: |a, b| a - b
+};
+
+const MULTIPLICATION: Arithmetic = Arithmetic {
+    description: "multiplied",
+    integer: |a, b| Ok(a.mul(b)),
+    decimal: ObjDecimal::mul,
+    rational: |a, b| Ok(a.mul(b)),
+    float: |a, b| a * b
+};
+
+const DIVISION: Arithmetic = Arithmetic {
+    description: "divided",
+    integer: |a, b| a.div_rem(b).map(|(quotient, _)| quotient).ok_or_else(division_by_zero),
+    decimal: ObjDecimal::div,
+    rational: ObjRational::div,
+    float: |a, b| a / b
+};
+
+const REMAINDER: Arithmetic = Arithmetic {
+    description: "used with %",
+    integer: |a, b| a.floor_div_rem(b).map(|(_, remainder)| remainder).ok_or_else(division_by_zero),
+    // the remainder of two decimals is exact at the larger of both scales
+    decimal: |a, b| ObjRational::from_decimal(a).floor_rem(&ObjRational::from_decimal(b))?.to_decimal(a.scale().max(b.scale())),
+    rational: ObjRational::floor_rem,
+    float: floor_rem_float
+};
+
+const FLOOR_DIVISION: Arithmetic = Arithmetic {
+    description: "floor divided",
+    integer: |a, b| a.floor_div_rem(b).map(|(quotient, _)| quotient).ok_or_else(division_by_zero),
+    decimal: |a, b| ObjRational::from_decimal(a).floor_div(&ObjRational::from_decimal(b)).map(ObjRational::from_integer)?.to_decimal(0),
+    rational: |a, b| a.floor_div(b).map(ObjRational::from_integer),
+    float: |a, b| (a / b).floor()
+};
+
+fn arithmetic(a: &Value, b: &Value, operation: &Arithmetic) -> Result<Value, Error> {
+    match promote_operands(a, b)? {
+        Some(Promoted::Integers(a, b)) => (operation.integer)(&a, &b).map(Value::from),
+        Some(Promoted::Decimals(a, b)) => (operation.decimal)(&a, &b).map(Value::from),
+        Some(Promoted::Rationals(a, b)) => (operation.rational)(&a, &b).map(Value::from),
+        Some(Promoted::Floats(a, b)) => Ok(Float((operation.float)(a, b))),
+        None => Err(format!("{} and {} can not be {}", describe(a), describe(b), operation.description))
+    }
+}
+
+fn rank(value: &Value) -> Option<u8> {
+    match value {
+        Integer(_) | Object(DynType::BigInt(_)) => Some(0),
+        Object(DynType::Decimal(_)) => Some(1),
+        Object(DynType::Rational(_)) => Some(2),
+        Float(_) => Some(3),
+        _ => None
+    }
+}
+
+// None if one of the operands isn't a number
+fn promote_operands<'a>(a: &'a Value, b: &'a Value) -> Result<Option<Promoted<'a>>, Error> {
+    let (Some(a_rank), Some(b_rank)) = (rank(a), rank(b)) else {
+        return Ok(None);
+    };
+
+    Ok(Some(match a_rank.max(b_rank) {
+        0 => Promoted::Integers(to_big(a), to_big(b)),
+        1 => Promoted::Decimals(to_decimal(a)?, to_decimal(b)?),
+        2 => Promoted::Rationals(to_rational(a), to_rational(b)),
+        _ => Promoted::Floats(to_f64(a), to_f64(b))
+    }))
+}
+
+fn to_big(value: &Value) -> Cow<'_, ObjBigInt> {
+    match value {
+        Integer(a) => Cow::Owned(ObjBigInt::from(*a)),
+        Object(DynType::BigInt(a)) => Cow::Borrowed(a),
+        _ => unreachable!("{:?} is not an integer", value)
+    }
+}
+
+fn to_decimal(value: &Value) -> Result<ObjDecimal, Error> {
+    match value {
+        Integer(a) => Ok(ObjDecimal::from(*a)),
+        Object(DynType::BigInt(a)) => a.to_i128().map(|a| ObjDecimal::new(a, 0)).ok_or_else(|| format!("{a} is too large for a decimal")),
+        Object(DynType::Decimal(a)) => Ok(**a),
+        _ => unreachable!("{:?} can not be converted to a decimal", value)
+    }
+}
+
+fn to_rational(value: &Value) -> Cow<'_, ObjRational> {
+    match value {
+        Object(DynType::Rational(a)) => Cow::Borrowed(a),
+        Object(DynType::Decimal(a)) => Cow::Owned(ObjRational::from_decimal(a)),
+        _ => Cow::Owned(ObjRational::from_integer(to_big(value).into_owned()))
+    }
+}
+
+fn to_f64(value: &Value) -> f64 {
+    match value {
+        Integer(a) => *a as f64,
+        Float(a) => *a,
+        Object(DynType::BigInt(a)) => a.to_f64(),
+        Object(DynType::Decimal(a)) => a.to_f64(),
+        Object(DynType::Rational(a)) => a.to_f64(),
+        _ => unreachable!("{:?} is not a number", value)
+    }
+}
+
+fn division_by_zero() -> Error {
+    "Division by zero".to_string()
+}
+
+fn big_pow(base: &ObjBigInt, exponent: u32) -> Result<Value, Error> {
+    if base.bits().saturating_mul(exponent as u64) > MAX_POWER_BITS {
+        return Err(format!("The result of {base} ** {exponent} has more than {MAX_POWER_BITS} bits"));
+    }
+    Ok(Value::from(base.pow(exponent)))
+}
+
+fn decimal_pow(base: &ObjDecimal, exponent: i64) -> Result<Value, Error> {
+    let power: u32 = u32::try_from(exponent.unsigned_abs()).map_err(|_| format!("The exponent {exponent} is too large"))?;
+    let result: ObjDecimal = base.pow(power)?;
+
+    match exponent < 0 {
+        true => ObjDecimal::from(1i64).div(&result).map(Value::from),
+        false => Ok(Value::from(result))
+    }
+}
+
+fn rational_pow(base: &ObjRational, exponent: i64) -> Result<Value, Error> {
+    let bits: u64 = base.numerator().bits().max(base.denominator().bits());
+    if bits.saturating_mul(exponent.unsigned_abs()) > MAX_POWER_BITS {
+        return Err(format!("The result of {base} ** {exponent} has more than {MAX_POWER_BITS} bits"));
+    }
+    base.pow(exponent).map(Value::from)
+}
+
+pub fn floor_rem_float(a: f64, b: f64) -> f64 {
+    let remainder: f64 = a % b;
+    if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+        return remainder + b;
+    }
+    remainder
+}
+
+fn shift_amount(amount: i64) -> Result<u64, Error> {
+    u64::try_from(amount).map_err(|_| format!("Can not shift by {amount}, the shift amount can't be negative"))
+}
+
+fn big_shl(base: &ObjBigInt, amount: u64) -> Result<Value, Error> {
+    if !base.is_zero() && base.bits().saturating_add(amount) > MAX_POWER_BITS {
+        return Err(format!("The result of {base} << {amount} has more than {MAX_POWER_BITS} bits"));
+    }
+    Ok(Value::from(base.shl(amount)))
+}
+
+fn is_integer(value: &Value) -> bool {
+    matches!(value, Integer(_) | Object(DynType::BigInt(_)))
+}
+
+fn bitwise_error(operator: &str, a: &Value, b: &Value) -> Error {
+    format!("{operator} is only available for integers, not for {} and {}", describe(a), describe(b))
+}
+
+
+/* COMPARISON
+
+ Numbers of different types are compared by their exact value, so 1 == 1.0 and 0.5d == rational(1, 2) are true.
+ Floats follow IEEE 754: NaN is unordered and unequal to everything including itself, so every comparison with it is false
+ except for !=, and -0.0 == 0.0. Strings are ordered lexicographically by their bytes, which is the order of their code points.
+ Ordering any other pair of values is a runtime error, while == and != compare them structurally.
+
+ */
+
+pub trait Comparison: Sized {
+    fn equal(&self, other: &Self) -> Self;
+
+    fn not_equal(&self, other: &Self) -> Self;
+
+    fn greater(&self, other: &Self) -> Result<Self, Error>;
 
-pub trait Comparison {
-    fn greater(&self, other: &Self) -> Result<Value, Error>;
+    fn greater_equal(&self, other: &Self) -> Result<Self, Error>;
 
-    fn less(&self, other: &Self) -> Result<Value, Error>;
+    fn less(&self, other: &Self) -> Result<Self, Error>;
+
+    fn less_equal(&self, other: &Self) -> Result<Self, Error>;
 }
 
 impl Comparison for Value {
+    fn equal(&self, other: &Self) -> Self {
+        Boolean(equal(self, other))
+    }
+
+    fn not_equal(&self, other: &Self) -> Self {
+        Boolean(!equal(self, other))
+    }
+
     fn greater(&self, other: &Self) -> Result<Self, Error> {
-        match (self, other) {
-            (Integer(a), Integer(b)) => Ok(Boolean(a > b)),
-            (Integer(a), Float(b)) => Ok(Boolean(*a as f64 > *b)),
-            (Float(a), Integer(b)) => Ok(Boolean(*a > *b as f64)),
-            (Float(a), Float(b)) => Ok(Boolean(a > b)),
-            _ => Err(" > is only available for Float and Integer".to_string())
-        }
+        Ok(Boolean(compare(self, other, ">")? == Some(Ordering::Greater)))
+    }
+
+    fn greater_equal(&self, other: &Self) -> Result<Self, Error> {
+        Ok(Boolean(matches!(compare(self, other, ">=")?, Some(Ordering::Greater | Ordering::Equal))))
     }
 
     fn less(&self, other: &Self) -> Result<Self, Error> {
-        match (self, other) {
-            (Integer(a), Integer(b)) => Ok(Boolean(a < b)),
-            (Integer(a), Float(b)) => Ok(Boolean((*a as f64) < *b)),
-            (Float(a), Integer(b)) => Ok(Boolean(*a < *b as f64)),
-            (Float(a), Float(b)) => Ok(Boolean(a > b)),
-            _ => Err(" < is only available for Float and Integer".to_string())
+        Ok(Boolean(compare(self, other, "<")? == Some(Ordering::Less)))
+    }
+
+    fn less_equal(&self, other: &Self) -> Result<Self, Error> {
+        Ok(Boolean(matches!(compare(self, other, "<=")?, Some(Ordering::Less | Ordering::Equal))))
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (rank(a), rank(b)) {
+        (Some(_), Some(_)) => compare(a, b, "==").is_ok_and(|ordering| ordering == Some(Ordering::Equal)),
+        _ => a == b
+    }
+}
+
+// None if one of the operands is NaN
+fn compare(a: &Value, b: &Value, operator: &str) -> Result<Option<Ordering>, Error> {
+    match (a, b) {
+        (Integer(a), Integer(b)) => Ok(Some(a.cmp(b))),
+        (Float(a), Float(b)) => Ok(a.partial_cmp(b)),
+        (Object(DynType::Text(a)), Object(DynType::Text(b))) => Ok(Some(a.cmp(b))),
+        (Object(DynType::Decimal(a)), Object(DynType::Decimal(b))) => Ok(Some(a.cmp(b))),
+        (Float(a), _) if rank(b).is_some() => Ok(cmp_f64(b, *a).map(Ordering::reverse)),
+        (_, Float(b)) if rank(a).is_some() => Ok(cmp_f64(a, *b)),
+        _ => match (rank(a), rank(b)) {
+            (Some(0), Some(0)) => Ok(Some(to_big(a).cmp(&to_big(b)))),
+            // a decimal can't hold every big integer, but a fraction can hold both
+            (Some(_), Some(_)) => Ok(Some(to_rational(a).cmp(&to_rational(b)))),
+            _ => Err(format!("{operator} is only available for numbers and strings, not for {} and {}", describe(a), describe(b)))
         }
     }
 }
 
+// a is an exact number, never a Float
+fn cmp_f64(a: &Value, b: f64) -> Option<Ordering> {
+    match (a, ObjRational::from_f64(b)) {
+        (Integer(_) | Object(DynType::BigInt(_)), _) => to_big(a).cmp_f64(b),
+        (_, Some(b)) => Some(to_rational(a).as_ref().cmp(&b)),
+        (_, None) if b.is_nan() => None,
+        (_, None) => Some(if b > 0.0 { Ordering::Less } else { Ordering::Greater })
+    }
+}
+
 impl Negate for Value {
     type Output = Result<Value, Error>;
     fn negate(self) -> Self::Output {
         match self {
-            Integer(a) => Ok(Integer(-a)),
+            Integer(a) => Ok(a.checked_neg().map(Integer).unwrap_or_else(|| Value::from(ObjBigInt::from(a).neg()))),
+            Object(DynType::BigInt(a)) => Ok(Value::from(a.neg())),
+            Object(DynType::Decimal(a)) => a.neg().map(Value::from),
+            Object(DynType::Rational(a)) => Ok(Value::from(a.neg())),
             Float(a) => Ok(Float(-a)),
-            Boolean(a) => Ok(Boolean(!a)),
-            _ => Err(format!("{:?} CANNOT BE NEGATED", self))
+            _ => Err(format!("{} CANNOT BE NEGATED", describe(&self)))
+        }
+    }
+}
+
+// Integer results which don't fit into an i64 are promoted to a big integer and demoted again once they fit
+impl From<ObjBigInt> for Value {
+    fn from(value: ObjBigInt) -> Self {
+        match value.to_i64() {
+            Some(value) => Integer(value),
+            None => Object(DynType::from(value))
         }
     }
 }
 
+impl From<ObjDecimal> for Value {
+    fn from(value: ObjDecimal) -> Self {
+        Object(DynType::from(value))
+    }
+}
+
+impl From<ObjRational> for Value {
+    fn from(value: ObjRational) -> Self {
+        Object(DynType::from(value))
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -127,3 +579,13 @@ impl Display for Value {
         }
     }
 }
+
+// Formats every part once, straight into the string
+pub fn interpolate(values: &[Value]) -> Value {
+    let mut buffer: String = String::new();
+    for value in values {
+        let _ = write!(buffer, "{value}");
+    }
+
+    Object(DynType::from::<String>(buffer))
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use super::chunk::{decode_operand, instruction_size, is_jump, jump_target, Chunk, INSTRUCTION_SIZE, OPERAND_SIZE};
+use super::native::NATIVES;
+use super::opcode::OpCode::{self, *};
+
+/* VERIFIER
+
+ The VM trusts the chunk it runs: it reads values at the offsets the instructions point to and expects the
+ stack to contain the operands of every instruction. verify checks a chunk before it is executed, e.g. after it
+ has been loaded from an eosc file:
+
+  - every opcode is known and every instruction ends within the code
+  - every value is one that has been written by add_value, so no arbitrary bytes are read as a Value
+  - the operand of CallNative is a native and every jump lands on an instruction or the end of the code
+  - the stack depth is the same on every path to an instruction and never drops below what it needs
+
+ */
+
+struct Instruction {
+    offset: usize,
+    opcode: OpCode
+}
+
+pub fn verify(chunk: &Chunk) -> Result<(), String> {
+    let code: &[u8] = chunk.code();
+    let instructions: Vec<Instruction> = decode_instructions(chunk)?;
+    let boundaries: HashSet<usize> = instructions.iter().map(|instruction| instruction.offset).chain([code.len()]).collect();
+
+    for instruction in &instructions {
+        let operand: usize = instruction.offset + INSTRUCTION_SIZE;
+
+        match instruction.opcode {
+            CallNative if decode_operand(code, operand) as usize >= NATIVES.len() => {
+                return Err(format!("{:06} CallNative: unknown native {}", instruction.offset, decode_operand(code, operand)));
+            },
+            opcode if is_jump(opcode) => {
+                let target: usize = jump_target(code, instruction.offset);
+                if !boundaries.contains(&target) {
+                    return Err(format!("{:06} {:?}: the target {target} is not the start of an instruction", instruction.offset, opcode));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    check_stack(code, &instructions)
+}
+
+fn decode_instructions(chunk: &Chunk) -> Result<Vec<Instruction>, String> {
+    let code: &[u8] = chunk.code();
+    let mut constants = chunk.constant_offsets().iter();
+    let mut instructions: Vec<Instruction> = Vec::new();
+
+    let mut offset: usize = 0;
+    while offset < code.len() {
+        let opcode: OpCode = OpCode::try_from(code[offset]).map_err(|err| format!("{offset:06} {err}"))?;
+        let size: usize = instruction_size(opcode);
+
+        if offset + size > code.len() {
+            return Err(format!("{offset:06} {opcode:?}: the instruction is cut off"));
+        }
+
+        // the values are stored in the same order as the instructions that carry them
+        if size - INSTRUCTION_SIZE > OPERAND_SIZE && constants.next() != Some(&(offset + INSTRUCTION_SIZE)) {
+            return Err(format!("{offset:06} {opcode:?}: no value has been written at this instruction"));
+        }
+
+        instructions.push(Instruction { offset, opcode });
+        offset += size;
+    }
+
+    match constants.next() {
+        Some(offset) => Err(format!("{offset:06} the value is not part of an instruction")),
+        None => Ok(instructions)
+    }
+}
+
+// Follows every path through the chunk and records the stack depth before each instruction
+fn check_stack(code: &[u8], instructions: &[Instruction]) -> Result<(), String> {
+    let mut depths: Vec<Option<usize>> = vec![None; instructions.len() + 1];
+    let mut pending: Vec<(usize, usize)> = vec![(0, 0)]; // (index of the instruction, depth before it)
+
+    while let Some((index, depth)) = pending.pop() {
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(known) => {
+                let offset: usize = instructions.get(index).map_or(code.len(), |instruction| instruction.offset);
+                return Err(format!("{offset:06} the stack holds {known} values on one path and {depth} on another"));
+            },
+            None => depths[index] = Some(depth)
+        }
+
+        let Some(instruction) = instructions.get(index) else {
+            continue; // the end of the code
+        };
+
+        let (needs, pushes): (usize, usize) = stack_effect(code, instruction);
+        if depth < needs {
+            return Err(format!("{:06} {:?} needs {needs} values on the stack, but there are {depth}", instruction.offset, instruction.opcode));
+        }
+        let after: usize = depth - needs + pushes;
+
+        if is_jump(instruction.opcode) {
+            let target: usize = jump_target(code, instruction.offset);
+            let target_index: usize = instructions.binary_search_by_key(&target, |other| other.offset).unwrap_or(instructions.len());
+            pending.push((target_index, after));
+        }
+
+        if instruction.opcode != Jump {
+            pending.push((index + 1, after));
+        }
+    }
+    Ok(())
+}
+
+// How many values the instruction takes from the stack and how many it puts back
+fn stack_effect(code: &[u8], instruction: &Instruction) -> (usize, usize) {
+    let operand = || decode_operand(code, instruction.offset + INSTRUCTION_SIZE) as usize;
+
+    match instruction.opcode {
+        Return | Jump => (0, 0),
+        Constant => (0, 1),
+        Negate | Not | BitNot | AddConstant | SubtractConstant | JumpIfFalse => (1, 1),
+        Print | Pop => (1, 0),
+        Equal | NotEqual | Greater | GreaterEqual | Less | LessEqual | Add | Subtract | Multiply | Divide | Modulo | FloorDivide | Power
+            | BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight | EqualJumpIfFalse | GreaterJumpIfFalse | LessJumpIfFalse => (2, 1),
+        Interpolate => (operand(), 1),
+        CallNative => (NATIVES[operand()].arity, 1)
+    }
+}
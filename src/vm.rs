@@ -1,117 +1,341 @@
-use super::chunk::{Chunk, VALUE_SIZE, INSTRUCTION_SIZE};
+use super::chunk::{decode_line, decode_opcode, decode_operand, decode_value, Chunk, VALUE_SIZE, INSTRUCTION_SIZE, OPERAND_SIZE};
 use super::common::{DEFAULT_STACK_CAPACITY, runtime_error};
-use super::opcode::OpCode::*;
-use super::value::{Negate, Value, Comparison};
+use super::native::{Native, NATIVES};
+use super::opcode::OpCode::{self, *};
+use super::trace::Trace;
+use super::value::{floor_div, floor_rem, interpolate, Comparison, Error, FloorDiv, Negate, Pow, Value};
+#[cfg(feature = "nan-boxing")]
+use super::nan_value::NanValue;
+use std::borrow::Cow;
+use std::mem::replace;
+
+// The representation of the values on the stack, the feature "nan-boxing" replaces the enum with 8 byte NanValues
+#[cfg(not(feature = "nan-boxing"))]
+pub type StackValue = Value;
+#[cfg(feature = "nan-boxing")]
+pub type StackValue = NanValue;
+
+#[cfg(not(feature = "nan-boxing"))]
+fn to_stack(value: Value) -> StackValue {
+    value
+}
+
+#[cfg(feature = "nan-boxing")]
+fn to_stack(value: Value) -> StackValue {
+    NanValue::from(value)
+}
+
+// Natives and interpolation work on Values, converting is only needed for NanValues
+#[cfg(not(feature = "nan-boxing"))]
+pub fn as_values(values: &[StackValue]) -> Cow<'_, [Value]> {
+    Cow::Borrowed(values)
+}
+
+#[cfg(feature = "nan-boxing")]
+pub fn as_values(values: &[StackValue]) -> Cow<'_, [Value]> {
+    Cow::Owned(values.iter().map(NanValue::to_value).collect())
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+fn integers(a: &StackValue, b: &StackValue) -> Option<(i64, i64)> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Some((*a, *b)),
+        _ => None
+    }
+}
+
+#[cfg(feature = "nan-boxing")]
+fn integers(a: &StackValue, b: &StackValue) -> Option<(i64, i64)> {
+    Some((a.as_integer()?, b.as_integer()?))
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+fn integer(value: i64) -> StackValue {
+    Value::Integer(value)
+}
+
+#[cfg(feature = "nan-boxing")]
+fn integer(value: i64) -> StackValue {
+    NanValue::from_i64(value)
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+fn boolean(value: bool) -> StackValue {
+    Value::Boolean(value)
+}
+
+#[cfg(feature = "nan-boxing")]
+fn boolean(value: bool) -> StackValue {
+    NanValue::from_bool(value)
+}
+
+// Only bytecode that wasn't verified can pop more values than it pushed
+fn empty_stack() -> Error {
+    "EXPECTED A NON-EMPTY STACK".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Paused,
+    Finished
+}
 
 #[derive(Debug)]
 pub struct VM<'a> {
     chunk: &'a Chunk,
-    stack: Vec<Value>,
+    stack: Vec<StackValue>,
     line: u32,
-    ptr: usize // it serves as an index into the arraylist bytes
+    ptr: usize, // it serves as an index into the arraylist bytes
+    error: Option<Error>,
+    trace: Option<Trace<'a>>
 }
 
+/* DISPATCH
+
+ run keeps the instruction pointer in a local and decodes straight from the code slice, the line of an
+ instruction is only read when it fails. Binary operations pop the right operand and overwrite the left one
+ in place, two Integers take a fast path which never leaves i64 and only fall back to the Value traits
+ when the result overflows. Every arm returns a Result so that errors are reported in one place.
+
+ Jumps store a forward offset as their operand, it is counted from the end of the jump instruction.
+
+ An unknown opcode or popping an empty stack stop the run with a runtime error, but run doesn't check that the
+ bytes it reads as a Value were written by add_value. The compiler only produces valid chunks, chunks from
+ anywhere else have to pass verify first (see verifier.rs), Chunk::deserialize does so on its own.
+
+ With a Trace set, every instruction is written out before it runs; without one it costs a single branch.
+ run_until lets tools like the debugger stop before any instruction, run passes a pause that is always false.
+
+ */
+
 impl <'a> VM <'a>{
     pub fn new(chunk: &'a Chunk) -> Self {
-        Self { chunk, stack: Vec::with_capacity(DEFAULT_STACK_CAPACITY), line: 0, ptr: 0 }
+        Self { chunk, stack: Vec::with_capacity(DEFAULT_STACK_CAPACITY), line: 0, ptr: 0, error: None, trace: None }
+    }
+
+    // Tracing is off by default, see trace.rs
+    pub fn set_trace(&mut self, trace: Option<Trace<'a>>) {
+        self.trace = trace;
     }
 
     pub fn run(&mut self) -> Option<()> {
-        while let Some(buffer) = self.chunk.read_opcode(self.ptr) {
-            self.line = buffer.1;
-
-            match buffer.0 {
-                Equal => self.equal(),
-                Greater => if let Err(err) = self.binary_op(|a, b| a.greater(&b) ) {
-                    self.error(&err);
-                    return None;
+        self.run_until(|_| false).map(|_| ())
+    }
+
+    // Like run, but stops before the instruction at ip if pause(ip) is true, the next call continues from there
+    #[inline(always)]
+    pub fn run_until(&mut self, mut pause: impl FnMut(usize) -> bool) -> Option<Status> {
+        let code: &'a [u8] = self.chunk.code();
+        let mut ip: usize = self.ptr;
+
+        while ip < code.len() {
+            if pause(ip) {
+                self.ptr = ip;
+                return Some(Status::Paused);
+            }
+
+            let instruction: usize = ip;
+            if let Some(trace) = &mut self.trace {
+                trace.step(self.chunk, instruction, &self.stack);
+            }
+            let opcode: OpCode = match decode_opcode(code, ip) {
+                Ok(opcode) => opcode,
+                Err(err) => {
+                    self.ptr = ip;
+                    return self.fail(code, ip, err);
+                }
+            };
+            ip += INSTRUCTION_SIZE;
+
+            let result: Result<(), Error> = match opcode {
+                // the instructions come from a Chunk, so ip is at one of its constant offsets
+                Constant => {
+                    self.stack.push(to_stack(unsafe { decode_value(code, ip) }));
+                    ip += VALUE_SIZE;
+                    Ok(())
                 },
-                Less => if let Err(err) = self.binary_op(|a, b| a.less(&b) ) {
-                    self.error(&err);
-                    return None;
+                Add => self.arithmetic(i64::checked_add, |a, b| a + b),
+                Subtract => self.arithmetic(i64::checked_sub, |a, b| a - b),
+                Multiply => self.arithmetic(i64::checked_mul, |a, b| a * b),
+                Divide => self.arithmetic(i64::checked_div, |a, b| a / b),
+                Modulo => self.arithmetic(floor_rem, |a, b| a % b),
+                FloorDivide => self.arithmetic(floor_div, |a, b| a.floor_div(b)),
+                Power => self.binary_op(|a, b| a.pow(b)),
+                BitAnd => self.arithmetic(|a, b| Some(a & b), |a, b| a & b),
+                BitOr => self.arithmetic(|a, b| Some(a | b), |a, b| a | b),
+                BitXor => self.arithmetic(|a, b| Some(a ^ b), |a, b| a ^ b),
+                ShiftLeft => self.binary_op(|a, b| a << b),
+                ShiftRight => self.binary_op(|a, b| a >> b),
+                Equal => self.comparison(|a, b| a == b, |a, b| Ok(a.equal(b))),
+                NotEqual => self.comparison(|a, b| a != b, |a, b| Ok(a.not_equal(b))),
+                Greater => self.comparison(|a, b| a > b, StackValue::greater),
+                GreaterEqual => self.comparison(|a, b| a >= b, StackValue::greater_equal),
+                Less => self.comparison(|a, b| a < b, StackValue::less),
+                LessEqual => self.comparison(|a, b| a <= b, StackValue::less_equal),
+                Negate => self.unary_op(|a| a.negate()),
+                Not => self.unary_op(|a| Ok(boolean(a.is_falsey()))),
+                BitNot => self.unary_op(|a| !a),
+                Interpolate => {
+                    let parts: usize = decode_operand(code, ip) as usize;
+                    ip += OPERAND_SIZE;
+                    self.interpolate(parts)
                 },
-                Add => if let Err(err) = self.binary_op(|a, b| a + b) {
-                    self.error(&err);
-                    return None;
+                CallNative => {
+                    let index: usize = decode_operand(code, ip) as usize;
+                    ip += OPERAND_SIZE;
+                    self.call_native(index)
                 },
-                Subtract => if let Err(err) = self.binary_op(|a, b| a - b) {
-                    self.error(&err);
-                    return None;
+                Print => self.print(),
+                Pop => self.stack.pop().map(|_| ()).ok_or_else(empty_stack),
+                Jump => {
+                    ip += OPERAND_SIZE + decode_operand(code, ip) as usize;
+                    Ok(())
                 },
-                Multiply => if let Err(err) = self.binary_op(|a, b| a * b) {
-                    self.error(&err);
-                    return None;
+                JumpIfFalse => {
+                    ip = self.jump_if_false(code, ip);
+                    Ok(())
                 },
-                Divide => if let Err(err) = self.binary_op(|a, b| a / b) {
-                    self.error(&err);
-                    return None;
+                AddConstant => {
+                    let b: StackValue = to_stack(unsafe { decode_value(code, ip) });
+                    ip += VALUE_SIZE;
+                    self.arithmetic_with(b, i64::checked_add, |a, b| a + b)
                 },
-                Negate => if let Err(err) = self.negate() {
-                    self.error(&err);
-                    return None;
+                SubtractConstant => {
+                    let b: StackValue = to_stack(unsafe { decode_value(code, ip) });
+                    ip += VALUE_SIZE;
+                    self.arithmetic_with(b, i64::checked_sub, |a, b| a - b)
                 },
-                Constant => self.push_to_stack(),
-                Print => self.print(),
-                Return => self.move_ptr(INSTRUCTION_SIZE)
+                EqualJumpIfFalse => self.comparison(|a, b| a == b, |a, b| Ok(a.equal(b))).map(|_| ip = self.jump_if_false(code, ip)),
+                GreaterJumpIfFalse => self.comparison(|a, b| a > b, StackValue::greater).map(|_| ip = self.jump_if_false(code, ip)),
+                LessJumpIfFalse => self.comparison(|a, b| a < b, StackValue::less).map(|_| ip = self.jump_if_false(code, ip)),
+                Return => Ok(())
+            };
+
+            if let Err(err) = result {
+                self.ptr = ip;
+                return self.fail(code, instruction, err);
             }
         }
-        Some(())
+        self.ptr = ip;
+        Some(Status::Finished)
     }
 
-    pub fn get_stack(&self) -> &[Value] {
+    pub fn get_stack(&self) -> &[StackValue] {
         &self.stack
     }
 
-    fn binary_op<F: Fn(Value, Value) -> Result<Value, String>>(&mut self, operand: F) -> Result<(), String>{
-        self.move_ptr(INSTRUCTION_SIZE);
+    // The line of the instruction that failed
+    pub fn get_line(&self) -> u32 {
+        self.line
+    }
 
-        let a: Value = self.stack.pop().ok_or_else(|| "EXPECTED TO  A NONE-EMPTY STACK".to_string())?;
-        let b: Value = self.stack.pop().ok_or_else(|| "EXPECTED TO  A NONE-EMPTY STACK".to_string())?;
+    // The message of the runtime error, without the line
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
 
-        let temp: Value = operand(b, a)?;
+    pub fn get_chunk(&self) -> &'a Chunk {
+        self.chunk
+    }
 
-        self.stack.push(temp);
+    // The offset of the next instruction
+    pub fn get_ip(&self) -> usize {
+        self.ptr
+    }
 
-        Ok(())
+    // The left operand stays on the stack and is replaced by the result
+    #[inline(always)]
+    fn arithmetic(&mut self, fast: fn(i64, i64) -> Option<i64>, operation: fn(StackValue, StackValue) -> Result<StackValue, Error>) -> Result<(), Error> {
+        let b: StackValue = self.stack.pop().ok_or_else(empty_stack)?;
+        self.arithmetic_with(b, fast, operation)
     }
 
-    fn equal(&mut self) {
-        self.move_ptr(INSTRUCTION_SIZE);
+    // The right operand either comes from the stack or is stored in the instruction, e.g. OpCode::AddConstant
+    #[inline(always)]
+    fn arithmetic_with(&mut self, b: StackValue, fast: fn(i64, i64) -> Option<i64>, operation: fn(StackValue, StackValue) -> Result<StackValue, Error>) -> Result<(), Error> {
+        let a: &mut StackValue = self.stack.last_mut().ok_or_else(empty_stack)?;
+
+        if let Some(result) = integers(a, &b).and_then(|(x, y)| fast(x, y)) {
+            *a = integer(result);
+            return Ok(());
+        }
+
+        let left: StackValue = replace(a, integer(0));
+        *a = operation(left, b)?;
+        Ok(())
+    }
 
-        let a: Value = self.stack.pop().expect("EXPECTED TO  A NONE-EMPTY STACK");
-        let b: Value = self.stack.pop().expect("EXPECTED TO  A NONE-EMPTY STACK");
+    #[inline(always)]
+    fn comparison(&mut self, fast: fn(&i64, &i64) -> bool, operation: fn(&StackValue, &StackValue) -> Result<StackValue, Error>) -> Result<(), Error> {
+        let b: StackValue = self.stack.pop().ok_or_else(empty_stack)?;
+        let a: &mut StackValue = self.stack.last_mut().ok_or_else(empty_stack)?;
 
-        self.stack.push(Value::Boolean(a == b));
+        *a = match integers(a, &b) {
+            Some((x, y)) => boolean(fast(&x, &y)),
+            None => operation(a, &b)?
+        };
+        Ok(())
     }
 
-    fn negate(&mut self) -> Result<(), String>{
-        self.move_ptr(INSTRUCTION_SIZE);
+    // The condition stays on the stack, returns the position of the next instruction
+    #[inline(always)]
+    fn jump_if_false(&self, code: &[u8], ip: usize) -> usize {
+        let offset: usize = decode_operand(code, ip) as usize;
 
-        let temp: Value = self.stack.pop().expect("EXPECTED TO  A NONE-EMPTY STACK");
+        match self.stack.last() {
+            Some(condition) if condition.is_falsey() => ip + OPERAND_SIZE + offset,
+            _ => ip + OPERAND_SIZE
+        }
+    }
+
+    fn binary_op(&mut self, operation: fn(StackValue, StackValue) -> Result<StackValue, Error>) -> Result<(), Error> {
+        self.arithmetic(|_, _| None, operation)
+    }
 
-        self.stack.push(temp.negate()?);
+    #[inline(always)]
+    fn unary_op<F: Fn(StackValue) -> Result<StackValue, Error>>(&mut self, operation: F) -> Result<(), Error> {
+        let a: &mut StackValue = self.stack.last_mut().ok_or_else(empty_stack)?;
 
+        let operand: StackValue = replace(a, integer(0));
+        *a = operation(operand)?;
         Ok(())
     }
 
-    fn push_to_stack(&mut self) {
-        self.move_ptr(INSTRUCTION_SIZE);
-
-        let value: Value = self.chunk.read_value(self.ptr).expect("EXPECTED VALUE").0;
-        self.stack.push(value);
+    fn interpolate(&mut self, parts: usize) -> Result<(), Error> {
+        let start: usize = self.stack.len().checked_sub(parts).ok_or_else(empty_stack)?;
+        let temp: StackValue = to_stack(interpolate(&as_values(&self.stack[start..])));
 
-        self.move_ptr(VALUE_SIZE);
+        self.stack.truncate(start);
+        self.stack.push(temp);
 
+        Ok(())
     }
 
-    fn print(&mut self) {
-        self.move_ptr(INSTRUCTION_SIZE);
+    fn call_native(&mut self, index: usize) -> Result<(), Error> {
+        let native: &Native = NATIVES.get(index).ok_or_else(|| format!("UNKNOWN NATIVE FUNCTION {index}"))?;
+        let start: usize = self.stack.len().checked_sub(native.arity).ok_or_else(empty_stack)?;
+        let temp: StackValue = to_stack((native.function)(&as_values(&self.stack[start..]))?);
+
+        self.stack.truncate(start);
+        self.stack.push(temp);
+
+        Ok(())
+    }
 
-        println!("{}", self.stack.pop().expect("EXPECTED TO  A NONE-EMPTY STACK"));
+    fn print(&mut self) -> Result<(), Error> {
+        println!("{}", self.stack.pop().ok_or_else(empty_stack)?);
+        Ok(())
     }
 
-    fn move_ptr(&mut self, amount: usize){
-        self.ptr += amount;
+    // Reports the error with the line of the instruction at index, an invalid instruction may be cut off before its line
+    fn fail(&mut self, code: &[u8], index: usize, err: Error) -> Option<Status> {
+        self.line = match index + INSTRUCTION_SIZE <= code.len() {
+            true => decode_line(code, index),
+            false => 0
+        };
+        self.error(&err);
+        self.error = Some(err);
+        None
     }
 
     fn error(&self, msg: &str) {